@@ -1,16 +1,31 @@
 CREATE TABLE IF NOT EXISTS users (
   id SERIAL PRIMARY KEY,
-  username VARCHAR (255) NOT NULL UNIQUE,
-  email VARCHAR (255) NOT NULL UNIQUE,
+  username VARCHAR (255) NOT NULL,
+  email VARCHAR (255) NOT NULL,
   password VARCHAR (255) NOT NULL,
   status BOOLEAN DEFAULT FALSE,
-  isadmin BOOLEAN DEFAULT FALSE
+  isadmin BOOLEAN DEFAULT FALSE,
+  deleted_at TIMESTAMP DEFAULT NULL
 );
 CREATE TABLE IF NOT EXISTS hardwares (
   id SERIAL PRIMARY KEY,
   name VARCHAR (255) NOT NULL,
   type VARCHAR (255) NOT NULL,
-  description VARCHAR (255) NOT NULL
+  description VARCHAR (255) NOT NULL,
+  deleted_at TIMESTAMP DEFAULT NULL
+);
+CREATE TABLE IF NOT EXISTS firmwares (
+  id SERIAL PRIMARY KEY,
+  hardware_id INTEGER NOT NULL,
+  version VARCHAR (64) NOT NULL,
+  checksum VARCHAR (64) NOT NULL,
+  size BIGINT NOT NULL CHECK (size > 0),
+  release_notes TEXT DEFAULT NULL,
+  user_id INTEGER DEFAULT NULL,
+  created_at TIMESTAMP NOT NULL,
+  UNIQUE (hardware_id, version),
+  FOREIGN KEY (hardware_id) REFERENCES hardwares (id) ON UPDATE CASCADE ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users (id) ON UPDATE CASCADE ON DELETE SET NULL
 );
 CREATE TABLE IF NOT EXISTS nodes (
   id SERIAL PRIMARY KEY,
@@ -21,16 +36,185 @@ CREATE TABLE IF NOT EXISTS nodes (
   hardware_sensor_ids INTEGER[10] NOT NULL,
   hardware_sensor_names TEXT[10] NOT NULL,
   ispublic BOOLEAN DEFAULT false,
+  deleted_at TIMESTAMP DEFAULT NULL,
+  feeds_retention_days INTEGER DEFAULT NULL CHECK (feeds_retention_days > 0),
+  heartbeat_interval_secs INTEGER DEFAULT NULL CHECK (heartbeat_interval_secs > 0),
+  last_seen_at TIMESTAMP DEFAULT NULL,
+  last_value FLOAT[10] DEFAULT NULL,
+  latitude DOUBLE PRECISION DEFAULT NULL CHECK (latitude BETWEEN -90 AND 90),
+  longitude DOUBLE PRECISION DEFAULT NULL CHECK (longitude BETWEEN -180 AND 180),
+  altitude DOUBLE PRECISION DEFAULT NULL,
+  address VARCHAR (255) DEFAULT NULL,
+  virtual_sensor_names TEXT[] NOT NULL DEFAULT '{}',
+  virtual_sensor_expressions TEXT[] NOT NULL DEFAULT '{}',
+  firmware_id INTEGER DEFAULT NULL,
+  firmware_version VARCHAR (64) DEFAULT NULL,
+  firmware_reported_at TIMESTAMP DEFAULT NULL,
+  CHECK ((latitude IS NULL) = (longitude IS NULL)),
   FOREIGN KEY (user_id) REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
-  FOREIGN KEY (hardware_id) REFERENCES hardwares (id) ON UPDATE CASCADE ON DELETE CASCADE
+  FOREIGN KEY (hardware_id) REFERENCES hardwares (id) ON UPDATE CASCADE ON DELETE CASCADE,
+  FOREIGN KEY (firmware_id) REFERENCES firmwares (id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_username_key;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_key;
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_active_idx ON users (username) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_active_idx ON users (email) WHERE deleted_at IS NULL;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS hardwares_type_idx ON hardwares (type);
+CREATE INDEX IF NOT EXISTS hardwares_name_trgm_idx ON hardwares USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS hardwares_description_trgm_idx ON hardwares USING GIN (description gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS nodes_user_id_idx ON nodes (user_id);
+CREATE INDEX IF NOT EXISTS nodes_hardware_id_idx ON nodes (hardware_id);
+CREATE INDEX IF NOT EXISTS nodes_hardware_sensor_ids_idx ON nodes USING GIN (hardware_sensor_ids);
+CREATE INDEX IF NOT EXISTS nodes_name_trgm_idx ON nodes USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS nodes_location_trgm_idx ON nodes USING GIN (location gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS nodes_latitude_longitude_idx ON nodes (latitude, longitude);
+CREATE INDEX IF NOT EXISTS nodes_firmware_id_idx ON nodes (firmware_id);
+
+CREATE TABLE IF NOT EXISTS node_groups (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL,
+  name VARCHAR (255) NOT NULL,
+  description VARCHAR (255) DEFAULT NULL,
+  UNIQUE (user_id, name),
+  FOREIGN KEY (user_id) REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS node_group_members (
+  group_id INTEGER NOT NULL,
+  node_id INTEGER NOT NULL,
+  PRIMARY KEY (group_id, node_id),
+  FOREIGN KEY (group_id) REFERENCES node_groups (id) ON UPDATE CASCADE ON DELETE CASCADE,
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS node_group_members_node_id_idx ON node_group_members (node_id);
+CREATE TABLE IF NOT EXISTS node_tags (
+  node_id INTEGER NOT NULL,
+  key VARCHAR (64) NOT NULL,
+  value VARCHAR (255) NOT NULL,
+  PRIMARY KEY (node_id, key),
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
 );
+CREATE INDEX IF NOT EXISTS node_tags_key_value_idx ON node_tags (key, value);
 
 CREATE TABLE IF NOT EXISTS feeds (
   node_id INTEGER NOT NULL, 
   time TIMESTAMP NOT NULL, 
   value FLOAT[10] NOT null,
   FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+) PARTITION BY RANGE (time);
+CREATE TABLE IF NOT EXISTS feeds_default PARTITION OF feeds DEFAULT;
+CREATE INDEX IF NOT EXISTS feeds_node_id_time_idx ON feeds (node_id, time);
+
+CREATE TABLE IF NOT EXISTS node_anomaly_detectors (
+  node_id INTEGER NOT NULL,
+  sensor INTEGER NOT NULL CHECK (sensor >= 0),
+  method VARCHAR (16) NOT NULL,
+  window_size INTEGER NOT NULL CHECK (window_size > 0),
+  threshold DOUBLE PRECISION NOT NULL,
+  alpha DOUBLE PRECISION NOT NULL,
+  PRIMARY KEY (node_id, sensor, method),
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS node_group_anomaly_detectors (
+  group_id INTEGER NOT NULL,
+  sensor INTEGER NOT NULL CHECK (sensor >= 0),
+  method VARCHAR (16) NOT NULL,
+  window_size INTEGER NOT NULL CHECK (window_size > 0),
+  threshold DOUBLE PRECISION NOT NULL,
+  alpha DOUBLE PRECISION NOT NULL,
+  PRIMARY KEY (group_id, sensor, method),
+  FOREIGN KEY (group_id) REFERENCES node_groups (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS feed_anomalies (
+  node_id INTEGER NOT NULL,
+  time TIMESTAMP NOT NULL,
+  sensor INTEGER NOT NULL,
+  method VARCHAR (16) NOT NULL,
+  value DOUBLE PRECISION NOT NULL,
+  score DOUBLE PRECISION NOT NULL,
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
 );
+CREATE INDEX IF NOT EXISTS feed_anomalies_node_id_time_idx ON feed_anomalies (node_id, time);
+
+CREATE TABLE IF NOT EXISTS node_commands (
+  id SERIAL PRIMARY KEY,
+  node_id INTEGER NOT NULL,
+  user_id INTEGER DEFAULT NULL,
+  command VARCHAR (64) NOT NULL,
+  payload TEXT DEFAULT NULL,
+  status VARCHAR (16) NOT NULL DEFAULT 'queued',
+  result TEXT DEFAULT NULL,
+  created_at TIMESTAMP NOT NULL,
+  delivered_at TIMESTAMP DEFAULT NULL,
+  acked_at TIMESTAMP DEFAULT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users (id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+CREATE INDEX IF NOT EXISTS node_commands_node_id_status_idx ON node_commands (node_id, status);
+CREATE INDEX IF NOT EXISTS node_commands_created_at_idx ON node_commands (created_at);
+
+CREATE TABLE IF NOT EXISTS node_shadows (
+  node_id INTEGER PRIMARY KEY,
+  desired JSONB NOT NULL DEFAULT '{}',
+  reported JSONB NOT NULL DEFAULT '{}',
+  desired_version INTEGER NOT NULL DEFAULT 0,
+  reported_version INTEGER NOT NULL DEFAULT 0,
+  desired_updated_at TIMESTAMP DEFAULT NULL,
+  reported_updated_at TIMESTAMP DEFAULT NULL,
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS node_locations (
+  node_id INTEGER NOT NULL,
+  time TIMESTAMP NOT NULL,
+  latitude DOUBLE PRECISION NOT NULL CHECK (latitude BETWEEN -90 AND 90),
+  longitude DOUBLE PRECISION NOT NULL CHECK (longitude BETWEEN -180 AND 180),
+  altitude DOUBLE PRECISION DEFAULT NULL,
+  PRIMARY KEY (node_id, time),
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS feeds_hourly (
+  node_id INTEGER NOT NULL,
+  bucket TIMESTAMP NOT NULL,
+  sensor INTEGER NOT NULL,
+  min FLOAT NOT NULL,
+  max FLOAT NOT NULL,
+  avg FLOAT NOT NULL,
+  count BIGINT NOT NULL,
+  PRIMARY KEY (node_id, bucket, sensor),
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS feeds_daily (
+  node_id INTEGER NOT NULL,
+  bucket TIMESTAMP NOT NULL,
+  sensor INTEGER NOT NULL,
+  min FLOAT NOT NULL,
+  max FLOAT NOT NULL,
+  avg FLOAT NOT NULL,
+  count BIGINT NOT NULL,
+  PRIMARY KEY (node_id, bucket, sensor),
+  FOREIGN KEY (node_id) REFERENCES nodes (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+  id BIGSERIAL PRIMARY KEY,
+  time TIMESTAMP NOT NULL,
+  user_id INTEGER,
+  ip INET,
+  action VARCHAR (32) NOT NULL,
+  target_type VARCHAR (32) NOT NULL,
+  target_id INTEGER,
+  diff JSONB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audit_log_time_idx ON audit_log (time);
+CREATE INDEX IF NOT EXISTS audit_log_user_id_idx ON audit_log (user_id);
+CREATE INDEX IF NOT EXISTS audit_log_target_idx ON audit_log (target_type, target_id);
+CREATE OR REPLACE RULE audit_log_no_update AS ON UPDATE TO audit_log DO INSTEAD NOTHING;
+CREATE OR REPLACE RULE audit_log_no_delete AS ON DELETE TO audit_log DO INSTEAD NOTHING;
 
 
 insert into users (username, email, password, status, isadmin) values ('admin', 'admin@example.com', '$2b$12$dqY4QSddDD5ouFcRzHg4b.iQjoccxzl//IjHuFKHvQ3wfL1chQdua', true, true);
@@ -1156,4 +1340,6 @@ insert into feeds (node_id, time, value) values (64, '2022-03-15T17:01:23Z', '{"
 insert into feeds (node_id, time, value) values (87, '2022-06-28T00:46:56Z', '{"231.3","563.22","132.54","967.97","452.76","634.94","636.32","279.8","374.56","482.96"}');
 insert into feeds (node_id, time, value) values (57, '2023-02-27T15:07:52Z', '{"246.07","475.61","573.48","148.44","325.74","676.9","751.18","860.86","401.04","758.84"}');
 insert into feeds (node_id, time, value) values (38, '2022-10-07T20:55:40Z', '{"328.18","217.64","209.89","828.1","740.79","787.76","530.52","801.57","317.35","527.72"}');
-insert into feeds (node_id, time, value) values (17, '2022-02-10T19:03:22Z', '{"137.01","947.99","191.1","406.74","437.07","888.39","942.67","923.21","980.31","772.19"}');
\ No newline at end of file
+insert into feeds (node_id, time, value) values (17, '2022-02-10T19:03:22Z', '{"137.01","947.99","191.1","406.74","437.07","888.39","942.67","923.21","980.31","772.19"}');
+
+UPDATE nodes n SET last_seen_at = f.time, last_value = f.value FROM (SELECT DISTINCT ON (node_id) node_id, time, value FROM feeds ORDER BY node_id, time DESC) f WHERE n.id = f.node_id;
@@ -1,6 +1,8 @@
 #!/bin/bash
 set -e
 
+export DB_PASSWORD="${DB_PASSWORD:-password}"
+
 ./reset_database.sh
 RUSTFLAGS="-C target-cpu=native" cargo build --release
 ./target/release/iot-server-api
@@ -1,10 +1,213 @@
+use std::{cell::Cell, time::Instant};
+
 use deadpool_postgres::Pool;
-use ntex::http::{Method, Request, Response};
+use ntex::http::{Method, Request, Response, StatusCode};
 use ntex::service::{Service, ServiceCtx, ServiceFactory};
 use ntex::web::Error;
 
+use tracing::Instrument;
+
+use crate::{constant::config, logging, metrics};
+
+#[derive(Clone, Copy)]
+pub enum Route {
+    GetUsers,
+    PostSignup,
+    PostLogin,
+    ForgotPassword,
+    ChangePassword,
+    GetDeletedUsers,
+    RestoreUser,
+    GetUserById,
+    DeleteUser,
+    ActivateUser,
+    GetHardwares,
+    PostHardwares,
+    GetDeletedHardwares,
+    RestoreHardware,
+    GetHardwareById,
+    UpdateHardware,
+    DeleteHardware,
+    GetFirmwares,
+    PostFirmware,
+    GetFirmwareReport,
+    RolloutFirmware,
+    GetFirmwareById,
+    DeleteFirmware,
+    GetGroups,
+    PostGroup,
+    ExportGroupFeeds,
+    AddGroupMembers,
+    RemoveGroupMembers,
+    PostGroupCommand,
+    PutGroupDetectors,
+    GetGroupById,
+    UpdateGroup,
+    DeleteGroup,
+    GetNodes,
+    PostNodes,
+    GetDeletedNodes,
+    GetNodesGeojson,
+    AssignTags,
+    RestoreNode,
+    ExportFeeds,
+    ImportFeeds,
+    GetNodeTrack,
+    GetDetectors,
+    PutDetectors,
+    GetAnomalies,
+    GetCommands,
+    PostCommand,
+    GetShadow,
+    UpdateDesiredShadow,
+    GetCompleteness,
+    GetNodeTags,
+    GetNodeById,
+    UpdateNode,
+    DeleteNode,
+    AddFeed,
+    PollCommands,
+    AckCommand,
+    GetDeviceShadow,
+    ReportShadow,
+    CheckFirmware,
+    DownloadFirmware,
+    GetAuditLog,
+    Healthz,
+    Readyz,
+    Metrics,
+}
+
+pub const ROUTE_COUNT: usize = 65;
+
+pub static ROUTES: [(Method, &str, Route); ROUTE_COUNT] = [
+    (Method::GET, "/users/", Route::GetUsers),
+    (Method::POST, "/users/signup/", Route::PostSignup),
+    (Method::POST, "/users/login/", Route::PostLogin),
+    (
+        Method::POST,
+        "/users/forgot-password/",
+        Route::ForgotPassword,
+    ),
+    (
+        Method::PUT,
+        "/users/change-password/",
+        Route::ChangePassword,
+    ),
+    (Method::GET, "/users/deleted/", Route::GetDeletedUsers),
+    (Method::PUT, "/users/{id}/restore/", Route::RestoreUser),
+    (Method::GET, "/users/{id}/", Route::GetUserById),
+    (Method::DELETE, "/users/{id}/", Route::DeleteUser),
+    (Method::GET, "/activate/{token}/", Route::ActivateUser),
+    (Method::GET, "/hardwares/", Route::GetHardwares),
+    (Method::POST, "/hardwares/", Route::PostHardwares),
+    (
+        Method::GET,
+        "/hardwares/deleted/",
+        Route::GetDeletedHardwares,
+    ),
+    (
+        Method::PUT,
+        "/hardwares/{id}/restore/",
+        Route::RestoreHardware,
+    ),
+    (Method::GET, "/hardwares/{id}/", Route::GetHardwareById),
+    (Method::PUT, "/hardwares/{id}/", Route::UpdateHardware),
+    (Method::DELETE, "/hardwares/{id}/", Route::DeleteHardware),
+    (
+        Method::GET,
+        "/hardwares/{id}/firmwares/",
+        Route::GetFirmwares,
+    ),
+    (
+        Method::POST,
+        "/hardwares/{id}/firmwares/",
+        Route::PostFirmware,
+    ),
+    (Method::GET, "/firmwares/report/", Route::GetFirmwareReport),
+    (
+        Method::POST,
+        "/firmwares/{id}/rollout/",
+        Route::RolloutFirmware,
+    ),
+    (Method::GET, "/firmwares/{id}/", Route::GetFirmwareById),
+    (Method::DELETE, "/firmwares/{id}/", Route::DeleteFirmware),
+    (Method::GET, "/groups/", Route::GetGroups),
+    (Method::POST, "/groups/", Route::PostGroup),
+    (
+        Method::GET,
+        "/groups/{id}/feeds/export",
+        Route::ExportGroupFeeds,
+    ),
+    (Method::POST, "/groups/{id}/nodes/", Route::AddGroupMembers),
+    (
+        Method::DELETE,
+        "/groups/{id}/nodes/",
+        Route::RemoveGroupMembers,
+    ),
+    (
+        Method::POST,
+        "/groups/{id}/commands/",
+        Route::PostGroupCommand,
+    ),
+    (
+        Method::PUT,
+        "/groups/{id}/detectors/",
+        Route::PutGroupDetectors,
+    ),
+    (Method::GET, "/groups/{id}/", Route::GetGroupById),
+    (Method::PUT, "/groups/{id}/", Route::UpdateGroup),
+    (Method::DELETE, "/groups/{id}/", Route::DeleteGroup),
+    (Method::GET, "/nodes/", Route::GetNodes),
+    (Method::POST, "/nodes/", Route::PostNodes),
+    (Method::GET, "/nodes/deleted/", Route::GetDeletedNodes),
+    (Method::GET, "/nodes/geojson/", Route::GetNodesGeojson),
+    (Method::PUT, "/nodes/tags/", Route::AssignTags),
+    (Method::PUT, "/nodes/{id}/restore/", Route::RestoreNode),
+    (Method::GET, "/nodes/{id}/feeds/export", Route::ExportFeeds),
+    (Method::POST, "/nodes/{id}/feeds/import", Route::ImportFeeds),
+    (Method::GET, "/nodes/{id}/track/", Route::GetNodeTrack),
+    (Method::GET, "/nodes/{id}/detectors/", Route::GetDetectors),
+    (Method::PUT, "/nodes/{id}/detectors/", Route::PutDetectors),
+    (Method::GET, "/nodes/{id}/anomalies/", Route::GetAnomalies),
+    (Method::GET, "/nodes/{id}/commands/", Route::GetCommands),
+    (Method::POST, "/nodes/{id}/commands/", Route::PostCommand),
+    (Method::GET, "/nodes/{id}/shadow/", Route::GetShadow),
+    (
+        Method::PUT,
+        "/nodes/{id}/shadow/",
+        Route::UpdateDesiredShadow,
+    ),
+    (
+        Method::GET,
+        "/nodes/{id}/completeness/",
+        Route::GetCompleteness,
+    ),
+    (Method::GET, "/nodes/{id}/tags/", Route::GetNodeTags),
+    (Method::GET, "/nodes/{id}/", Route::GetNodeById),
+    (Method::PUT, "/nodes/{id}/", Route::UpdateNode),
+    (Method::DELETE, "/nodes/{id}/", Route::DeleteNode),
+    (Method::POST, "/channel/", Route::AddFeed),
+    (Method::GET, "/channel/commands/", Route::PollCommands),
+    (Method::POST, "/channel/commands/ack/", Route::AckCommand),
+    (Method::GET, "/channel/shadow/", Route::GetDeviceShadow),
+    (Method::PUT, "/channel/shadow/", Route::ReportShadow),
+    (Method::GET, "/channel/firmware/", Route::CheckFirmware),
+    (
+        Method::GET,
+        "/channel/firmware/download/",
+        Route::DownloadFirmware,
+    ),
+    (Method::GET, "/audit/", Route::GetAuditLog),
+    (Method::GET, "/healthz", Route::Healthz),
+    (Method::GET, "/readyz", Route::Readyz),
+    (Method::GET, "/metrics", Route::Metrics),
+];
+
 pub struct App {
     pub pool: Pool,
+    pub replica: Option<Pool>,
+    pub health_only: bool,
 }
 
 impl Service<Request> for App {
@@ -12,48 +215,128 @@ impl Service<Request> for App {
     type Error = Error;
 
     async fn call(&self, req: Request, _: ServiceCtx<'_, Self>) -> Result<Response, Error> {
-        match (req.path(), req.method()) {
-            ("/users/", &Method::GET) => self.handle_get_users(req).await,
-            ("/users/signup/", &Method::POST) => self.handle_post_signup(req).await,
-            ("/users/login/", &Method::POST) => self.handle_post_login(req).await,
-            ("/users/forgot-password/", &Method::POST) => self.handle_forgot_password(req).await,
-            ("/users/change-password/", &Method::PUT) => self.handle_change_password(req).await,
-            _ if req.path().starts_with("/users/") => match req.method() {
-                &Method::GET => self.handle_get_user_by_id(req).await,
-                _ => self.handle_not_found(req).await,
-            },
-            _ if req.path().starts_with("/activate/") => match req.method() {
-                &Method::GET => self.handle_activate_user(req).await,
-                _ => self.handle_not_found(req).await,
-            },
-
-            ("/hardwares/", &Method::GET) => self.handle_get_hardwares(req).await,
-            ("/hardwares/", &Method::POST) => self.handle_post_hardwares(req).await,
-            _ if req.path().starts_with("/hardwares/") => match req.method() {
-                &Method::GET => self.handle_get_hardware_by_id(req).await,
-                &Method::PUT => self.handle_update_hardware(req).await,
-                &Method::DELETE => self.handle_delete_hardware(req).await,
-                _ => self.handle_not_found(req).await,
-            },
+        let start = Instant::now();
+        let route = metrics::route(req.method(), req.path());
+        let method = req.method().clone();
+        let request_id = logging::request_id(&req);
+        let span = tracing::info_span!(
+            "request",
+            request_id = request_id.to_str().unwrap_or_default(),
+        );
 
-            ("/nodes/", &Method::GET) => self.handle_get_nodes(req).await,
-            ("/nodes/", &Method::POST) => self.handle_post_nodes(req).await,
-            _ if req.path().starts_with("/nodes/") => match req.method() {
-                &Method::GET => self.handle_get_node_by_id(req).await,
-                &Method::PUT => self.handle_update_node(req).await,
-                &Method::DELETE => self.handle_delete_node(req).await,
-                _ => self.handle_not_found(req).await,
-            },
+        let (mut result, user_id) = logging::CURRENT_USER
+            .scope(Cell::new(None), async {
+                let result = self.dispatch(req, route).await;
+                (result, logging::CURRENT_USER.with(Cell::get))
+            })
+            .instrument(span)
+            .await;
 
-            ("/channel/", &Method::POST) => self.handle_add_feed(req).await,
+        let elapsed = start.elapsed();
+        let status = match &mut result {
+            Ok(res) => {
+                res.headers_mut()
+                    .insert(logging::HDR_REQUEST_ID, request_id.clone());
+                res.status()
+            }
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        metrics::record_request(route, status, elapsed);
+        tracing::info!(
+            target: "access",
+            method = method.as_str(),
+            route = metrics::route_name(route),
+            status = status.as_u16(),
+            latency_ms = elapsed.as_secs_f64() * 1000.0,
+            user_id,
+            request_id = request_id.to_str().unwrap_or_default(),
+        );
+        result
+    }
+}
 
-            _ => self.handle_not_found(req).await,
+impl App {
+    async fn dispatch(&self, req: Request, route: usize) -> Result<Response, Error> {
+        if self.health_only && !matches!(req.path(), "/healthz" | "/readyz" | "/metrics") {
+            return self.handle_not_found(req).await;
+        }
+        let Some(&(_, _, route)) = ROUTES.get(route) else {
+            return self.handle_not_found(req).await;
+        };
+        match route {
+            Route::GetUsers => self.handle_get_users(req).await,
+            Route::PostSignup => self.handle_post_signup(req).await,
+            Route::PostLogin => self.handle_post_login(req).await,
+            Route::ForgotPassword => self.handle_forgot_password(req).await,
+            Route::ChangePassword => self.handle_change_password(req).await,
+            Route::GetDeletedUsers => self.handle_get_deleted_users(req).await,
+            Route::RestoreUser => self.handle_restore_user(req).await,
+            Route::GetUserById => self.handle_get_user_by_id(req).await,
+            Route::DeleteUser => self.handle_delete_user(req).await,
+            Route::ActivateUser => self.handle_activate_user(req).await,
+            Route::GetHardwares => self.handle_get_hardwares(req).await,
+            Route::PostHardwares => self.handle_post_hardwares(req).await,
+            Route::GetDeletedHardwares => self.handle_get_deleted_hardwares(req).await,
+            Route::RestoreHardware => self.handle_restore_hardware(req).await,
+            Route::GetHardwareById => self.handle_get_hardware_by_id(req).await,
+            Route::UpdateHardware => self.handle_update_hardware(req).await,
+            Route::DeleteHardware => self.handle_delete_hardware(req).await,
+            Route::GetFirmwares => self.handle_get_firmwares(req).await,
+            Route::PostFirmware => self.handle_post_firmware(req).await,
+            Route::GetFirmwareReport => self.handle_get_firmware_report(req).await,
+            Route::RolloutFirmware => self.handle_rollout_firmware(req).await,
+            Route::GetFirmwareById => self.handle_get_firmware_by_id(req).await,
+            Route::DeleteFirmware => self.handle_delete_firmware(req).await,
+            Route::GetGroups => self.handle_get_groups(req).await,
+            Route::PostGroup => self.handle_post_group(req).await,
+            Route::ExportGroupFeeds => self.handle_export_group_feeds(req).await,
+            Route::AddGroupMembers => self.handle_add_group_members(req).await,
+            Route::RemoveGroupMembers => self.handle_remove_group_members(req).await,
+            Route::PostGroupCommand => self.handle_post_group_command(req).await,
+            Route::PutGroupDetectors => self.handle_put_group_detectors(req).await,
+            Route::GetGroupById => self.handle_get_group_by_id(req).await,
+            Route::UpdateGroup => self.handle_update_group(req).await,
+            Route::DeleteGroup => self.handle_delete_group(req).await,
+            Route::GetNodes => self.handle_get_nodes(req).await,
+            Route::PostNodes => self.handle_post_nodes(req).await,
+            Route::GetDeletedNodes => self.handle_get_deleted_nodes(req).await,
+            Route::GetNodesGeojson => self.handle_get_nodes_geojson(req).await,
+            Route::AssignTags => self.handle_assign_tags(req).await,
+            Route::RestoreNode => self.handle_restore_node(req).await,
+            Route::ExportFeeds => self.handle_export_feeds(req).await,
+            Route::ImportFeeds => self.handle_import_feeds(req).await,
+            Route::GetNodeTrack => self.handle_get_node_track(req).await,
+            Route::GetDetectors => self.handle_get_detectors(req).await,
+            Route::PutDetectors => self.handle_put_detectors(req).await,
+            Route::GetAnomalies => self.handle_get_anomalies(req).await,
+            Route::GetCommands => self.handle_get_commands(req).await,
+            Route::PostCommand => self.handle_post_command(req).await,
+            Route::GetShadow => self.handle_get_shadow(req).await,
+            Route::UpdateDesiredShadow => self.handle_update_desired_shadow(req).await,
+            Route::GetCompleteness => self.handle_get_completeness(req).await,
+            Route::GetNodeTags => self.handle_get_node_tags(req).await,
+            Route::GetNodeById => self.handle_get_node_by_id(req).await,
+            Route::UpdateNode => self.handle_update_node(req).await,
+            Route::DeleteNode => self.handle_delete_node(req).await,
+            Route::AddFeed => self.handle_add_feed(req).await,
+            Route::PollCommands => self.handle_poll_commands(req).await,
+            Route::AckCommand => self.handle_ack_command(req).await,
+            Route::GetDeviceShadow => self.handle_get_device_shadow(req).await,
+            Route::ReportShadow => self.handle_report_shadow(req).await,
+            Route::CheckFirmware => self.handle_check_firmware(req).await,
+            Route::DownloadFirmware => self.handle_download_firmware(req).await,
+            Route::GetAuditLog => self.handle_get_audit_log(req).await,
+            Route::Healthz => self.handle_healthz(req).await,
+            Route::Readyz => self.handle_readyz(req).await,
+            Route::Metrics => self.handle_metrics(req).await,
         }
     }
 }
 
 pub struct AppFactory {
     pub pool: std::sync::Arc<Pool>,
+    pub replica: Option<std::sync::Arc<Pool>>,
+    pub health_only: bool,
 }
 
 impl ServiceFactory<Request> for AppFactory {
@@ -63,8 +346,15 @@ impl ServiceFactory<Request> for AppFactory {
     type InitError = ();
 
     async fn create(&self, _: ()) -> Result<Self::Service, Self::InitError> {
+        if config::DB_WARMUP_STATEMENTS && !self.health_only {
+            if let Err(e) = self.pool.get().await {
+                tracing::warn!(error = %e, "Statement warmup skipped");
+            }
+        }
         Ok(App {
             pool: self.pool.as_ref().clone(),
+            replica: self.replica.as_deref().cloned(),
+            health_only: self.health_only,
         })
     }
 }
@@ -0,0 +1,62 @@
+use std::{
+    io::{self, Result as IoResult},
+    path::Path,
+};
+
+use deadpool_postgres::Pool;
+use futures::stream;
+use ntex::util::Bytes;
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::{database::feeds, models::feeds::ImportFormat, tasks::rollup};
+
+static USAGE: &str = "usage: iot-server-api import-feeds <node_id> <path> [csv|ndjson]";
+
+pub async fn run(pool: Pool, args: &[String]) -> IoResult<()> {
+    match args.first().map(String::as_str) {
+        Some("import-feeds") => import_feeds(pool, &args[1..]).await,
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, USAGE)),
+    }
+}
+
+async fn import_feeds(pool: Pool, args: &[String]) -> IoResult<()> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, USAGE);
+    let node_id: i32 = args
+        .first()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(invalid)?;
+    let path = args.get(1).ok_or_else(invalid)?;
+    let format = match args.get(2).map(String::as_str).or_else(|| {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+    }) {
+        Some("csv") => ImportFormat::Csv,
+        Some("ndjson") | Some("jsonl") => ImportFormat::Ndjson,
+        _ => return Err(invalid()),
+    };
+
+    let file = File::open(path).await?;
+    let body = Box::pin(stream::unfold(file, |mut file| async move {
+        let mut buf = vec![0; 64 * 1024];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    }));
+
+    let client = pool.get().await.map_err(io::Error::other)?;
+    let (data, status) = feeds::import_feeds(&client, node_id, format, None, body).await;
+    println!("{}", String::from_utf8_lossy(&data));
+    if status.is_success() {
+        rollup::flush(&pool)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    } else {
+        Err(io::Error::other(status.to_string()))
+    }
+}
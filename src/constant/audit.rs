@@ -0,0 +1,14 @@
+pub static CREATE: &str = "create";
+pub static UPDATE: &str = "update";
+pub static DELETE: &str = "delete";
+pub static RESTORE: &str = "restore";
+pub static LOGIN: &str = "login";
+pub static LOGIN_FAILED: &str = "login_failed";
+pub static ACTIVATE: &str = "activate";
+pub static PASSWORD_RESET: &str = "password_reset";
+pub static PASSWORD_CHANGE: &str = "password_change";
+pub static TARGET_USER: &str = "user";
+pub static TARGET_HARDWARE: &str = "hardware";
+pub static TARGET_NODE: &str = "node";
+pub static TARGET_FIRMWARE: &str = "firmware";
+pub static TARGET_GROUP: &str = "group";
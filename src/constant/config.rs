@@ -5,3 +5,95 @@ pub static EMAIL_USERNAME: &str = "rustiotserver@gmail.com";
 pub static EMAIL_PASSWORD: &str = "susc nhxi dgfj akst";
 pub static EMAIL_RELAY: &str = "smtp.gmail.com";
 pub static ENVIROMENT: &str = "development";
+pub static SOFT_DELETE_RETENTION_DAYS: i64 = 30;
+pub static PURGE_INTERVAL_SECS: u16 = 60 * 60;
+pub static DELETED_USERS_REFRESH_SECS: u16 = 30;
+pub static AUDIT_LOG_DEFAULT_LIMIT: i64 = 100;
+pub static AUDIT_LOG_MAX_LIMIT: i64 = 1000;
+pub static EXPORT_FETCH_SIZE: i32 = 5000;
+pub static IMPORT_MAX_LINE_BYTES: usize = 64 * 1024;
+pub static IMPORT_MAX_REPORTED_REJECTS: usize = 100;
+pub static RESPONSE_INITIAL_CAPACITY: usize = 1024;
+pub static STREAM_CHUNK_SIZE: usize = 64 * 1024;
+pub static MAX_PAYLOAD_BYTES: usize = 256 * 1024;
+pub static CLIENT_TIMEOUT_SECS: u16 = 3;
+pub static HEADERS_READ_MAX_TIMEOUT_SECS: u16 = 5;
+pub static HEADERS_READ_RATE: u16 = 256;
+pub static PAYLOAD_READ_TIMEOUT_SECS: u16 = 5;
+pub static PAYLOAD_READ_MAX_TIMEOUT_SECS: u16 = 0;
+pub static PAYLOAD_READ_RATE: u16 = 1024;
+pub static READYZ_TIMEOUT_MS: u64 = 1000;
+pub static LOG_LEVEL: &str = "info";
+pub static REQUEST_ID_LEN: usize = 16;
+pub static REQUEST_ID_MAX_LEN: usize = 128;
+pub static SHUTDOWN_TIMEOUT_SECS: u16 = 30;
+pub static MAIL_DRAIN_TIMEOUT_SECS: u64 = 10;
+pub static MAIL_TIMEOUT_SECS: u64 = 10;
+pub static HTTP_ADDR: &str = "0.0.0.0:8080";
+pub static HTTPS_ADDR: &str = "0.0.0.0:8443";
+pub static HEALTH_ADDR: Option<&str> = Some("127.0.0.1:8081");
+pub static TLS_CERT_PATH: &str = "certs/cert.pem";
+pub static TLS_KEY_PATH: &str = "certs/key.pem";
+pub static TLS_RELOAD_INTERVAL_SECS: u16 = 30;
+pub static DB_HOST: &str = "localhost";
+pub static DB_PORT: u16 = 5432;
+pub static DB_NAME: &str = "rustdemo";
+pub static DB_USER: &str = "postgres";
+pub static DB_USER_ENV: &str = "DB_USER";
+pub static DB_PASSWORD_ENV: &str = "DB_PASSWORD";
+pub static DB_PASSWORD_FILE_ENV: &str = "DB_PASSWORD_FILE";
+pub static DB_TLS_CA_PATH: Option<&str> = None;
+pub static DB_CONNECT_TIMEOUT_SECS: u64 = 5;
+pub static DB_STATEMENT_TIMEOUT_MS: u64 = 30_000;
+pub static DB_POOL_MAX_SIZE: usize = 50;
+pub static DB_POOL_WAIT_TIMEOUT_MS: u64 = 2000;
+pub static DB_WARMUP_STATEMENTS: bool = true;
+pub static DB_REPLICA_HOST: Option<&str> = None;
+pub static DB_REPLICA_PORT: u16 = 5432;
+pub static DB_REPLICA_MAX_LAG_MS: u64 = 5000;
+pub static DB_REPLICA_CHECK_INTERVAL_SECS: u16 = 5;
+pub static FEEDS_RETENTION_DAYS: Option<i32> = None;
+pub static FEEDS_PARTITIONS_AHEAD_MONTHS: u32 = 2;
+pub static PARTITION_INTERVAL_SECS: u16 = 60 * 60;
+pub static ROLLUP_INTERVAL_SECS: u16 = 60;
+pub static ROLLUP_BATCH_SIZE: usize = 1000;
+pub static ROLLUP_HOURLY_MIN_RANGE_HOURS: i64 = 48;
+pub static ROLLUP_DAILY_MIN_RANGE_DAYS: i64 = 60;
+pub static NODE_HEARTBEAT_INTERVAL_SECS: i32 = 5 * 60;
+pub static LIST_MAX_LIMIT: i64 = 1000;
+pub static NEAR_DEFAULT_RADIUS_KM: f64 = 10.0;
+pub static NEAR_MAX_RADIUS_KM: f64 = 20_000.0;
+pub static VIRTUAL_SENSOR_MAX_EXPRESSION_LEN: usize = 256;
+pub static VIRTUAL_SENSOR_MAX_DEPTH: usize = 32;
+pub static ANOMALY_ZSCORE_WINDOW: i32 = 30;
+pub static ANOMALY_ZSCORE_THRESHOLD: f64 = 3.0;
+pub static ANOMALY_EWMA_WARMUP: i32 = 10;
+pub static ANOMALY_EWMA_THRESHOLD: f64 = 3.0;
+pub static ANOMALY_EWMA_ALPHA: f64 = 0.1;
+pub static ANOMALY_FLATLINE_WINDOW: i32 = 10;
+pub static ANOMALY_FLATLINE_TOLERANCE: f64 = 0.0;
+pub static ANOMALY_MAX_WINDOW: i32 = 1000;
+pub static ANOMALY_ALERT_COOLDOWN_SECS: u64 = 15 * 60;
+pub static ANOMALY_MAX_CACHED_NODES: usize = 16 * 1024;
+pub static COMPLETENESS_DEFAULT_RANGE_HOURS: i64 = 24;
+pub static COMPLETENESS_MAX_RANGE_DAYS: i64 = 366;
+pub static COMPLETENESS_GAP_TOLERANCE: f64 = 1.5;
+pub static COMMAND_MAX_NAME_LEN: usize = 64;
+pub static COMMAND_MAX_PAYLOAD_BYTES: usize = 4096;
+pub static COMMAND_DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+pub static COMMAND_MAX_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+pub static COMMAND_REDELIVERY_SECS: i64 = 5 * 60;
+pub static COMMAND_DELIVERY_BATCH: i64 = 16;
+pub static COMMAND_MAX_WAIT_SECS: u64 = 60;
+pub static COMMAND_POLL_INTERVAL_SECS: u64 = 5;
+pub static COMMAND_EXPIRY_INTERVAL_SECS: u16 = 60;
+pub static COMMAND_HISTORY_RETENTION_DAYS: i64 = 90;
+pub static SHADOW_MAX_BYTES: usize = 8192;
+pub static FIRMWARE_DIR: &str = "firmware";
+pub static FIRMWARE_MAX_BYTES: u64 = 32 * 1024 * 1024;
+pub static FIRMWARE_MAX_VERSION_LEN: usize = 64;
+pub static FIRMWARE_MAX_NOTES_LEN: usize = 4096;
+pub static TAG_MAX_KEY_LEN: usize = 64;
+pub static TAG_MAX_VALUE_LEN: usize = 255;
+pub static TAG_MAX_PER_REQUEST: usize = 32;
+pub static GROUP_MAX_NAME_LEN: usize = 255;
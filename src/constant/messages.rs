@@ -14,6 +14,45 @@ pub static HARDWARE_NOT_FOUND: &str = "Hardware not found";
 pub static SENSOR_NOT_FOUND: &str = "Sensor not found";
 pub static NODE_NOT_FOUND: &str = "Node not found";
 pub static NOT_FOUND: &str = "Not found";
+pub static DATABASE_UNAVAILABLE: &str = "Database unavailable";
+pub static NOTHING_TO_RESTORE: &str = "Nothing to restore";
 pub static INVALID_PAYLOAD: &str = "Invalid payload";
+pub static INVALID_UTF8: &str = "Payload is not valid UTF-8";
+pub static PAYLOAD_TOO_LARGE: &str = "Payload too large";
+pub static UNSUPPORTED_MEDIA_TYPE: &str = "Content-Type must be application/json";
 pub static SENSOR_ID_AND_SENSOR_NAME_MUST_HAVE_SAME_LENGTH: &str =
     "Sensor id and sensor name must have the same length";
+pub static UNSUPPORTED_FORMAT: &str = "Unsupported format";
+pub static IMPORT_INVALID_HEADER: &str =
+    "Header must contain a time column and the node's sensor names";
+pub static IMPORT_INVALID_LINE: &str = "Malformed line";
+pub static IMPORT_INVALID_TIME: &str = "Missing or invalid time";
+pub static IMPORT_INVALID_VALUE: &str = "Invalid sensor value";
+pub static IMPORT_UNKNOWN_COLUMN: &str = "Unknown sensor column";
+pub static IMPORT_MISSING_VALUE: &str = "Sensor value missing before a later sensor value";
+pub static IMPORT_NO_VALUES: &str = "No sensor values";
+pub static IMPORT_LINE_TOO_LONG: &str = "Line too long";
+pub static INVALID_RETENTION_DAYS: &str = "Retention days must be positive";
+pub static INVALID_HEARTBEAT_INTERVAL: &str = "Heartbeat interval must be positive";
+pub static INVALID_COORDINATES: &str =
+    "Latitude must be within [-90, 90] and longitude within [-180, 180]";
+pub static INVALID_EXPRESSION: &str = "Invalid virtual sensor expression";
+pub static INVALID_DETECTOR: &str = "Invalid anomaly detector";
+pub static INVALID_TIME_RANGE: &str = "Invalid time range";
+pub static INVALID_VIRTUAL_SENSOR: &str =
+    "Virtual sensors may only reference physical sensors and earlier virtual sensors";
+pub static SENSOR_VALUE_COUNT_MISMATCH: &str =
+    "Nodes with virtual sensors need exactly one value per physical sensor";
+pub static INVALID_COMMAND: &str = "Invalid command";
+pub static INVALID_SHADOW: &str = "Shadow state must be a JSON object within the size limit";
+pub static SHADOW_VERSION_CONFLICT: &str = "Shadow version conflict";
+pub static INVALID_FIRMWARE: &str = "Invalid firmware version, release notes or binary";
+pub static FIRMWARE_NOT_FOUND: &str = "Firmware not found";
+pub static FIRMWARE_EXISTS: &str = "Firmware version already exists for this hardware";
+pub static RANGE_NOT_SATISFIABLE: &str = "Requested range not satisfiable";
+pub static GROUP_NOT_FOUND: &str = "Group not found";
+pub static GROUP_EXISTS: &str = "Group name already exists";
+pub static INVALID_GROUP: &str = "Invalid group name or description";
+pub static GROUP_EMPTY: &str = "Group has no nodes";
+pub static INVALID_TAGS: &str = "Invalid tag keys or values";
+pub static COMMAND_NOT_FOUND: &str = "Command not found or no longer pending";
@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod config;
 pub mod messages;
 pub mod query;
@@ -1,34 +1,142 @@
-pub static USERS_SELECT: &str = "SELECT id, username, email, status, isadmin FROM users";
-pub static USERS_INSERT: &str = "INSERT INTO users (username, email, password, status, isadmin) VALUES ($1, $2, $3, false, false)";
-pub static USERS_SELECT_BY_USERNAME: &str = "SELECT * FROM users WHERE username = $1";
+pub static USERS_SELECT: &str =
+    "SELECT id, username, email, status, isadmin FROM users WHERE deleted_at IS NULL";
+pub static USERS_INSERT: &str = "INSERT INTO users (username, email, password, status, isadmin) VALUES ($1, $2, $3, false, false) RETURNING id";
+pub static USERS_SELECT_BY_USERNAME: &str =
+    "SELECT * FROM users WHERE username = $1 AND deleted_at IS NULL";
 pub static USERS_SELECT_BY_ID: &str =
-    "SELECT id, username, email, status, isadmin FROM users WHERE id = $1;";
+    "SELECT id, username, email, status, isadmin FROM users WHERE id = $1 AND deleted_at IS NULL;";
 pub static USERS_SELECT_BY_USERNAME_AND_EMAIL: &str =
-    "SELECT * FROM users WHERE username = $1 AND email = $2";
+    "SELECT * FROM users WHERE username = $1 AND email = $2 AND deleted_at IS NULL";
 pub static USERS_UPDATE_STATUS_BY_USERNAME: &str =
-    "UPDATE users SET status = true WHERE username = $1";
+    "UPDATE users SET status = true WHERE username = $1 AND deleted_at IS NULL RETURNING id";
 pub static USERS_UPDATE_PASSWORD_BY_USERNAME: &str =
-    "UPDATE users SET password = $1 WHERE username = $2";
-pub static HARDWARES_SELECT: &str = "SELECT * FROM hardwares";
-pub static HARDWARES_SELECT_BY_ID: &str = "SELECT * FROM hardwares WHERE id = $1";
+    "UPDATE users SET password = $1 WHERE username = $2 AND deleted_at IS NULL";
+pub static USERS_DELETE_BY_ID: &str = "WITH n AS (UPDATE nodes SET deleted_at = $2 WHERE user_id = $1 AND deleted_at IS NULL) UPDATE users SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL";
+pub static USERS_SELECT_DELETED: &str =
+    "SELECT id, username, email, status, isadmin, deleted_at FROM users WHERE deleted_at IS NOT NULL";
+pub static USERS_RESTORE_BY_ID: &str = "WITH u AS (SELECT deleted_at FROM users WHERE id = $1 AND deleted_at IS NOT NULL), n AS (UPDATE nodes SET deleted_at = NULL WHERE user_id = $1 AND deleted_at = (SELECT deleted_at FROM u)) UPDATE users SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL";
+pub static USERS_PURGE: &str = "DELETE FROM users WHERE deleted_at < $1";
+pub static USERS_SELECT_DELETED_IDS: &str = "SELECT id FROM users WHERE deleted_at IS NOT NULL UNION SELECT d.id FROM unnest($1::int4[]) AS d(id) WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = d.id)";
+pub static HARDWARES_SELECT: &str = "SELECT * FROM hardwares WHERE deleted_at IS NULL";
+pub static HARDWARES_SELECT_BY_ID: &str =
+    "SELECT * FROM hardwares WHERE id = $1 AND deleted_at IS NULL";
 pub static HARDWARES_INSERT: &str =
-    "INSERT INTO hardwares (name, type, description) VALUES ($1, $2, $3)";
+    "INSERT INTO hardwares (name, type, description) VALUES ($1, $2, $3) RETURNING id";
 pub static HARDWARES_UPDATE_BY_ID: &str =
-    "UPDATE hardwares SET name = $1, type = $2, description = $3 WHERE id = $4";
-pub static HARDWARES_DELETE_BY_ID: &str = "DELETE FROM hardwares WHERE id = $1";
-pub static NODES_SELECT: &str = "SELECT * FROM nodes";
-pub static NODES_SELECT_BY_USER_OR_ISPUBLIC: &str =
-    "SELECT * FROM nodes WHERE user_id = $1 or ispublic = true";
-pub static NODES_SELECT_BY_ID: &str = "SELECT * FROM nodes WHERE id = $1";
+    "UPDATE hardwares SET name = $1, type = $2, description = $3 WHERE id = $4 AND deleted_at IS NULL";
+pub static HARDWARES_DELETE_BY_ID: &str = "WITH n AS (UPDATE nodes SET deleted_at = $2 WHERE hardware_id = $1 AND deleted_at IS NULL) UPDATE hardwares SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL";
+pub static HARDWARES_SELECT_DELETED: &str =
+    "SELECT id, name, type, description, deleted_at FROM hardwares WHERE deleted_at IS NOT NULL";
+pub static HARDWARES_RESTORE_BY_ID: &str = "WITH h AS (SELECT deleted_at FROM hardwares WHERE id = $1 AND deleted_at IS NOT NULL), n AS (UPDATE nodes SET deleted_at = NULL WHERE hardware_id = $1 AND deleted_at = (SELECT deleted_at FROM h)) UPDATE hardwares SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL";
+pub static HARDWARES_PURGE: &str = "DELETE FROM hardwares WHERE deleted_at < $1";
+pub static NODES_SELECT: &str = "SELECT * FROM nodes WHERE deleted_at IS NULL";
+pub static NODES_SELECT_PUBLIC_LOCATED: &str =
+    "SELECT * FROM nodes WHERE deleted_at IS NULL AND ispublic AND latitude IS NOT NULL";
+pub static NODES_SELECT_BY_ID: &str = "SELECT * FROM nodes WHERE id = $1 AND deleted_at IS NULL";
 pub static NODES_SELECT_BY_ID_AND_BY_USER_OR_ISPUBLIC: &str =
-    "SELECT * FROM nodes WHERE id = $1 AND (user_id = $2 OR ispublic = true)";
-pub static NODES_INSERT: &str = "INSERT INTO nodes (user_id, hardware_id, name, location, hardware_sensor_ids, hardware_sensor_names, ispublic) VALUES ($1, $2, $3, $4, $5, $6, $7)";
-pub static NODES_UPDATE_BY_ID: &str = "UPDATE nodes SET hardware_id = $1, name = $2, location = $3, hardware_sensor_ids = $4, hardware_sensor_names = $5, ispublic = $6 WHERE id = $7";
-pub static NODES_UPDATE_BY_ID_AND_USER_ID: &str = "UPDATE nodes SET hardware_id = $1, name = $2, location = $3, hardware_sensor_ids = $4, hardware_sensor_names = $5, ispublic = $6 WHERE id = $7 AND user_id = $8";
-pub static NODES_DELETE_BY_ID: &str = "DELETE FROM nodes WHERE id = $1";
+    "SELECT * FROM nodes WHERE id = $1 AND (user_id = $2 OR ispublic = true) AND deleted_at IS NULL";
+pub static NODES_INSERT: &str = "INSERT INTO nodes (user_id, hardware_id, name, location, hardware_sensor_ids, hardware_sensor_names, ispublic, feeds_retention_days, heartbeat_interval_secs, latitude, longitude, altitude, address, virtual_sensor_names, virtual_sensor_expressions) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15) RETURNING id";
+pub static NODES_UPDATE_BY_ID: &str = "UPDATE nodes SET hardware_id = $1, name = $2, location = $3, hardware_sensor_ids = $4, hardware_sensor_names = $5, ispublic = $6, feeds_retention_days = $8, heartbeat_interval_secs = $9, latitude = $10, longitude = $11, altitude = $12, address = $13, virtual_sensor_names = $14, virtual_sensor_expressions = $15 WHERE id = $7 AND deleted_at IS NULL";
+pub static NODES_UPDATE_BY_ID_AND_USER_ID: &str = "UPDATE nodes SET hardware_id = $1, name = $2, location = $3, hardware_sensor_ids = $4, hardware_sensor_names = $5, ispublic = $6, feeds_retention_days = $9, heartbeat_interval_secs = $10, latitude = $11, longitude = $12, altitude = $13, address = $14, virtual_sensor_names = $15, virtual_sensor_expressions = $16 WHERE id = $7 AND user_id = $8 AND deleted_at IS NULL";
+pub static NODES_FILTER_BBOX: &str =
+    "latitude BETWEEN $?2 AND $?4 AND longitude BETWEEN $?1 AND $?3";
+pub static NODES_FILTER_BBOX_ANTIMERIDIAN: &str =
+    "latitude BETWEEN $?2 AND $?4 AND (longitude >= $?1 OR longitude <= $?3)";
+pub static NODES_FILTER_NEAR: &str = "latitude BETWEEN $?1 - $?3 / 111.045 AND $?1 + $?3 / 111.045 AND 2 * 6371.0088 * asin(sqrt(power(sin(radians(latitude - $?1) / 2), 2) + cos(radians($?1)) * cos(radians(latitude)) * power(sin(radians(longitude - $?2) / 2), 2))) <= $?3";
+pub static NODES_FILTER_GROUP: &str =
+    "id IN (SELECT node_id FROM node_group_members WHERE group_id = $?)";
+pub static NODES_FILTER_TAG_KEY: &str = "id IN (SELECT node_id FROM node_tags WHERE key = $?)";
+pub static NODES_FILTER_TAG: &str =
+    "id IN (SELECT node_id FROM node_tags WHERE key = $?1 AND value = $?2)";
+pub static NODES_DELETE_BY_ID: &str =
+    "UPDATE nodes SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL";
 pub static NODES_DELETE_BY_ID_AND_USER_ID: &str =
-    "DELETE FROM nodes WHERE id = $1 AND user_id = $2";
-pub static FEEDS_SELECT_BY_NODE_ID: &str = "SELECT * FROM feeds WHERE node_id = $1";
-pub static FEEDS_INSERT: &str = "INSERT INTO feeds (node_id, time, value) VALUES ($1, $2, $3)";
-pub static FEEDS_SELECT_BY_NODE_IDS: &str =
-    "SELECT time, value, node_id FROM feeds WHERE node_id = ANY($1)";
+    "UPDATE nodes SET deleted_at = $3 WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL";
+pub static NODES_SELECT_DELETED: &str = "SELECT * FROM nodes WHERE deleted_at IS NOT NULL";
+pub static NODES_RESTORE_BY_ID: &str = "UPDATE nodes SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL AND EXISTS (SELECT 1 FROM hardwares h WHERE h.id = nodes.hardware_id AND h.deleted_at IS NULL) AND EXISTS (SELECT 1 FROM users u WHERE u.id = nodes.user_id AND u.deleted_at IS NULL)";
+pub static NODES_PURGE: &str = "DELETE FROM nodes WHERE deleted_at < $1 RETURNING id";
+pub static FEEDS_INSERT: &str = "WITH feed AS (INSERT INTO feeds (node_id, time, value) VALUES ($1, $2, $3) RETURNING node_id), seen AS (UPDATE nodes SET last_seen_at = $2, last_value = $3 FROM feed WHERE nodes.id = feed.node_id AND (last_seen_at IS NULL OR last_seen_at < $2)) SELECT node_id FROM feed";
+pub static FEEDS_INSERT_WITH_POSITION: &str = "WITH feed AS (INSERT INTO feeds (node_id, time, value) VALUES ($1, $2, $3) RETURNING node_id), track AS (INSERT INTO node_locations (node_id, time, latitude, longitude, altitude) SELECT node_id, $2, $4, $5, $6 FROM feed), seen AS (UPDATE nodes SET last_seen_at = $2, last_value = $3, latitude = $4, longitude = $5, altitude = $6 FROM feed WHERE nodes.id = feed.node_id AND (last_seen_at IS NULL OR last_seen_at < $2)) SELECT node_id FROM feed";
+pub static ANOMALY_DETECTORS_SELECT_BY_NODE_ID: &str = "SELECT sensor, method, window_size, threshold, alpha FROM node_anomaly_detectors WHERE node_id = $1 ORDER BY sensor, method";
+pub static ANOMALY_DETECTORS_DELETE_BY_NODE_ID: &str =
+    "DELETE FROM node_anomaly_detectors WHERE node_id = $1";
+pub static ANOMALY_DETECTORS_DELETE_OUT_OF_RANGE: &str =
+    "DELETE FROM node_anomaly_detectors WHERE node_id = $1 AND sensor >= $2";
+pub static ANOMALY_DETECTORS_INSERT: &str = "INSERT INTO node_anomaly_detectors (node_id, sensor, method, window_size, threshold, alpha) VALUES ($1, $2, $3, $4, $5, $6)";
+pub static ANOMALY_DETECTORS_SELECT_EFFECTIVE_BY_NODE_ID: &str = "SELECT sensor, method, window_size, threshold, alpha FROM node_anomaly_detectors WHERE node_id = $1 UNION ALL (SELECT DISTINCT ON (d.sensor, d.method) d.sensor, d.method, d.window_size, d.threshold, d.alpha FROM node_group_anomaly_detectors d JOIN node_group_members m ON m.group_id = d.group_id WHERE m.node_id = $1 AND NOT EXISTS (SELECT 1 FROM node_anomaly_detectors n WHERE n.node_id = $1 AND n.sensor = d.sensor AND n.method = d.method) ORDER BY d.sensor, d.method, d.group_id) ORDER BY sensor, method";
+pub static GROUP_ANOMALY_DETECTORS_DELETE_BY_GROUP_ID: &str =
+    "DELETE FROM node_group_anomaly_detectors WHERE group_id = $1";
+pub static GROUP_ANOMALY_DETECTORS_INSERT: &str = "INSERT INTO node_group_anomaly_detectors (group_id, sensor, method, window_size, threshold, alpha) VALUES ($1, $2, $3, $4, $5, $6)";
+pub static FEED_ANOMALIES_INSERT: &str = "INSERT INTO feed_anomalies (node_id, time, sensor, method, value, score) VALUES ($1, $2, $3, $4, $5, $6)";
+pub static FEED_ANOMALIES_SELECT_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT time, sensor, method, value, score FROM feed_anomalies WHERE node_id = $1 AND ($2::timestamp IS NULL OR time >= $2) AND ($3::timestamp IS NULL OR time < $3) ORDER BY time";
+pub static FEEDS_STATS_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT count(*), min(time), max(time) FROM feeds WHERE node_id = $1 AND time >= $2 AND time < $3";
+pub static FEEDS_GAPS_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT prev, time FROM (SELECT time, lag(time) OVER (ORDER BY time) AS prev FROM feeds WHERE node_id = $1 AND time >= $2 AND time < $3) t WHERE time - prev > make_interval(secs => $4) ORDER BY time";
+pub static NODE_COMMANDS_INSERT: &str = "INSERT INTO node_commands (node_id, user_id, command, payload, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, node_id, user_id, command, payload, status, result, created_at, delivered_at, acked_at, expires_at";
+pub static NODE_COMMANDS_SELECT_BY_NODE_ID: &str = "SELECT * FROM (SELECT id, node_id, user_id, command, payload, CASE WHEN status IN ('queued', 'delivered') AND expires_at <= $2 THEN 'expired' ELSE status END AS status, result, created_at, delivered_at, acked_at, expires_at FROM node_commands WHERE node_id = $1) c WHERE ($3::varchar IS NULL OR status = $3) ORDER BY id DESC LIMIT $4 OFFSET $5";
+pub static NODE_COMMANDS_DELIVER: &str = "UPDATE node_commands SET status = 'delivered', delivered_at = $2 WHERE id IN (SELECT id FROM node_commands WHERE node_id = $1 AND expires_at > $2 AND (status = 'queued' OR (status = 'delivered' AND delivered_at < $2 - make_interval(secs => $3))) ORDER BY id LIMIT $4 FOR UPDATE SKIP LOCKED) RETURNING id, node_id, user_id, command, payload, status, result, created_at, delivered_at, acked_at, expires_at";
+pub static NODE_COMMANDS_ACK: &str = "UPDATE node_commands SET status = $3, result = $4, acked_at = $5 WHERE id = $1 AND node_id = $2 AND status IN ('queued', 'delivered') AND expires_at > $5 RETURNING id, node_id, user_id, command, payload, status, result, created_at, delivered_at, acked_at, expires_at";
+pub static NODE_COMMANDS_EXPIRE: &str = "UPDATE node_commands SET status = 'expired' WHERE status IN ('queued', 'delivered') AND expires_at <= $1";
+pub static NODE_COMMANDS_RETENTION_DELETE: &str =
+    "DELETE FROM node_commands WHERE created_at < $1 AND status NOT IN ('queued', 'delivered')";
+pub static NODE_SHADOWS_SELECT_BY_NODE_ID: &str = "SELECT desired::text, reported::text, desired_version, reported_version, desired_updated_at, reported_updated_at FROM node_shadows WHERE node_id = $1";
+pub static NODE_SHADOWS_UPSERT_DESIRED: &str = "INSERT INTO node_shadows (node_id, desired, desired_version, desired_updated_at) VALUES ($1, $2::jsonb, $3 + 1, $4) ON CONFLICT (node_id) DO UPDATE SET desired = EXCLUDED.desired, desired_version = EXCLUDED.desired_version, desired_updated_at = EXCLUDED.desired_updated_at WHERE node_shadows.desired_version = $3 RETURNING desired::text, reported::text, desired_version, reported_version, desired_updated_at, reported_updated_at";
+pub static NODE_SHADOWS_UPSERT_REPORTED: &str = "INSERT INTO node_shadows (node_id, reported, reported_version, reported_updated_at) VALUES ($1, $2::jsonb, $3 + 1, $4) ON CONFLICT (node_id) DO UPDATE SET reported = EXCLUDED.reported, reported_version = EXCLUDED.reported_version, reported_updated_at = EXCLUDED.reported_updated_at WHERE node_shadows.reported_version = $3 RETURNING desired::text, reported::text, desired_version, reported_version, desired_updated_at, reported_updated_at";
+pub static FIRMWARES_INSERT: &str = "INSERT INTO firmwares (hardware_id, version, checksum, size, release_notes, user_id, created_at) SELECT id, $2, $3, $4, $5, $6, $7 FROM hardwares WHERE id = $1 AND deleted_at IS NULL RETURNING id, hardware_id, version, checksum, size, release_notes, created_at";
+pub static FIRMWARES_SELECT_BY_HARDWARE_ID: &str = "SELECT id, hardware_id, version, checksum, size, release_notes, created_at FROM firmwares WHERE hardware_id = $1 ORDER BY id DESC";
+pub static FIRMWARES_SELECT_BY_ID: &str = "SELECT id, hardware_id, version, checksum, size, release_notes, created_at FROM firmwares WHERE id = $1";
+pub static FIRMWARES_DELETE_BY_ID: &str = "DELETE FROM firmwares WHERE id = $1";
+pub static FIRMWARES_SNAPSHOT_BY_ID: &str =
+    "SELECT to_jsonb(t)::text FROM firmwares t WHERE id = $1 FOR UPDATE";
+pub static FIRMWARES_ROLLOUT: &str = "UPDATE nodes n SET firmware_id = f.id FROM firmwares f WHERE f.id = $1 AND n.hardware_id = f.hardware_id AND (n.id = ANY($2) OR n.id IN (SELECT m.node_id FROM node_group_members m JOIN node_groups g ON g.id = m.group_id WHERE m.group_id = ANY($4) AND ($3::int IS NULL OR g.user_id = $3))) AND n.deleted_at IS NULL AND ($3::int IS NULL OR n.user_id = $3) RETURNING n.id";
+pub static FIRMWARES_SELECT_ASSIGNED_BY_NODE_ID: &str = "SELECT f.id, f.hardware_id, f.version, f.checksum, f.size, f.release_notes, f.created_at FROM nodes n JOIN firmwares f ON f.id = n.firmware_id WHERE n.id = $1";
+pub static FIRMWARES_REPORT: &str = "SELECT n.hardware_id, n.firmware_version, f.version, count(*), array_agg(n.id ORDER BY n.id) FROM nodes n LEFT JOIN firmwares f ON f.id = n.firmware_id WHERE n.deleted_at IS NULL AND ($1::int IS NULL OR n.hardware_id = $1) AND ($2::int IS NULL OR n.user_id = $2) GROUP BY n.hardware_id, n.firmware_version, f.version ORDER BY n.hardware_id, n.firmware_version NULLS FIRST, f.version NULLS FIRST";
+pub static NODES_UPDATE_FIRMWARE_VERSION: &str = "UPDATE nodes SET firmware_version = $2, firmware_reported_at = $3 WHERE id = $1 RETURNING firmware_version";
+pub static NODE_COMMANDS_INSERT_MANY: &str = "INSERT INTO node_commands (node_id, user_id, command, payload, created_at, expires_at) SELECT unnest($1::int4[]), $2, $3, $4, $5, $6 RETURNING id, node_id, user_id, command, payload, status, result, created_at, delivered_at, acked_at, expires_at";
+pub static GROUPS_SELECT: &str = "SELECT g.id, g.user_id, g.name, g.description, COALESCE(array_agg(m.node_id ORDER BY m.node_id) FILTER (WHERE m.node_id IS NOT NULL), '{}') FROM node_groups g LEFT JOIN node_group_members m ON m.group_id = g.id WHERE ($1::int IS NULL OR g.user_id = $1) GROUP BY g.id ORDER BY g.id";
+pub static GROUPS_SELECT_BY_ID: &str = "SELECT g.id, g.user_id, g.name, g.description, COALESCE(array_agg(m.node_id ORDER BY m.node_id) FILTER (WHERE m.node_id IS NOT NULL), '{}') FROM node_groups g LEFT JOIN node_group_members m ON m.group_id = g.id WHERE g.id = $1 AND ($2::int IS NULL OR g.user_id = $2) GROUP BY g.id";
+pub static GROUPS_INSERT: &str =
+    "INSERT INTO node_groups (user_id, name, description) VALUES ($1, $2, $3) RETURNING id";
+pub static GROUPS_UPDATE_BY_ID: &str = "UPDATE node_groups SET name = $2, description = $3 WHERE id = $1 AND ($4::int IS NULL OR user_id = $4)";
+pub static GROUPS_DELETE_BY_ID: &str = "DELETE FROM node_groups WHERE id = $1 AND ($2::int IS NULL OR user_id = $2) RETURNING ARRAY(SELECT node_id FROM node_group_members WHERE group_id = node_groups.id)";
+pub static GROUPS_SNAPSHOT_BY_ID: &str =
+    "SELECT to_jsonb(t)::text FROM node_groups t WHERE id = $1 FOR UPDATE";
+pub static GROUP_MEMBERS_INSERT: &str = "WITH allowed AS (SELECT id FROM nodes WHERE id = ANY($2) AND deleted_at IS NULL AND ($3::int IS NULL OR user_id = $3)), inserted AS (INSERT INTO node_group_members (group_id, node_id) SELECT $1, id FROM allowed ON CONFLICT DO NOTHING) SELECT id FROM allowed";
+pub static GROUP_MEMBERS_DELETE: &str =
+    "DELETE FROM node_group_members WHERE group_id = $1 AND node_id = ANY($2) RETURNING node_id";
+pub static NODES_SELECT_BY_GROUP_ID: &str = "SELECT n.* FROM nodes n JOIN node_group_members m ON m.node_id = n.id WHERE m.group_id = $1 AND n.deleted_at IS NULL AND ($2::int IS NULL OR n.user_id = $2) ORDER BY n.id";
+pub static NODES_SELECT_IDS_BY_OWNER: &str = "SELECT id FROM nodes WHERE id = ANY($1) AND deleted_at IS NULL AND ($2::int IS NULL OR user_id = $2)";
+pub static NODE_TAGS_DELETE: &str =
+    "DELETE FROM node_tags WHERE node_id = ANY($1) AND key = ANY($2)";
+pub static NODE_TAGS_UPSERT: &str = "INSERT INTO node_tags (node_id, key, value) SELECT n, t.key, t.value FROM unnest($1::int4[]) AS n CROSS JOIN unnest($2::varchar[], $3::varchar[]) AS t(key, value) ON CONFLICT (node_id, key) DO UPDATE SET value = EXCLUDED.value";
+pub static NODE_TAGS_SELECT_BY_NODE_ID: &str =
+    "SELECT key, value FROM node_tags WHERE node_id = $1 ORDER BY key";
+pub static FEEDS_SELECT_BY_NODE_IDS_AND_TIME_RANGE: &str = "SELECT node_id, time, value FROM feeds WHERE node_id = ANY($1) AND ($2::timestamp IS NULL OR time >= $2) AND ($3::timestamp IS NULL OR time < $3) ORDER BY time, node_id";
+pub static NODE_LOCATIONS_SELECT_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT time, latitude, longitude, altitude FROM node_locations WHERE node_id = $1 AND ($2::timestamp IS NULL OR time >= $2) AND ($3::timestamp IS NULL OR time < $3) ORDER BY time";
+pub static USERS_SNAPSHOT_BY_ID: &str =
+    "SELECT (to_jsonb(t) - 'password')::text FROM users t WHERE id = $1 FOR UPDATE";
+pub static HARDWARES_SNAPSHOT_BY_ID: &str =
+    "SELECT to_jsonb(t)::text FROM hardwares t WHERE id = $1 FOR UPDATE";
+pub static NODES_SNAPSHOT_BY_ID: &str =
+    "SELECT to_jsonb(t)::text FROM nodes t WHERE id = $1 FOR UPDATE";
+pub static AUDIT_LOG_INSERT: &str = "INSERT INTO audit_log (time, user_id, ip, action, target_type, target_id, diff) VALUES ($1, $2, $3, $4, $5, $6, jsonb_build_object('before', $7::jsonb, 'after', $8::jsonb))";
+pub static AUDIT_LOG_SELECT: &str = "SELECT id, time, user_id, host(ip), action, target_type, target_id, diff::text FROM audit_log WHERE ($1::int IS NULL OR user_id = $1) AND ($2::text IS NULL OR action = $2) AND ($3::text IS NULL OR target_type = $3) AND ($4::int IS NULL OR target_id = $4) AND ($5::timestamp IS NULL OR time >= $5) AND ($6::timestamp IS NULL OR time < $6) ORDER BY id DESC LIMIT $7 OFFSET $8";
+pub static FEEDS_SELECT_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT time, value FROM feeds WHERE node_id = $1 AND ($2::timestamp IS NULL OR time >= $2) AND ($3::timestamp IS NULL OR time < $3) ORDER BY time";
+pub static FEEDS_COPY_IN: &str = "COPY feeds (node_id, time, value) FROM STDIN BINARY";
+pub static HEALTH_CHECK: &str = "SELECT 1";
+pub static REPLICA_LAG_MS: &str = "SELECT CASE WHEN NOT pg_is_in_recovery() OR pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) * 1000, 0) END::float8";
+pub static FEEDS_PARTITIONS: &str = "SELECT c.relname::text FROM pg_inherits i JOIN pg_class c ON c.oid = i.inhrelid WHERE i.inhparent = 'feeds'::regclass AND c.relname ~ '^feeds_p[0-9]{6}$'";
+pub static FEEDS_DEFAULT_MONTHS: &str =
+    "SELECT DISTINCT date_trunc('month', time) FROM feeds_default";
+pub static FEEDS_DEFAULT_DELETE_RANGE: &str =
+    "DELETE FROM feeds_default WHERE time >= $1 AND time < $2";
+pub static NODES_MAX_RETENTION_DAYS: &str = "SELECT max(feeds_retention_days) FROM nodes";
+pub static FEEDS_RETENTION_DELETE: &str = "DELETE FROM feeds f USING nodes n WHERE f.node_id = n.id AND f.time < $1 - make_interval(days => COALESCE(n.feeds_retention_days, $2))";
+pub static FEED_ANOMALIES_RETENTION_DELETE: &str = "DELETE FROM feed_anomalies a USING nodes n WHERE a.node_id = n.id AND a.time < $1 - make_interval(days => COALESCE(n.feeds_retention_days, $2))";
+pub static NODE_LOCATIONS_RETENTION_DELETE: &str = "DELETE FROM node_locations l USING nodes n WHERE l.node_id = n.id AND l.time < $1 - make_interval(days => COALESCE(n.feeds_retention_days, $2))";
+pub static ROLLUP_WATERMARKS: &str = "SELECT n.id, (SELECT max(h.bucket) FROM feeds_hourly h WHERE h.node_id = n.id) FROM nodes n WHERE n.last_seen_at IS NOT NULL";
+pub static ROLLUP_BACKFILL: &str = "SELECT DISTINCT f.node_id, date_trunc('hour', f.time) FROM unnest($1::int4[], $2::timestamp[]) AS w(node_id, since) JOIN feeds f ON f.node_id = w.node_id AND f.time >= COALESCE(w.since, '-infinity') WHERE ($3::timestamp IS NULL OR f.time >= $3)";
+pub static ROLLUP_HOURLY_UPSERT: &str = "INSERT INTO feeds_hourly (node_id, bucket, sensor, min, max, avg, count) SELECT d.node_id, d.bucket, v.sensor::int - 1, min(v.value), max(v.value), avg(v.value), count(*) FROM unnest($1::int4[], $2::timestamp[]) AS d(node_id, bucket) JOIN feeds f ON f.node_id = d.node_id AND f.time >= d.bucket AND f.time < d.bucket + interval '1 hour' CROSS JOIN LATERAL unnest(f.value) WITH ORDINALITY AS v(value, sensor) WHERE v.value IS NOT NULL AND v.value <> 'NaN' AND v.value NOT IN ('Infinity', '-Infinity') GROUP BY d.node_id, d.bucket, v.sensor ON CONFLICT (node_id, bucket, sensor) DO UPDATE SET min = EXCLUDED.min, max = EXCLUDED.max, avg = EXCLUDED.avg, count = EXCLUDED.count";
+pub static ROLLUP_DAILY_UPSERT: &str = "INSERT INTO feeds_daily (node_id, bucket, sensor, min, max, avg, count) SELECT d.node_id, d.bucket, h.sensor, min(h.min), max(h.max), sum(h.avg * h.count) / sum(h.count), sum(h.count)::bigint FROM unnest($1::int4[], $2::timestamp[]) AS d(node_id, bucket) JOIN feeds_hourly h ON h.node_id = d.node_id AND h.bucket >= d.bucket AND h.bucket < d.bucket + interval '1 day' GROUP BY d.node_id, d.bucket, h.sensor ON CONFLICT (node_id, bucket, sensor) DO UPDATE SET min = EXCLUDED.min, max = EXCLUDED.max, avg = EXCLUDED.avg, count = EXCLUDED.count";
+pub static FEEDS_HOURLY_SELECT_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT bucket, array_agg(sensor ORDER BY sensor), array_agg(min ORDER BY sensor), array_agg(max ORDER BY sensor), array_agg(avg ORDER BY sensor), array_agg(count ORDER BY sensor) FROM feeds_hourly WHERE node_id = $1 AND ($2::timestamp IS NULL OR bucket >= date_trunc('hour', $2)) AND ($3::timestamp IS NULL OR bucket < $3) GROUP BY bucket ORDER BY bucket";
+pub static FEEDS_DAILY_SELECT_BY_NODE_ID_AND_TIME_RANGE: &str = "SELECT bucket, array_agg(sensor ORDER BY sensor), array_agg(min ORDER BY sensor), array_agg(max ORDER BY sensor), array_agg(avg ORDER BY sensor), array_agg(count ORDER BY sensor) FROM feeds_daily WHERE node_id = $1 AND ($2::timestamp IS NULL OR bucket >= date_trunc('day', $2)) AND ($3::timestamp IS NULL OR bucket < $3) GROUP BY bucket ORDER BY bucket";
+pub static NODES_UPDATE_LAST_SEEN: &str = "UPDATE nodes SET last_seen_at = $2, last_value = $3 WHERE id = $1 AND (last_seen_at IS NULL OR last_seen_at < $2)";
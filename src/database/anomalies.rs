@@ -0,0 +1,390 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use deadpool_postgres::Object;
+use lettre::message::header::ContentType;
+use ntex::{http::StatusCode, util::Bytes};
+use tokio_postgres::{types::Type, Row};
+use tracing::instrument;
+
+use crate::{
+    constant::{config, messages, query},
+    metrics,
+    models::{
+        anomalies::{Anomaly, AnomalyQuery, Detector, DetectorMethod},
+        nodes::Node,
+        response::{ApiResponse, Data},
+    },
+    tasks::mail,
+    utils::{anomaly, http::serialize_response},
+};
+
+async fn load_detectors(
+    client: &Object,
+    select: &str,
+    node_id: i32,
+) -> Result<Vec<Detector>, tokio_postgres::Error> {
+    let stmt = client.prepare_typed_cached(select, &[Type::INT4]).await?;
+    let rows = client.query(&stmt, &[&node_id]).await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some(Detector {
+                sensor: row.get(0),
+                method: DetectorMethod::parse(row.get(1))?,
+                window: row.get(2),
+                threshold: row.get(3),
+                alpha: row.get(4),
+            })
+        })
+        .collect())
+}
+
+fn valid_detector(detector: &Detector, channels: usize) -> bool {
+    let threshold_valid = match detector.method {
+        DetectorMethod::Flatline => detector.threshold.is_some_and(|t| t >= 0.0),
+        _ => detector.threshold.is_some_and(|t| t > 0.0),
+    };
+    detector.sensor >= 0
+        && (detector.sensor as usize) < channels
+        && detector
+            .window
+            .is_some_and(|w| (2..=config::ANOMALY_MAX_WINDOW).contains(&w))
+        && threshold_valid
+        && detector.alpha.is_some_and(|a| a > 0.0 && a <= 1.0)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_detectors(client: &Object, node_id: i32) -> (Bytes, StatusCode) {
+    match load_detectors(client, query::ANOMALY_DETECTORS_SELECT_BY_NODE_ID, node_id).await {
+        Ok(detectors) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Multiple(detectors),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Detector> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Group detectors are checked against the members present when they are stored.
+// Nodes added later with fewer channels skip the detectors they cannot serve in `detect`.
+fn validate_detectors(nodes: &[Node], data: Vec<Detector>) -> Option<Vec<Detector>> {
+    let channels = nodes
+        .iter()
+        .map(|node| node.hardware_sensor_ids.len() + node.virtual_sensors.len())
+        .min()
+        .unwrap_or_default();
+    let detectors: Vec<Detector> = data.into_iter().map(anomaly::defaults).collect();
+    let mut seen = HashSet::new();
+    detectors
+        .iter()
+        .all(|d| valid_detector(d, channels) && seen.insert((d.sensor, d.method)))
+        .then_some(detectors)
+}
+
+async fn replace_detectors(
+    client: &mut Object,
+    delete: &str,
+    insert: &str,
+    id: i32,
+    detectors: &[Detector],
+) -> Result<(), tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    let delete = transaction
+        .prepare_typed_cached(delete, &[Type::INT4])
+        .await?;
+    let insert = transaction
+        .prepare_typed_cached(
+            insert,
+            &[
+                Type::INT4,
+                Type::INT4,
+                Type::VARCHAR,
+                Type::INT4,
+                Type::FLOAT8,
+                Type::FLOAT8,
+            ],
+        )
+        .await?;
+    transaction.execute(&delete, &[&id]).await?;
+    for detector in detectors {
+        transaction
+            .execute(
+                &insert,
+                &[
+                    &id,
+                    &detector.sensor,
+                    &detector.method.as_str(),
+                    &detector.window,
+                    &detector.threshold,
+                    &detector.alpha,
+                ],
+            )
+            .await?;
+    }
+    transaction.commit().await
+}
+
+async fn store_detectors(
+    client: &mut Object,
+    delete: &str,
+    insert: &str,
+    id: i32,
+    nodes: &[Node],
+    data: Vec<Detector>,
+) -> (Bytes, StatusCode) {
+    let Some(detectors) = validate_detectors(nodes, data) else {
+        let error_response: ApiResponse<Detector> = ApiResponse {
+            message: messages::INVALID_DETECTOR,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    };
+
+    match replace_detectors(client, delete, insert, id, &detectors).await {
+        Ok(()) => {
+            for node in nodes {
+                anomaly::reset(node.id);
+            }
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Multiple(detectors),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Detector> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn set_detectors(
+    client: &mut Object,
+    node: &Node,
+    data: Vec<Detector>,
+) -> (Bytes, StatusCode) {
+    store_detectors(
+        client,
+        query::ANOMALY_DETECTORS_DELETE_BY_NODE_ID,
+        query::ANOMALY_DETECTORS_INSERT,
+        node.id,
+        std::slice::from_ref(node),
+        data,
+    )
+    .await
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn set_group_detectors(
+    client: &mut Object,
+    group_id: i32,
+    nodes: &[Node],
+    data: Vec<Detector>,
+) -> (Bytes, StatusCode) {
+    if nodes.is_empty() {
+        let error_response: ApiResponse<Detector> = ApiResponse {
+            message: messages::GROUP_EMPTY,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    store_detectors(
+        client,
+        query::GROUP_ANOMALY_DETECTORS_DELETE_BY_GROUP_ID,
+        query::GROUP_ANOMALY_DETECTORS_INSERT,
+        group_id,
+        nodes,
+        data,
+    )
+    .await
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_anomalies(
+    client: &Object,
+    node_id: i32,
+    filter: AnomalyQuery,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(
+            query::FEED_ANOMALIES_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+            &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP],
+        )
+        .await
+        .unwrap();
+    let rows = client
+        .query(&stmt, &[&node_id, &filter.from, &filter.to])
+        .await
+        .unwrap();
+    let anomalies: Vec<Anomaly> = rows
+        .iter()
+        .filter_map(|row| {
+            Some(Anomaly {
+                time: row.get(0),
+                sensor: row.get(1),
+                method: DetectorMethod::parse(row.get(2))?,
+                value: row.get(3),
+                score: row.get(4),
+            })
+        })
+        .collect();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(anomalies),
+    };
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn detect(client: &Object, node: &Row, time: NaiveDateTime, value: &[f64]) {
+    let node_id: i32 = node.get(0);
+    let detectors = match anomaly::cached_detectors(node_id) {
+        Ok(detectors) => detectors,
+        Err(generation) => match load_detectors(
+            client,
+            query::ANOMALY_DETECTORS_SELECT_EFFECTIVE_BY_NODE_ID,
+            node_id,
+        )
+        .await
+        {
+            Ok(detectors) => {
+                anomaly::cache_detectors(node_id, generation, detectors.clone());
+                detectors
+            }
+            Err(e) => {
+                tracing::error!(error = %e, node_id, "failed to load anomaly detectors");
+                return;
+            }
+        },
+    };
+
+    let mut anomalies = Vec::new();
+    for detector in &detectors {
+        let Some(x) = value.get(detector.sensor as usize) else {
+            continue;
+        };
+        if let Some(score) = anomaly::observe(node_id, detector, *x) {
+            anomalies.push(Anomaly {
+                time,
+                sensor: detector.sensor,
+                method: detector.method,
+                value: *x,
+                score,
+            });
+        }
+    }
+    if anomalies.is_empty() {
+        return;
+    }
+    metrics::record_anomalies_detected(anomalies.len() as u64);
+
+    let stmt = match client
+        .prepare_typed_cached(
+            query::FEED_ANOMALIES_INSERT,
+            &[
+                Type::INT4,
+                Type::TIMESTAMP,
+                Type::INT4,
+                Type::VARCHAR,
+                Type::FLOAT8,
+                Type::FLOAT8,
+            ],
+        )
+        .await
+    {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            tracing::error!(error = %e, node_id, "failed to record anomalies");
+            return;
+        }
+    };
+    for anomaly in &anomalies {
+        if let Err(e) = client
+            .execute(
+                &stmt,
+                &[
+                    &node_id,
+                    &anomaly.time,
+                    &anomaly.sensor,
+                    &anomaly.method.as_str(),
+                    &anomaly.value,
+                    &anomaly.score,
+                ],
+            )
+            .await
+        {
+            tracing::error!(error = %e, node_id, "failed to record anomaly");
+        }
+    }
+
+    let alerts: Vec<&Anomaly> = anomalies
+        .iter()
+        .filter(|a| anomaly::should_alert(node_id, a.sensor, a.method))
+        .collect();
+    if !alerts.is_empty() {
+        alert(client, node, &alerts).await;
+    }
+}
+
+async fn alert(client: &Object, node: &Row, anomalies: &[&Anomaly]) {
+    let owner: i32 = node.get(1);
+    let Ok(stmt) = client
+        .prepare_typed_cached(query::USERS_SELECT_BY_ID, &[Type::INT4])
+        .await
+    else {
+        return;
+    };
+    let Ok(Some(user)) = client.query_opt(&stmt, &[&owner]).await else {
+        return;
+    };
+    let Ok(to) = user.get::<_, &str>(2).parse() else {
+        return;
+    };
+
+    let names: Vec<&str> = node
+        .get::<_, Vec<&str>>(6)
+        .into_iter()
+        .chain(node.get::<_, Vec<&str>>(17))
+        .collect();
+    let mut body = format!(
+        "Anomalous readings were detected on node \"{}\" (id {}):\n\n",
+        node.get::<_, &str>(3),
+        node.get::<_, i32>(0)
+    );
+    for anomaly in anomalies {
+        let sensor = names
+            .get(anomaly.sensor as usize)
+            .copied()
+            .unwrap_or_default();
+        body.push_str(&format!(
+            "- {} sensor {} ({}): value {} at {}, score {:.2}\n",
+            anomaly.method.as_str(),
+            anomaly.sensor,
+            sensor,
+            anomaly.value,
+            anomaly.time,
+            anomaly.score
+        ));
+    }
+    let email = lettre::Message::builder()
+        .from(config::EMAIL.parse().unwrap())
+        .to(to)
+        .subject("Anomaly detected")
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .unwrap();
+    mail::send(email);
+}
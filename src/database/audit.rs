@@ -0,0 +1,153 @@
+use std::borrow::Cow::Owned;
+
+use chrono::{NaiveDateTime, Utc};
+use deadpool_postgres::{GenericClient, Object, Transaction};
+use ntex::{http::StatusCode, util::Bytes};
+use tokio_postgres::types::Type;
+use tracing::instrument;
+
+use crate::{
+    constant::{config, messages, query},
+    models::{
+        audit::{AuditEntry, AuditLog, AuditLogQuery},
+        response::{ApiResponse, Data},
+    },
+    utils::http::serialize_response,
+};
+
+#[instrument(level = "debug", skip_all)]
+pub async fn snapshot(
+    client: &impl GenericClient,
+    snapshot_query: &str,
+    id: i32,
+) -> Option<String> {
+    let stmt = client
+        .prepare_typed_cached(snapshot_query, &[Type::INT4])
+        .await
+        .unwrap();
+    client
+        .query_opt(&stmt, &[&id])
+        .await
+        .unwrap()
+        .map(|row| row.get(0))
+}
+
+async fn insert(
+    client: &impl GenericClient,
+    entry: &AuditEntry<'_>,
+) -> Result<(), tokio_postgres::Error> {
+    let stmt = client
+        .prepare_typed_cached(
+            query::AUDIT_LOG_INSERT,
+            &[
+                Type::TIMESTAMP,
+                Type::INT4,
+                Type::INET,
+                Type::VARCHAR,
+                Type::VARCHAR,
+                Type::INT4,
+                Type::TEXT,
+                Type::TEXT,
+            ],
+        )
+        .await?;
+    client
+        .execute(
+            &stmt,
+            &[
+                &Utc::now().naive_utc(),
+                &entry.user_id,
+                &entry.ip,
+                &entry.action,
+                &entry.target_type,
+                &entry.target_id,
+                &entry.before,
+                &entry.after,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn commit(
+    transaction: Transaction<'_>,
+    entry: AuditEntry<'_>,
+) -> Result<(), (Bytes, StatusCode)> {
+    let result = async {
+        insert(&transaction, &entry).await?;
+        transaction.commit().await
+    }
+    .await;
+    result.map_err(|e| {
+        tracing::error!(error = %e, "failed to record audit entry");
+        let error_response: ApiResponse<AuditLog> = ApiResponse {
+            message: &e.to_string(),
+            data: Data::None,
+        };
+        serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+    })
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_audit_log(client: &Object, filter: AuditLogQuery) -> (Bytes, StatusCode) {
+    let limit = filter
+        .limit
+        .unwrap_or(config::AUDIT_LOG_DEFAULT_LIMIT)
+        .clamp(1, config::AUDIT_LOG_MAX_LIMIT);
+    let offset = filter.offset.unwrap_or(0).max(0);
+
+    let stmt = client
+        .prepare_typed_cached(
+            query::AUDIT_LOG_SELECT,
+            &[
+                Type::INT4,
+                Type::TEXT,
+                Type::TEXT,
+                Type::INT4,
+                Type::TIMESTAMP,
+                Type::TIMESTAMP,
+                Type::INT8,
+                Type::INT8,
+            ],
+        )
+        .await
+        .unwrap();
+    let rows = client
+        .query(
+            &stmt,
+            &[
+                &filter.user_id,
+                &filter.action,
+                &filter.target_type,
+                &filter.target_id,
+                &filter.from,
+                &filter.to,
+                &limit,
+                &offset,
+            ],
+        )
+        .await
+        .unwrap();
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        entries.push(AuditLog {
+            id: row.get(0),
+            time: row.get::<_, NaiveDateTime>(1),
+            user_id: row.get(2),
+            ip: row.get::<_, Option<&str>>(3).map(|s| Owned(s.to_string())),
+            action: Owned(row.get::<_, &str>(4).to_string()),
+            target_type: Owned(row.get::<_, &str>(5).to_string()),
+            target_id: row.get(6),
+            diff: sonic_rs::from_str(row.get::<_, &str>(7)).unwrap(),
+        });
+    }
+
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(entries),
+    };
+
+    serialize_response(response, StatusCode::OK)
+}
@@ -0,0 +1,195 @@
+use tokio_postgres::types::{ToSql, Type};
+
+pub struct QueryBuilder {
+    sql: String,
+    types: Vec<Type>,
+    params: Vec<Box<dyn ToSql + Sync + Send>>,
+}
+
+impl QueryBuilder {
+    pub fn new(base: &str) -> Self {
+        QueryBuilder {
+            sql: base.to_string(),
+            types: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    fn bind<T: ToSql + Sync + Send + 'static>(&mut self, ty: Type, value: T) -> String {
+        self.types.push(ty);
+        self.params.push(Box::new(value));
+        format!("${}", self.params.len())
+    }
+
+    pub fn filter<T: ToSql + Sync + Send + 'static>(
+        &mut self,
+        clause: &str,
+        ty: Type,
+        value: Option<T>,
+    ) -> &mut Self {
+        if let Some(value) = value {
+            let placeholder = self.bind(ty, value);
+            self.sql.push_str(" AND ");
+            self.sql.push_str(&clause.replace("$?", &placeholder));
+        }
+        self
+    }
+
+    pub fn filter_all<T: ToSql + Sync + Send + 'static, const N: usize>(
+        &mut self,
+        clause: &str,
+        ty: Type,
+        values: Option<[T; N]>,
+    ) -> &mut Self {
+        if let Some(values) = values {
+            let mut clause = clause.to_string();
+            for (index, value) in values.into_iter().enumerate() {
+                let placeholder = self.bind(ty.clone(), value);
+                clause = clause.replace(&format!("$?{}", index + 1), &placeholder);
+            }
+            self.sql.push_str(" AND ");
+            self.sql.push_str(&clause);
+        }
+        self
+    }
+
+    pub fn search(&mut self, columns: &[&str], q: Option<&str>) -> &mut Self {
+        let Some(q) = q.filter(|q| !q.is_empty()) else {
+            return self;
+        };
+        let pattern = format!(
+            "%{}%",
+            q.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let placeholder = self.bind(Type::TEXT, pattern);
+        let clause = columns
+            .iter()
+            .map(|column| format!("{} ILIKE {}", column, placeholder))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        self.sql.push_str(&format!(" AND ({})", clause));
+        self
+    }
+
+    pub fn order_by(&mut self, clause: &str) -> &mut Self {
+        self.sql.push_str(" ORDER BY ");
+        self.sql.push_str(clause);
+        self
+    }
+
+    pub fn paginate(
+        &mut self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        max_limit: i64,
+    ) -> &mut Self {
+        if let Some(limit) = limit {
+            let placeholder = self.bind(Type::INT8, limit.clamp(1, max_limit));
+            self.sql.push_str(" LIMIT ");
+            self.sql.push_str(&placeholder);
+        }
+        if let Some(offset) = offset.filter(|offset| *offset > 0) {
+            let placeholder = self.bind(Type::INT8, offset);
+            self.sql.push_str(" OFFSET ");
+            self.sql.push_str(&placeholder);
+        }
+        self
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn types(&self) -> &[Type] {
+        &self.types
+    }
+
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.params
+            .iter()
+            .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_renumbers_placeholders_in_bind_order() {
+        let mut builder = QueryBuilder::new("SELECT * FROM nodes WHERE deleted_at IS NULL");
+        builder
+            .filter("user_id = $?", Type::INT4, Some(7))
+            .filter("hardware_id = $?", Type::INT4, None::<i32>)
+            .filter("name = $?", Type::TEXT, Some("node".to_string()));
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM nodes WHERE deleted_at IS NULL AND user_id = $1 AND name = $2"
+        );
+        assert_eq!(builder.types(), &[Type::INT4, Type::TEXT]);
+        assert_eq!(builder.params().len(), 2);
+    }
+
+    #[test]
+    fn filter_all_renumbers_indexed_placeholders() {
+        let mut builder = QueryBuilder::new("SELECT * FROM feeds WHERE TRUE");
+        builder
+            .filter("node_id = $?", Type::INT4, Some(1))
+            .filter_all(
+                "time >= $?1 AND time < $?2 AND $?1 < $?2",
+                Type::INT8,
+                Some([10i64, 20i64]),
+            );
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM feeds WHERE TRUE AND node_id = $1 AND time >= $2 AND time < $3 AND $2 < $3"
+        );
+        assert_eq!(builder.types(), &[Type::INT4, Type::INT8, Type::INT8]);
+    }
+
+    #[test]
+    fn filter_all_skips_missing_values() {
+        let mut builder = QueryBuilder::new("SELECT 1 WHERE TRUE");
+        builder.filter_all("a = $?1 OR b = $?2", Type::INT4, None::<[i32; 2]>);
+        assert_eq!(builder.sql(), "SELECT 1 WHERE TRUE");
+        assert!(builder.types().is_empty());
+    }
+
+    #[test]
+    fn search_binds_one_escaped_pattern_for_all_columns() {
+        let mut builder = QueryBuilder::new("SELECT * FROM users WHERE TRUE");
+        builder
+            .filter("id = $?", Type::INT4, Some(3))
+            .search(&["username", "email"], Some("50%_off\\"));
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM users WHERE TRUE AND id = $1 AND (username ILIKE $2 OR email ILIKE $2)"
+        );
+        assert_eq!(builder.types(), &[Type::INT4, Type::TEXT]);
+        assert_eq!(
+            format!("{:?}", builder.params[1]),
+            format!("{:?}", "%50\\%\\_off\\\\%")
+        );
+    }
+
+    #[test]
+    fn search_ignores_empty_query() {
+        let mut builder = QueryBuilder::new("SELECT * FROM users WHERE TRUE");
+        builder
+            .search(&["username"], Some(""))
+            .search(&["username"], None);
+        assert_eq!(builder.sql(), "SELECT * FROM users WHERE TRUE");
+        assert!(builder.params().is_empty());
+    }
+
+    #[test]
+    fn paginate_clamps_limit_and_drops_zero_offset() {
+        let mut builder = QueryBuilder::new("SELECT * FROM nodes");
+        builder.order_by("id").paginate(Some(500), Some(0), 100);
+        assert_eq!(builder.sql(), "SELECT * FROM nodes ORDER BY id LIMIT $1");
+        assert_eq!(format!("{:?}", builder.params[0]), "100");
+    }
+}
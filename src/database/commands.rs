@@ -0,0 +1,333 @@
+use chrono::{Duration, Utc};
+use deadpool_postgres::Object;
+use ntex::{http::StatusCode, util::Bytes};
+use tokio_postgres::{types::Type, Row};
+use tracing::instrument;
+
+use crate::{
+    constant::{config, messages, query},
+    models::{
+        commands::{Command, CommandAck, CommandListQuery, CommandPayload, CommandStatus},
+        response::{ApiResponse, Data},
+    },
+    tasks,
+    utils::http::serialize_response,
+};
+
+fn command_from_row(row: &Row) -> Option<Command> {
+    Some(Command {
+        id: row.get(0),
+        node_id: row.get(1),
+        user_id: row.get(2),
+        command: row.get(3),
+        payload: row
+            .get::<_, Option<&str>>(4)
+            .and_then(|payload| sonic_rs::from_str(payload).ok()),
+        status: CommandStatus::parse(row.get(5))?,
+        result: row.get(6),
+        created_at: row.get(7),
+        delivered_at: row.get(8),
+        acked_at: row.get(9),
+        expires_at: row.get(10),
+    })
+}
+
+fn invalid_command() -> (Bytes, StatusCode) {
+    let error_response: ApiResponse<Command> = ApiResponse {
+        message: messages::INVALID_COMMAND,
+        data: Data::None,
+    };
+    serialize_response(error_response, StatusCode::BAD_REQUEST)
+}
+
+fn validate(data: &CommandPayload) -> Option<(Option<String>, i64)> {
+    let ttl = data.ttl_secs.unwrap_or(config::COMMAND_DEFAULT_TTL_SECS);
+    let payload = data
+        .payload
+        .as_ref()
+        .map(sonic_rs::to_string)
+        .transpose()
+        .ok()?;
+    if data.command.trim().is_empty()
+        || data.command.len() > config::COMMAND_MAX_NAME_LEN
+        || payload
+            .as_ref()
+            .is_some_and(|payload| payload.len() > config::COMMAND_MAX_PAYLOAD_BYTES)
+        || !(1..=config::COMMAND_MAX_TTL_SECS).contains(&ttl)
+    {
+        return None;
+    }
+    Some((payload, ttl))
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_command(
+    client: &Object,
+    node_id: i32,
+    user_id: i32,
+    data: CommandPayload,
+) -> (Bytes, StatusCode) {
+    let Some((payload, ttl)) = validate(&data) else {
+        return invalid_command();
+    };
+
+    let now = Utc::now().naive_utc();
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_COMMANDS_INSERT,
+            &[
+                Type::INT4,
+                Type::INT4,
+                Type::VARCHAR,
+                Type::TEXT,
+                Type::TIMESTAMP,
+                Type::TIMESTAMP,
+            ],
+        )
+        .await
+        .unwrap();
+    match client
+        .query_one(
+            &stmt,
+            &[
+                &node_id,
+                &user_id,
+                &data.command,
+                &payload,
+                &now,
+                &(now + Duration::seconds(ttl)),
+            ],
+        )
+        .await
+    {
+        Ok(row) => {
+            tasks::commands::notify(node_id);
+            let response = ApiResponse {
+                message: messages::CREATED,
+                data: command_from_row(&row).map_or(Data::None, Data::Single),
+            };
+            serialize_response(response, StatusCode::CREATED)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Command> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_group_command(
+    client: &Object,
+    node_ids: Vec<i32>,
+    user_id: i32,
+    data: CommandPayload,
+) -> (Bytes, StatusCode) {
+    let Some((payload, ttl)) = validate(&data) else {
+        return invalid_command();
+    };
+
+    let now = Utc::now().naive_utc();
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_COMMANDS_INSERT_MANY,
+            &[
+                Type::INT4_ARRAY,
+                Type::INT4,
+                Type::VARCHAR,
+                Type::TEXT,
+                Type::TIMESTAMP,
+                Type::TIMESTAMP,
+            ],
+        )
+        .await
+        .unwrap();
+    match client
+        .query(
+            &stmt,
+            &[
+                &node_ids,
+                &user_id,
+                &data.command,
+                &payload,
+                &now,
+                &(now + Duration::seconds(ttl)),
+            ],
+        )
+        .await
+    {
+        Ok(rows) => {
+            for node_id in &node_ids {
+                tasks::commands::notify(*node_id);
+            }
+            let commands: Vec<Command> = rows.iter().filter_map(command_from_row).collect();
+            let response = ApiResponse {
+                message: messages::CREATED,
+                data: Data::Multiple(commands),
+            };
+            serialize_response(response, StatusCode::CREATED)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Command> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_commands(
+    client: &Object,
+    node_id: i32,
+    filter: CommandListQuery,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_COMMANDS_SELECT_BY_NODE_ID,
+            &[
+                Type::INT4,
+                Type::TIMESTAMP,
+                Type::VARCHAR,
+                Type::INT8,
+                Type::INT8,
+            ],
+        )
+        .await
+        .unwrap();
+    let limit = filter
+        .limit
+        .unwrap_or(config::LIST_MAX_LIMIT)
+        .clamp(1, config::LIST_MAX_LIMIT);
+    let offset = filter.offset.unwrap_or_default().max(0);
+    let rows = client
+        .query(
+            &stmt,
+            &[
+                &node_id,
+                &Utc::now().naive_utc(),
+                &filter.status.map(|status| status.as_str()),
+                &limit,
+                &offset,
+            ],
+        )
+        .await
+        .unwrap();
+    let commands: Vec<Command> = rows.iter().filter_map(command_from_row).collect();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(commands),
+    };
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn take_pending(
+    client: &Object,
+    node_id: i32,
+) -> Result<Vec<Command>, tokio_postgres::Error> {
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_COMMANDS_DELIVER,
+            &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8, Type::INT8],
+        )
+        .await?;
+    let rows = client
+        .query(
+            &stmt,
+            &[
+                &node_id,
+                &Utc::now().naive_utc(),
+                &(config::COMMAND_REDELIVERY_SECS as f64),
+                &config::COMMAND_DELIVERY_BATCH,
+            ],
+        )
+        .await?;
+    let mut commands: Vec<Command> = rows.iter().filter_map(command_from_row).collect();
+    commands.sort_by_key(|command| command.id);
+    Ok(commands)
+}
+
+pub fn pending_response(
+    result: Result<Vec<Command>, tokio_postgres::Error>,
+) -> (Bytes, StatusCode) {
+    match result {
+        Ok(commands) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Multiple(commands),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Command> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn ack_command(client: &Object, data: CommandAck) -> (Bytes, StatusCode) {
+    if !matches!(data.status, CommandStatus::Acked | CommandStatus::Failed)
+        || data
+            .result
+            .as_ref()
+            .is_some_and(|result| result.len() > config::COMMAND_MAX_PAYLOAD_BYTES)
+    {
+        return invalid_command();
+    }
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_COMMANDS_ACK,
+            &[
+                Type::INT4,
+                Type::INT4,
+                Type::VARCHAR,
+                Type::TEXT,
+                Type::TIMESTAMP,
+            ],
+        )
+        .await
+        .unwrap();
+    match client
+        .query_opt(
+            &stmt,
+            &[
+                &data.id,
+                &data.node_id,
+                &data.status.as_str(),
+                &data.result,
+                &Utc::now().naive_utc(),
+            ],
+        )
+        .await
+    {
+        Ok(Some(row)) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: command_from_row(&row).map_or(Data::None, Data::Single),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Ok(None) => {
+            let error_response: ApiResponse<Command> = ApiResponse {
+                message: messages::COMMAND_NOT_FOUND,
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::NOT_FOUND)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Command> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,52 +1,67 @@
+use std::{
+    borrow::Cow::Borrowed,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io,
+    pin::pin,
+    str,
+};
+
+use chrono::NaiveDateTime;
 use deadpool_postgres::Object;
-use futures::StreamExt;
+use futures::{channel::mpsc::Sender, SinkExt, Stream, StreamExt, TryStreamExt};
 
-use ntex::{
-    http::{Payload, StatusCode},
-    util::Bytes,
+use ntex::{http::StatusCode, util::Bytes};
+use tokio_postgres::{
+    binary_copy::BinaryCopyInWriter,
+    types::{ToSql, Type},
+    Row,
 };
-use tokio_postgres::types::Type;
+use tracing::instrument;
 
 use crate::{
-    constant::{messages, query},
+    constant::{config, messages, query},
+    database::{anomalies, commands},
+    metrics,
     models::{
-        feeds::FeedPayload,
+        feeds::{CompletenessReport, FeedPayload, Gap, ImportFormat, ImportReport, RejectedLine},
         response::{ApiResponse, Data},
     },
-    utils::http::serialize_response,
+    tasks::rollup,
+    utils::{
+        export::{column_names, FeedEncoder},
+        expression::{self, Expression},
+        http::{serialize_response, JsonStream},
+        import::FeedParser,
+    },
 };
 
-pub async fn add_feed(client: &Object, payload: &mut Payload, user_id: i32) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = std::str::from_utf8(&buf).unwrap();
-    let data: FeedPayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<FeedPayload> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
+fn virtual_expressions(row: &Row) -> Vec<Expression> {
+    row.get::<_, Vec<&str>>(18)
+        .into_iter()
+        .filter_map(|expression| Expression::parse(expression).ok())
+        .collect()
+}
 
+#[instrument(level = "debug", skip_all)]
+pub async fn add_feed(
+    client: &Object,
+    data: FeedPayload,
+    user_id: i32,
+    deliver_commands: bool,
+) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::NODES_SELECT_BY_ID, &[Type::INT4])
         .await
         .unwrap();
-    let row = client.query_opt(&stmt, &[&data.node_id]).await.unwrap();
-    if row.is_none() {
+    let Some(row) = client.query_opt(&stmt, &[&data.node_id]).await.unwrap() else {
         let response: ApiResponse<FeedPayload> = ApiResponse {
             message: messages::NODE_NOT_FOUND,
             data: Data::None,
         };
         return serialize_response(response, StatusCode::NOT_FOUND);
     };
-    if row.unwrap().get::<_, i32>(1) != user_id {
+    if row.get::<_, i32>(1) != user_id {
         let response: ApiResponse<FeedPayload> = ApiResponse {
             message: messages::UNAUTHORIZED,
             data: Data::None,
@@ -54,21 +69,69 @@ pub async fn add_feed(client: &Object, payload: &mut Payload, user_id: i32) -> (
         return serialize_response(response, StatusCode::UNAUTHORIZED);
     }
 
-    let stmt = client
-        .prepare_typed_cached(
-            query::FEEDS_INSERT,
-            &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8_ARRAY],
-        )
-        .await
-        .unwrap();
+    if data.position.is_some_and(|position| !position.is_valid()) {
+        let response: ApiResponse<FeedPayload> = ApiResponse {
+            message: messages::INVALID_COORDINATES,
+            data: Data::None,
+        };
+        return serialize_response(response, StatusCode::BAD_REQUEST);
+    }
 
-    match client
-        .execute(
-            &stmt,
-            &[&data.node_id, &chrono::Utc::now().naive_utc(), &data.value],
-        )
-        .await
-    {
+    let mut value = data.value;
+    if let Err(message) = expression::append_virtual(
+        &mut value,
+        row.get::<_, Vec<i32>>(5).len(),
+        &virtual_expressions(&row),
+    ) {
+        let response: ApiResponse<FeedPayload> = ApiResponse {
+            message,
+            data: Data::None,
+        };
+        return serialize_response(response, StatusCode::BAD_REQUEST);
+    }
+    let time = chrono::Utc::now().naive_utc();
+    let result = match data.position {
+        Some(position) => {
+            let stmt = client
+                .prepare_typed_cached(
+                    query::FEEDS_INSERT_WITH_POSITION,
+                    &[
+                        Type::INT4,
+                        Type::TIMESTAMP,
+                        Type::FLOAT8_ARRAY,
+                        Type::FLOAT8,
+                        Type::FLOAT8,
+                        Type::FLOAT8,
+                    ],
+                )
+                .await
+                .unwrap();
+            client
+                .execute(
+                    &stmt,
+                    &[
+                        &data.node_id,
+                        &time,
+                        &value,
+                        &position.latitude,
+                        &position.longitude,
+                        &position.altitude,
+                    ],
+                )
+                .await
+        }
+        None => {
+            let stmt = client
+                .prepare_typed_cached(
+                    query::FEEDS_INSERT,
+                    &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8_ARRAY],
+                )
+                .await
+                .unwrap();
+            client.execute(&stmt, &[&data.node_id, &time, &value]).await
+        }
+    };
+    match result {
         Ok(rows) => {
             if rows == 0 {
                 let response: ApiResponse<FeedPayload> = ApiResponse {
@@ -77,9 +140,25 @@ pub async fn add_feed(client: &Object, payload: &mut Payload, user_id: i32) -> (
                 };
                 return serialize_response(response, StatusCode::NOT_FOUND);
             }
-            let response: ApiResponse<FeedPayload> = ApiResponse {
+            metrics::record_feeds_ingested(rows);
+            rollup::mark(data.node_id, time);
+            anomalies::detect(client, &row, time, &value).await;
+            if !deliver_commands {
+                let response: ApiResponse<FeedPayload> = ApiResponse {
+                    message: messages::CREATED,
+                    data: Data::None,
+                };
+                return serialize_response(response, StatusCode::CREATED);
+            }
+            let commands = commands::take_pending(client, data.node_id)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!(error = %e, node_id = data.node_id, "failed to deliver commands");
+                    Vec::new()
+                });
+            let response = ApiResponse {
                 message: messages::CREATED,
-                data: Data::None,
+                data: Data::Multiple(commands),
             };
             serialize_response(response, StatusCode::CREATED)
         }
@@ -92,3 +171,354 @@ pub async fn add_feed(client: &Object, payload: &mut Payload, user_id: i32) -> (
         }
     }
 }
+
+#[instrument(level = "debug", skip_all)]
+pub async fn export_feeds(
+    mut client: Object,
+    layouts: HashMap<i32, Vec<usize>>,
+    width: usize,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    mut encoder: FeedEncoder,
+    mut tx: Sender<io::Result<Bytes>>,
+) {
+    let result: io::Result<()> = async {
+        let transaction = client.transaction().await.map_err(io::Error::other)?;
+        let stmt = transaction
+            .prepare_typed_cached(
+                query::FEEDS_SELECT_BY_NODE_IDS_AND_TIME_RANGE,
+                &[Type::INT4_ARRAY, Type::TIMESTAMP, Type::TIMESTAMP],
+            )
+            .await
+            .map_err(io::Error::other)?;
+        let node_ids: Vec<i32> = layouts.keys().copied().collect();
+        let portal = transaction
+            .bind(&stmt, &[&node_ids, &from, &to])
+            .await
+            .map_err(io::Error::other)?;
+
+        loop {
+            let rows = transaction
+                .query_portal(&portal, config::EXPORT_FETCH_SIZE)
+                .await
+                .map_err(io::Error::other)?;
+            let batch: Vec<(i32, NaiveDateTime, Vec<f64>)> = rows
+                .iter()
+                .map(|row| {
+                    let node_id: i32 = row.get(0);
+                    let mut value = vec![f64::NAN; width];
+                    if let Some(layout) = layouts.get(&node_id) {
+                        for (index, v) in row.get::<_, Vec<f64>>(2).into_iter().enumerate() {
+                            if let Some(column) = layout.get(index) {
+                                value[*column] = v;
+                            }
+                        }
+                    }
+                    (node_id, row.get(1), value)
+                })
+                .collect();
+            let chunk = encoder.encode(&batch)?;
+            if !chunk.is_empty() {
+                tx.send(Ok(chunk)).await.map_err(io::Error::other)?;
+            }
+            if rows.len() < config::EXPORT_FETCH_SIZE as usize {
+                break;
+            }
+        }
+
+        let chunk = encoder.finish()?;
+        if !chunk.is_empty() {
+            tx.send(Ok(chunk)).await.map_err(io::Error::other)?;
+        }
+        transaction.commit().await.map_err(io::Error::other)
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tx.send(Err(e)).await;
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn import_feeds<S, E>(
+    client: &Object,
+    node_id: i32,
+    format: ImportFormat,
+    owner: Option<i32>,
+    mut body: S,
+) -> (Bytes, StatusCode)
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let stmt = client
+        .prepare_typed_cached(query::NODES_SELECT_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    let Some(row) = client.query_opt(&stmt, &[&node_id]).await.unwrap() else {
+        let response: ApiResponse<ImportReport> = ApiResponse {
+            message: messages::NODE_NOT_FOUND,
+            data: Data::None,
+        };
+        return serialize_response(response, StatusCode::NOT_FOUND);
+    };
+    if owner.is_some_and(|user_id| row.get::<_, i32>(1) != user_id) {
+        let response: ApiResponse<ImportReport> = ApiResponse {
+            message: messages::UNAUTHORIZED,
+            data: Data::None,
+        };
+        return serialize_response(response, StatusCode::UNAUTHORIZED);
+    }
+    let mut parser = FeedParser::new(format, column_names(&row.get::<_, Vec<String>>(6)));
+    let sensors = row.get::<_, Vec<i32>>(5).len();
+    let expressions = virtual_expressions(&row);
+
+    let sink = client.copy_in(query::FEEDS_COPY_IN).await.unwrap();
+    let mut writer = pin!(BinaryCopyInWriter::new(
+        sink,
+        &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8_ARRAY],
+    ));
+    let mut report = ImportReport::default();
+    let mut hours = HashSet::new();
+    let mut latest: Option<(NaiveDateTime, Vec<f64>)> = None;
+    let mut buf: Vec<u8> = Vec::new();
+    let mut line_no = 0;
+    let mut done = false;
+
+    while !done {
+        match body.next().await {
+            Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                let error_response: ApiResponse<ImportReport> = ApiResponse {
+                    message: &e.to_string(),
+                    data: Data::None,
+                };
+                return serialize_response(error_response, StatusCode::BAD_REQUEST);
+            }
+            None => {
+                if !buf.is_empty() && !buf.ends_with(b"\n") {
+                    buf.push(b'\n');
+                }
+                done = true;
+            }
+        }
+
+        let mut start = 0;
+        while let Some(pos) = buf[start..].iter().position(|b| *b == b'\n') {
+            let line = &buf[start..start + pos];
+            start += pos + 1;
+            line_no += 1;
+
+            let line = match str::from_utf8(line) {
+                Ok(line) => line.trim_end_matches('\r'),
+                Err(_) => {
+                    report.reject(line_no, messages::IMPORT_INVALID_LINE);
+                    continue;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if parser.needs_header() {
+                if let Err(message) = parser.parse_header(line) {
+                    let error_response: ApiResponse<ImportReport> = ApiResponse {
+                        message,
+                        data: Data::None,
+                    };
+                    return serialize_response(error_response, StatusCode::BAD_REQUEST);
+                }
+                continue;
+            }
+            match parser.parse_line(line) {
+                Ok((time, mut value)) => {
+                    if let Err(reason) =
+                        expression::append_virtual(&mut value, sensors, &expressions)
+                    {
+                        report.reject(line_no, reason);
+                        continue;
+                    }
+                    hours.insert(rollup::hour(time));
+                    if latest.as_ref().is_none_or(|(latest, _)| time > *latest) {
+                        latest = Some((time, value.clone()));
+                    }
+                    if let Err(e) = writer.as_mut().write(&[&node_id, &time, &value]).await {
+                        let error_response: ApiResponse<ImportReport> = ApiResponse {
+                            message: &e.to_string(),
+                            data: Data::None,
+                        };
+                        return serialize_response(
+                            error_response,
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        );
+                    }
+                }
+                Err(reason) => report.reject(line_no, reason),
+            }
+        }
+        buf.drain(..start);
+
+        if buf.len() > config::IMPORT_MAX_LINE_BYTES {
+            line_no += 1;
+            report.reject(line_no, messages::IMPORT_LINE_TOO_LONG);
+            let error_response: ApiResponse<ImportReport> = ApiResponse {
+                message: messages::IMPORT_LINE_TOO_LONG,
+                data: Data::Single(report),
+            };
+            return serialize_response(error_response, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    match writer.as_mut().finish().await {
+        Ok(imported) => {
+            metrics::record_feeds_ingested(imported);
+            let hours = hours.into_iter().map(|hour| (node_id, hour)).collect();
+            if let Err(e) = rollup::rebuild(client, hours).await {
+                tracing::error!(error = %e, node_id, "rollup of imported feeds failed");
+            }
+            if let Some((time, value)) = latest {
+                let stmt = client
+                    .prepare_typed_cached(
+                        query::NODES_UPDATE_LAST_SEEN,
+                        &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8_ARRAY],
+                    )
+                    .await
+                    .unwrap();
+                client
+                    .execute(&stmt, &[&node_id, &time, &value])
+                    .await
+                    .unwrap();
+            }
+            report.imported = imported;
+            let response = ApiResponse {
+                message: messages::CREATED,
+                data: Data::Single(report),
+            };
+            serialize_response(response, StatusCode::CREATED)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<ImportReport> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+impl ImportReport {
+    fn reject(&mut self, line: usize, reason: &'static str) {
+        self.rejected += 1;
+        if self.rejected_lines.len() < config::IMPORT_MAX_REPORTED_REJECTS {
+            self.rejected_lines.push(RejectedLine {
+                line,
+                reason: Borrowed(reason),
+            });
+        }
+    }
+}
+
+fn gap(start: NaiveDateTime, end: NaiveDateTime, interval: i32, inner: bool) -> Gap {
+    let duration_secs = (end - start).num_milliseconds() as f64 / 1000.0;
+    let slots = (duration_secs / f64::from(interval)).floor() as i64;
+    Gap {
+        start,
+        end,
+        duration_secs,
+        missing: (slots - i64::from(inner)).max(0),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn stream_completeness(
+    client: Object,
+    node_id: i32,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    interval: i32,
+    tx: Sender<io::Result<Bytes>>,
+) {
+    let mut out = JsonStream::new(tx);
+    let result: io::Result<()> = async {
+        let tolerance = f64::from(interval) * config::COMPLETENESS_GAP_TOLERANCE;
+        let stmt = client
+            .prepare_typed_cached(
+                query::FEEDS_STATS_BY_NODE_ID_AND_TIME_RANGE,
+                &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP],
+            )
+            .await
+            .map_err(io::Error::other)?;
+        let stats = client
+            .query_one(&stmt, &[&node_id, &from, &to])
+            .await
+            .map_err(io::Error::other)?;
+        let readings: i64 = stats.get(0);
+        let first: Option<NaiveDateTime> = stats.get(1);
+        let last: Option<NaiveDateTime> = stats.get(2);
+
+        let stmt = client
+            .prepare_typed_cached(
+                query::FEEDS_GAPS_BY_NODE_ID_AND_TIME_RANGE,
+                &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP, Type::FLOAT8],
+            )
+            .await
+            .map_err(io::Error::other)?;
+        let params: [&(dyn ToSql + Sync); 4] = [&node_id, &from, &to, &tolerance];
+        let mut rows = pin!(client
+            .query_raw(&stmt, params)
+            .await
+            .map_err(io::Error::other)?);
+
+        out.write_raw(b"{\"message\":").await?;
+        out.write(&messages::OK).await?;
+        out.write_raw(b",\"data\":{\"gaps\":[").await?;
+
+        let is_gap = |start: NaiveDateTime, end: NaiveDateTime| {
+            (end - start).num_milliseconds() as f64 / 1000.0 > tolerance
+        };
+        let mut gaps = Vec::new();
+        match first {
+            Some(first) if is_gap(from, first) => gaps.push(gap(from, first, interval, false)),
+            Some(_) => {}
+            None => gaps.push(gap(from, to, interval, false)),
+        }
+        let (mut count, mut missing, mut down_secs) = (0i64, 0i64, 0.0);
+        loop {
+            let next = rows.try_next().await.map_err(io::Error::other)?;
+            if let Some(row) = &next {
+                gaps.push(gap(row.get(0), row.get(1), interval, true));
+            } else if let Some(last) = last.filter(|last| is_gap(*last, to)) {
+                gaps.push(gap(last, to, interval, false));
+            }
+            for gap in gaps.drain(..) {
+                if count > 0 {
+                    out.write_raw(b",").await?;
+                }
+                count += 1;
+                missing += gap.missing;
+                down_secs += gap.duration_secs;
+                out.write(&gap).await?;
+            }
+            if next.is_none() {
+                break;
+            }
+        }
+
+        let range_secs = (to - from).num_milliseconds() as f64 / 1000.0;
+        out.write_raw(b"],\"report\":").await?;
+        out.write(&CompletenessReport {
+            from,
+            to,
+            interval_secs: interval,
+            readings,
+            expected: (range_secs / f64::from(interval)).floor() as i64,
+            missing,
+            gaps: count,
+            uptime_percent: (100.0 * (range_secs - down_secs) / range_secs).clamp(0.0, 100.0),
+        })
+        .await?;
+        out.write_raw(b"}}").await
+    }
+    .await;
+
+    out.finish(result).await;
+}
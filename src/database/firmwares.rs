@@ -0,0 +1,373 @@
+use std::{fmt::Display, net::IpAddr};
+
+use chrono::Utc;
+use deadpool_postgres::Object;
+use futures::Stream;
+use ntex::{http::StatusCode, util::Bytes};
+use tokio::fs;
+use tokio_postgres::{error::SqlState, types::Type, Row};
+use tracing::instrument;
+
+use crate::{
+    constant::{audit as action, config, messages, query},
+    database::audit,
+    models::{
+        audit::AuditEntry,
+        firmwares::{
+            Firmware, FirmwareCheck, FirmwareReportEntry, FirmwareReportQuery, FirmwareRollout,
+            FirmwareUploadQuery, RolloutReport,
+        },
+        response::{ApiResponse, Data},
+    },
+    utils::{
+        firmware::{self, Upload, UploadError},
+        http::serialize_response,
+    },
+};
+
+fn firmware_from_row(row: &Row) -> Firmware {
+    Firmware {
+        id: row.get(0),
+        hardware_id: row.get(1),
+        version: row.get(2),
+        checksum: row.get(3),
+        size: row.get(4),
+        release_notes: row.get(5),
+        created_at: row.get(6),
+    }
+}
+
+fn error_response(message: &str, status: StatusCode) -> (Bytes, StatusCode) {
+    let error_response: ApiResponse<Firmware> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    serialize_response(error_response, status)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn receive_firmware<S, E>(
+    data: &FirmwareUploadQuery,
+    body: S,
+) -> Result<Upload, (Bytes, StatusCode)>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    if data.version.is_empty()
+        || data.version.len() > config::FIRMWARE_MAX_VERSION_LEN
+        || !data
+            .version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'))
+        || data
+            .release_notes
+            .as_ref()
+            .is_some_and(|notes| notes.len() > config::FIRMWARE_MAX_NOTES_LEN)
+    {
+        return Err(error_response(
+            messages::INVALID_FIRMWARE,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    firmware::receive(body).await.map_err(|e| match e {
+        UploadError::Empty => error_response(messages::INVALID_FIRMWARE, StatusCode::BAD_REQUEST),
+        UploadError::TooLarge => {
+            error_response(messages::PAYLOAD_TOO_LARGE, StatusCode::PAYLOAD_TOO_LARGE)
+        }
+        UploadError::Body(message) => error_response(&message, StatusCode::BAD_REQUEST),
+        UploadError::Io(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    })
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_firmware(
+    client: &mut Object,
+    hardware_id: i32,
+    data: FirmwareUploadQuery,
+    upload: Upload,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
+        .prepare_typed_cached(
+            query::FIRMWARES_INSERT,
+            &[
+                Type::INT4,
+                Type::VARCHAR,
+                Type::VARCHAR,
+                Type::INT8,
+                Type::TEXT,
+                Type::INT4,
+                Type::TIMESTAMP,
+            ],
+        )
+        .await
+        .unwrap();
+    let row = transaction
+        .query_opt(
+            &stmt,
+            &[
+                &hardware_id,
+                &data.version.as_ref(),
+                &upload.checksum,
+                &upload.size,
+                &data.release_notes.as_deref(),
+                &user_id,
+                &Utc::now().naive_utc(),
+            ],
+        )
+        .await;
+    let firmware = match row {
+        Ok(Some(row)) => firmware_from_row(&row),
+        Ok(None) => {
+            let _ = fs::remove_file(&upload.path).await;
+            return error_response(messages::HARDWARE_NOT_FOUND, StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&upload.path).await;
+            if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+                return error_response(messages::FIRMWARE_EXISTS, StatusCode::CONFLICT);
+            }
+            return error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Err(e) = fs::rename(&upload.path, firmware::path(firmware.id)).await {
+        let _ = fs::remove_file(&upload.path).await;
+        return error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let entry = AuditEntry {
+        user_id: Some(user_id),
+        ip,
+        action: action::CREATE,
+        target_type: action::TARGET_FIRMWARE,
+        target_id: Some(firmware.id),
+        before: None,
+        after: audit::snapshot(&transaction, query::FIRMWARES_SNAPSHOT_BY_ID, firmware.id).await,
+    };
+    if let Err(res) = audit::commit(transaction, entry).await {
+        let _ = fs::remove_file(firmware::path(firmware.id)).await;
+        return res;
+    }
+    let response = ApiResponse {
+        message: messages::CREATED,
+        data: Data::Single(firmware),
+    };
+    serialize_response(response, StatusCode::CREATED)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_firmwares(client: &Object, hardware_id: i32) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::FIRMWARES_SELECT_BY_HARDWARE_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    let rows = client.query(&stmt, &[&hardware_id]).await.unwrap();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(rows.iter().map(firmware_from_row).collect()),
+    };
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_firmware(client: &Object, id: i32) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::FIRMWARES_SELECT_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    match client.query_opt(&stmt, &[&id]).await.unwrap() {
+        Some(row) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(firmware_from_row(&row)),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        None => error_response(messages::FIRMWARE_NOT_FOUND, StatusCode::NOT_FOUND),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn delete_firmware(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::FIRMWARES_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::FIRMWARES_DELETE_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    match transaction.execute(&stmt, &[&id]).await {
+        Ok(0) => error_response(messages::FIRMWARE_NOT_FOUND, StatusCode::NOT_FOUND),
+        Ok(_) => {
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::DELETE,
+                target_type: action::TARGET_FIRMWARE,
+                target_id: Some(id),
+                before,
+                after: None,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            if let Err(e) = fs::remove_file(firmware::path(id)).await {
+                tracing::warn!(error = %e, firmware_id = id, "failed to remove firmware file");
+            }
+            let response: ApiResponse<Firmware> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn rollout_firmware(
+    client: &Object,
+    id: i32,
+    data: FirmwareRollout,
+    owner: Option<i32>,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::FIRMWARES_SELECT_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    if client.query_opt(&stmt, &[&id]).await.unwrap().is_none() {
+        return error_response(messages::FIRMWARE_NOT_FOUND, StatusCode::NOT_FOUND);
+    }
+    let stmt = client
+        .prepare_typed_cached(
+            query::FIRMWARES_ROLLOUT,
+            &[Type::INT4, Type::INT4_ARRAY, Type::INT4, Type::INT4_ARRAY],
+        )
+        .await
+        .unwrap();
+    match client
+        .query(&stmt, &[&id, &data.node_ids, &owner, &data.group_ids])
+        .await
+    {
+        Ok(rows) => {
+            let mut assigned: Vec<i32> = rows.iter().map(|row| row.get(0)).collect();
+            assigned.sort_unstable();
+            let mut skipped: Vec<i32> = data
+                .node_ids
+                .into_iter()
+                .filter(|node_id| assigned.binary_search(node_id).is_err())
+                .collect();
+            skipped.sort_unstable();
+            skipped.dedup();
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(RolloutReport {
+                    firmware_id: id,
+                    assigned,
+                    skipped,
+                }),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn assigned_firmware(
+    client: &Object,
+    node_id: i32,
+) -> Result<Option<Firmware>, tokio_postgres::Error> {
+    let stmt = client
+        .prepare_typed_cached(query::FIRMWARES_SELECT_ASSIGNED_BY_NODE_ID, &[Type::INT4])
+        .await?;
+    Ok(client
+        .query_opt(&stmt, &[&node_id])
+        .await?
+        .as_ref()
+        .map(firmware_from_row))
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn check_firmware(
+    client: &Object,
+    node_id: i32,
+    version: Option<String>,
+) -> (Bytes, StatusCode) {
+    let result: Result<FirmwareCheck, tokio_postgres::Error> = async {
+        let current_version = match version {
+            Some(version) if version.len() <= config::FIRMWARE_MAX_VERSION_LEN => {
+                let stmt = client
+                    .prepare_typed_cached(
+                        query::NODES_UPDATE_FIRMWARE_VERSION,
+                        &[Type::INT4, Type::VARCHAR, Type::TIMESTAMP],
+                    )
+                    .await?;
+                client
+                    .execute(&stmt, &[&node_id, &version, &Utc::now().naive_utc()])
+                    .await?;
+                Some(version)
+            }
+            _ => None,
+        };
+        let firmware = assigned_firmware(client, node_id).await?;
+        Ok(FirmwareCheck {
+            update_available: firmware
+                .as_ref()
+                .is_some_and(|firmware| current_version.as_ref() != Some(&firmware.version)),
+            current_version,
+            firmware,
+        })
+    }
+    .await;
+    match result {
+        Ok(check) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(check),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_firmware_report(
+    client: &Object,
+    filter: FirmwareReportQuery,
+    owner: Option<i32>,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::FIRMWARES_REPORT, &[Type::INT4, Type::INT4])
+        .await
+        .unwrap();
+    let rows = client
+        .query(&stmt, &[&filter.hardware_id, &owner])
+        .await
+        .unwrap();
+    let entries: Vec<FirmwareReportEntry> = rows
+        .iter()
+        .map(|row| FirmwareReportEntry {
+            hardware_id: row.get(0),
+            running_version: row.get(1),
+            target_version: row.get(2),
+            nodes: row.get(3),
+            node_ids: row.get(4),
+        })
+        .collect();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(entries),
+    };
+    serialize_response(response, StatusCode::OK)
+}
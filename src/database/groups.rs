@@ -0,0 +1,380 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use deadpool_postgres::Object;
+use ntex::{http::StatusCode, util::Bytes};
+use tokio_postgres::{error::SqlState, types::Type, Row};
+use tracing::instrument;
+
+use crate::{
+    constant::{audit as action, config, messages, query},
+    database::audit,
+    models::{
+        audit::AuditEntry,
+        groups::{BulkReport, GroupMembers, NodeGroup, NodeGroupPayload, NodeTags, TagAssignment},
+        response::{ApiResponse, Data},
+    },
+    utils::{anomaly, http::serialize_response},
+};
+
+fn group_from_row(row: &Row) -> NodeGroup {
+    NodeGroup {
+        id: row.get(0),
+        user_id: row.get(1),
+        name: row.get(2),
+        description: row.get(3),
+        node_ids: row.get(4),
+    }
+}
+
+fn error_response(message: &str, status: StatusCode) -> (Bytes, StatusCode) {
+    let error_response: ApiResponse<NodeGroup> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    serialize_response(error_response, status)
+}
+
+fn valid_group(data: &NodeGroupPayload) -> bool {
+    !data.name.trim().is_empty()
+        && data.name.len() <= config::GROUP_MAX_NAME_LEN
+        && data
+            .description
+            .as_ref()
+            .is_none_or(|description| description.len() <= config::GROUP_MAX_NAME_LEN)
+}
+
+fn valid_tags(data: &TagAssignment) -> bool {
+    let valid_key = |key: &String| {
+        !key.is_empty() && key.len() <= config::TAG_MAX_KEY_LEN && !key.contains(':')
+    };
+    data.set.len() + data.remove.len() <= config::TAG_MAX_PER_REQUEST
+        && data
+            .set
+            .iter()
+            .all(|(key, value)| valid_key(key) && value.len() <= config::TAG_MAX_VALUE_LEN)
+        && data.remove.iter().all(valid_key)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_group(client: &Object, id: i32, owner: Option<i32>) -> Option<NodeGroup> {
+    let stmt = client
+        .prepare_typed_cached(query::GROUPS_SELECT_BY_ID, &[Type::INT4, Type::INT4])
+        .await
+        .unwrap();
+    client
+        .query_opt(&stmt, &[&id, &owner])
+        .await
+        .unwrap()
+        .as_ref()
+        .map(group_from_row)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_groups(client: &Object, owner: Option<i32>) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::GROUPS_SELECT, &[Type::INT4])
+        .await
+        .unwrap();
+    let rows = client.query(&stmt, &[&owner]).await.unwrap();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(rows.iter().map(group_from_row).collect()),
+    };
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_group(
+    client: &mut Object,
+    data: NodeGroupPayload,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    if !valid_group(&data) {
+        return error_response(messages::INVALID_GROUP, StatusCode::BAD_REQUEST);
+    }
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
+        .prepare_typed_cached(
+            query::GROUPS_INSERT,
+            &[Type::INT4, Type::VARCHAR, Type::VARCHAR],
+        )
+        .await
+        .unwrap();
+    match transaction
+        .query_one(
+            &stmt,
+            &[&user_id, &data.name.as_ref(), &data.description.as_deref()],
+        )
+        .await
+    {
+        Ok(row) => {
+            let id: i32 = row.get(0);
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::CREATE,
+                target_type: action::TARGET_GROUP,
+                target_id: Some(id),
+                before: None,
+                after: audit::snapshot(&transaction, query::GROUPS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            let response = ApiResponse {
+                message: messages::CREATED,
+                data: get_group(client, id, None)
+                    .await
+                    .map_or(Data::None, Data::Single),
+            };
+            serialize_response(response, StatusCode::CREATED)
+        }
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            error_response(messages::GROUP_EXISTS, StatusCode::CONFLICT)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn update_group(
+    client: &mut Object,
+    id: i32,
+    data: NodeGroupPayload,
+    user_id: i32,
+    owner: Option<i32>,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    if !valid_group(&data) {
+        return error_response(messages::INVALID_GROUP, StatusCode::BAD_REQUEST);
+    }
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::GROUPS_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(
+            query::GROUPS_UPDATE_BY_ID,
+            &[Type::INT4, Type::VARCHAR, Type::VARCHAR, Type::INT4],
+        )
+        .await
+        .unwrap();
+    match transaction
+        .execute(
+            &stmt,
+            &[
+                &id,
+                &data.name.as_ref(),
+                &data.description.as_deref(),
+                &owner,
+            ],
+        )
+        .await
+    {
+        Ok(0) => error_response(messages::GROUP_NOT_FOUND, StatusCode::NOT_FOUND),
+        Ok(_) => {
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::UPDATE,
+                target_type: action::TARGET_GROUP,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::GROUPS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            let response = ApiResponse {
+                message: messages::OK,
+                data: get_group(client, id, None)
+                    .await
+                    .map_or(Data::None, Data::Single),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            error_response(messages::GROUP_EXISTS, StatusCode::CONFLICT)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn delete_group(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    owner: Option<i32>,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::GROUPS_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::GROUPS_DELETE_BY_ID, &[Type::INT4, Type::INT4])
+        .await
+        .unwrap();
+    match transaction.query_opt(&stmt, &[&id, &owner]).await {
+        Ok(None) => error_response(messages::GROUP_NOT_FOUND, StatusCode::NOT_FOUND),
+        Ok(Some(row)) => {
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::DELETE,
+                target_type: action::TARGET_GROUP,
+                target_id: Some(id),
+                before,
+                after: None,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            for node_id in row.get::<_, Vec<i32>>(0) {
+                anomaly::reset(node_id);
+            }
+            let response: ApiResponse<NodeGroup> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_members(
+    client: &Object,
+    group: &NodeGroup,
+    data: GroupMembers,
+    owner: Option<i32>,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(
+            query::GROUP_MEMBERS_INSERT,
+            &[Type::INT4, Type::INT4_ARRAY, Type::INT4],
+        )
+        .await
+        .unwrap();
+    match client
+        .query(&stmt, &[&group.id, &data.node_ids, &owner])
+        .await
+    {
+        Ok(rows) => {
+            let updated: Vec<i32> = rows.iter().map(|row| row.get(0)).collect();
+            for node_id in &updated {
+                anomaly::reset(*node_id);
+            }
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(BulkReport::new(data.node_ids, updated)),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn remove_members(
+    client: &Object,
+    group: &NodeGroup,
+    data: GroupMembers,
+) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::GROUP_MEMBERS_DELETE, &[Type::INT4, Type::INT4_ARRAY])
+        .await
+        .unwrap();
+    match client.query(&stmt, &[&group.id, &data.node_ids]).await {
+        Ok(rows) => {
+            let updated: Vec<i32> = rows.iter().map(|row| row.get(0)).collect();
+            for node_id in &updated {
+                anomaly::reset(*node_id);
+            }
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(BulkReport::new(data.node_ids, updated)),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn assign_tags(
+    client: &mut Object,
+    data: TagAssignment,
+    owner: Option<i32>,
+) -> (Bytes, StatusCode) {
+    if !valid_tags(&data) {
+        return error_response(messages::INVALID_TAGS, StatusCode::BAD_REQUEST);
+    }
+    let (keys, values): (Vec<&str>, Vec<&str>) = data
+        .set
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .unzip();
+
+    let result: Result<Vec<i32>, tokio_postgres::Error> = async {
+        let transaction = client.transaction().await?;
+        let stmt = transaction
+            .prepare_typed_cached(
+                query::NODES_SELECT_IDS_BY_OWNER,
+                &[Type::INT4_ARRAY, Type::INT4],
+            )
+            .await?;
+        let node_ids: Vec<i32> = transaction
+            .query(&stmt, &[&data.node_ids, &owner])
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        let stmt = transaction
+            .prepare_typed_cached(
+                query::NODE_TAGS_DELETE,
+                &[Type::INT4_ARRAY, Type::VARCHAR_ARRAY],
+            )
+            .await?;
+        transaction
+            .execute(&stmt, &[&node_ids, &data.remove])
+            .await?;
+        let stmt = transaction
+            .prepare_typed_cached(
+                query::NODE_TAGS_UPSERT,
+                &[Type::INT4_ARRAY, Type::VARCHAR_ARRAY, Type::VARCHAR_ARRAY],
+            )
+            .await?;
+        transaction
+            .execute(&stmt, &[&node_ids, &keys, &values])
+            .await?;
+        transaction.commit().await?;
+        Ok(node_ids)
+    }
+    .await;
+
+    match result {
+        Ok(updated) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(BulkReport::new(data.node_ids, updated)),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_node_tags(client: &Object, node_id: i32) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::NODE_TAGS_SELECT_BY_NODE_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    let rows = client.query(&stmt, &[&node_id]).await.unwrap();
+    let tags: BTreeMap<String, String> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Single(NodeTags { node_id, tags }),
+    };
+    serialize_response(response, StatusCode::OK)
+}
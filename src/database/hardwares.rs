@@ -1,28 +1,47 @@
+use chrono::{NaiveDateTime, Utc};
 use deadpool_postgres::Object;
-use futures::StreamExt;
-use std::{borrow::Cow::Owned, str};
+use std::{borrow::Cow::Owned, net::IpAddr, str};
 use tokio_postgres::types::Type;
 
-use ntex::{
-    http::{Payload, StatusCode},
-    util::Bytes,
-};
+use ntex::{http::StatusCode, util::Bytes};
+use tracing::instrument;
 
 use crate::{
-    constant::{messages, query},
+    constant::{audit as action, config, messages, query},
+    database::{audit, builder::QueryBuilder},
     models::{
-        hardwares::{Hardware, HardwarePayload},
+        audit::AuditEntry,
+        deleted::Deleted,
+        hardwares::{Hardware, HardwareListQuery, HardwarePayload, HardwareSort},
         response::{ApiResponse, Data},
     },
     utils::http::serialize_response,
 };
 
-pub async fn get_all_hardware(client: &Object) -> (Bytes, StatusCode) {
+fn hardware_order(sort: HardwareSort) -> &'static str {
+    match sort {
+        HardwareSort::IdAsc => "id",
+        HardwareSort::IdDesc => "id DESC",
+        HardwareSort::NameAsc => "name, id",
+        HardwareSort::NameDesc => "name DESC, id",
+        HardwareSort::TypeAsc => "type, id",
+        HardwareSort::TypeDesc => "type DESC, id",
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_all_hardware(client: &Object, filter: HardwareListQuery) -> (Bytes, StatusCode) {
+    let mut builder = QueryBuilder::new(query::HARDWARES_SELECT);
+    builder
+        .filter("type = $?", Type::VARCHAR, filter.type_)
+        .search(&["name", "description"], filter.q.as_deref())
+        .order_by(hardware_order(filter.sort.unwrap_or(HardwareSort::IdAsc)))
+        .paginate(filter.limit, filter.offset, config::LIST_MAX_LIMIT);
     let stmt = client
-        .prepare_typed_cached(query::HARDWARES_SELECT, &[])
+        .prepare_typed_cached(builder.sql(), builder.types())
         .await
         .unwrap();
-    let rows = client.query(&stmt, &[]).await.unwrap();
+    let rows = client.query(&stmt, &builder.params()).await.unwrap();
 
     let mut hardwares = Vec::with_capacity(rows.len());
     for row in rows {
@@ -42,6 +61,7 @@ pub async fn get_all_hardware(client: &Object) -> (Bytes, StatusCode) {
     serialize_response(response, StatusCode::OK)
 }
 
+#[instrument(level = "debug", skip_all)]
 pub async fn get_one_hardware(client: &Object, id: i32) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::HARDWARES_SELECT_BY_ID, &[Type::INT4])
@@ -72,23 +92,13 @@ pub async fn get_one_hardware(client: &Object, id: i32) -> (Bytes, StatusCode) {
     serialize_response(response, StatusCode::OK)
 }
 
-pub async fn add_hardware(client: &Object, payload: &mut Payload) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = std::str::from_utf8(&buf).unwrap();
-    let data: HardwarePayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<Hardware> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
+#[instrument(level = "debug", skip_all)]
+pub async fn add_hardware(
+    client: &mut Object,
+    data: HardwarePayload,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
     if data.type_ != "sensor"
         && data.type_ != "single-board computer"
         && data.type_ != "microcontroller unit"
@@ -100,7 +110,8 @@ pub async fn add_hardware(client: &Object, payload: &mut Payload) -> (Bytes, Sta
         return serialize_response(error_response, StatusCode::BAD_REQUEST);
     }
 
-    let stmt = client
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(
             query::HARDWARES_INSERT,
             &[Type::VARCHAR, Type::VARCHAR, Type::VARCHAR],
@@ -108,8 +119,8 @@ pub async fn add_hardware(client: &Object, payload: &mut Payload) -> (Bytes, Sta
         .await
         .unwrap();
 
-    match client
-        .execute(
+    match transaction
+        .query_one(
             &stmt,
             &[
                 &data.name.as_ref(),
@@ -119,7 +130,20 @@ pub async fn add_hardware(client: &Object, payload: &mut Payload) -> (Bytes, Sta
         )
         .await
     {
-        Ok(_) => {
+        Ok(row) => {
+            let id: i32 = row.get(0);
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::CREATE,
+                target_type: action::TARGET_HARDWARE,
+                target_id: Some(id),
+                before: None,
+                after: audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             let response: ApiResponse<HardwarePayload> = ApiResponse {
                 message: messages::CREATED,
                 data: Data::None,
@@ -136,29 +160,17 @@ pub async fn add_hardware(client: &Object, payload: &mut Payload) -> (Bytes, Sta
     }
 }
 
+#[instrument(level = "debug", skip_all)]
 pub async fn update_hardware(
-    client: &Object,
+    client: &mut Object,
     id: i32,
-    payload: &mut Payload,
+    data: HardwarePayload,
+    user_id: i32,
+    ip: Option<IpAddr>,
 ) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = std::str::from_utf8(&buf).unwrap();
-    let data: HardwarePayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<Hardware> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
-    let stmt = client
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
         .prepare_typed_cached(
             query::HARDWARES_UPDATE_BY_ID,
             &[Type::VARCHAR, Type::VARCHAR, Type::VARCHAR, Type::INT4],
@@ -166,7 +178,7 @@ pub async fn update_hardware(
         .await
         .unwrap();
 
-    match client
+    match transaction
         .execute(
             &stmt,
             &[
@@ -186,6 +198,18 @@ pub async fn update_hardware(
                 };
                 return serialize_response(error_response, StatusCode::NOT_FOUND);
             }
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::UPDATE,
+                target_type: action::TARGET_HARDWARE,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             let response: ApiResponse<HardwarePayload> = ApiResponse {
                 message: messages::OK,
                 data: Data::None,
@@ -202,13 +226,120 @@ pub async fn update_hardware(
     }
 }
 
-pub async fn delete_hardware(client: &Object, id: i32) -> (Bytes, StatusCode) {
+#[instrument(level = "debug", skip_all)]
+pub async fn delete_hardware(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(
+            query::HARDWARES_DELETE_BY_ID,
+            &[Type::INT4, Type::TIMESTAMP],
+        )
+        .await
+        .unwrap();
+    match transaction
+        .execute(&stmt, &[&id, &Utc::now().naive_utc()])
+        .await
+    {
+        Ok(rows_deleted) => {
+            if rows_deleted > 0 {
+                let entry = AuditEntry {
+                    user_id: Some(user_id),
+                    ip,
+                    action: action::DELETE,
+                    target_type: action::TARGET_HARDWARE,
+                    target_id: Some(id),
+                    before,
+                    after: audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await,
+                };
+                if let Err(res) = audit::commit(transaction, entry).await {
+                    return res;
+                }
+            }
+            let response: ApiResponse<Hardware> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Hardware> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_deleted_hardware(client: &Object) -> (Bytes, StatusCode) {
     let stmt = client
-        .prepare_typed_cached(query::HARDWARES_DELETE_BY_ID, &[Type::INT4])
+        .prepare_typed_cached(query::HARDWARES_SELECT_DELETED, &[])
         .await
         .unwrap();
-    match client.execute(&stmt, &[&id]).await {
-        Ok(_) => {
+    let rows = client.query(&stmt, &[]).await.unwrap();
+
+    let mut hardwares = Vec::with_capacity(rows.len());
+    for row in rows {
+        hardwares.push(Deleted {
+            item: Hardware {
+                id: row.get(0),
+                name: Owned(row.get::<_, &str>(1).to_string()),
+                type_: Owned(row.get::<_, &str>(2).to_string()),
+                description: Owned(row.get::<_, &str>(3).to_string()),
+            },
+            deleted_at: row.get::<_, NaiveDateTime>(4),
+        });
+    }
+
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(hardwares),
+    };
+
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn restore_hardware(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::HARDWARES_RESTORE_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    match transaction.execute(&stmt, &[&id]).await {
+        Ok(rows_updated) => {
+            if rows_updated == 0 {
+                let error_response: ApiResponse<Hardware> = ApiResponse {
+                    message: messages::NOTHING_TO_RESTORE,
+                    data: Data::None,
+                };
+                return serialize_response(error_response, StatusCode::NOT_FOUND);
+            }
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::RESTORE,
+                target_type: action::TARGET_HARDWARE,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::HARDWARES_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             let response: ApiResponse<Hardware> = ApiResponse {
                 message: messages::OK,
                 data: Data::None,
@@ -1,4 +1,12 @@
+pub mod anomalies;
+pub mod audit;
+pub mod builder;
+pub mod commands;
 pub mod feeds;
+pub mod firmwares;
+pub mod groups;
 pub mod hardwares;
 pub mod nodes;
+pub mod pool;
+pub mod shadows;
 pub mod users;
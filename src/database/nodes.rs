@@ -1,102 +1,247 @@
-use chrono::NaiveDateTime;
-use deadpool_postgres::Object;
-use futures::StreamExt;
-use std::{borrow::Cow::Owned, collections::HashMap, str};
-use tokio_postgres::types::Type;
-
-use ntex::{
-    http::{Payload, StatusCode},
-    util::Bytes,
+use chrono::{Duration, NaiveDateTime, Utc};
+use deadpool_postgres::{Object, Transaction};
+use futures::{channel::mpsc::Sender, TryStreamExt};
+use std::{borrow::Cow::Owned, io, net::IpAddr, pin::pin, str};
+use tokio_postgres::{
+    types::{ToSql, Type},
+    Row,
 };
 
+use ntex::{http::StatusCode, util::Bytes};
+use tracing::instrument;
+
 use crate::{
-    constant::{messages, query},
+    constant::{audit as action, config, messages, query},
+    database::{audit, builder::QueryBuilder},
     models::{
-        feeds::Feed,
+        audit::AuditEntry,
+        deleted::Deleted,
+        feeds::{Feed, FeedRollup, NodeFeedsQuery, NodeTrackQuery, Resolution},
         hardwares::Hardware,
-        nodes::{Node, NodePayload, NodeWithFeed},
+        nodes::{
+            Coordinates, GeoJsonFeature, GeoJsonPoint, GeoJsonTrackFeature, Node, NodeListQuery,
+            NodePayload, NodeSort, NodeStatus, TrackPoint, VirtualSensor,
+        },
         response::{ApiResponse, Data},
     },
-    utils::http::serialize_response,
+    utils::{
+        anomaly,
+        expression::Expression,
+        http::{serialize_response, JsonStream},
+    },
 };
 
-pub async fn get_all_nodes(client: &Object, user_id: i32, is_admin: bool) -> (Bytes, StatusCode) {
-    let rows = if is_admin {
-        let stmt = client
-            .prepare_typed_cached(query::NODES_SELECT, &[])
-            .await
-            .unwrap();
-        client.query(&stmt, &[]).await.unwrap()
-    } else {
-        let stmt = client
-            .prepare_typed_cached(query::NODES_SELECT_BY_USER_OR_ISPUBLIC, &[Type::INT4])
-            .await
-            .unwrap();
-        client.query(&stmt, &[&user_id]).await.unwrap()
+fn node_from_row(row: &Row) -> Node {
+    let last_seen_at: Option<NaiveDateTime> = row.get(11);
+    let heartbeat = row
+        .get::<_, Option<i32>>(10)
+        .unwrap_or(config::NODE_HEARTBEAT_INTERVAL_SECS);
+    let status = match last_seen_at {
+        Some(time) if Utc::now().naive_utc() - time <= Duration::seconds(heartbeat.into()) => {
+            NodeStatus::Online
+        }
+        _ => NodeStatus::Offline,
     };
-
-    let mut nodes = Vec::with_capacity(rows.len());
-    for row in rows {
-        nodes.push(Node {
-            id: row.get(0),
-            user_id: row.get(1),
-            hardware_id: row.get(2),
-            name: Owned(row.get::<_, &str>(3).to_string()),
-            location: Owned(row.get::<_, &str>(4).to_string()),
-            hardware_sensor_ids: row.get::<_, Vec<i32>>(5),
-            hardware_sensor_names: row
-                .get::<_, Vec<&str>>(6)
-                .iter()
-                .map(|s| Owned(s.to_string()))
-                .collect(),
-            ispublic: row.get(7),
-        });
+    Node {
+        id: row.get(0),
+        user_id: row.get(1),
+        hardware_id: row.get(2),
+        name: Owned(row.get::<_, &str>(3).to_string()),
+        location: Owned(row.get::<_, &str>(4).to_string()),
+        hardware_sensor_ids: row.get::<_, Vec<i32>>(5),
+        hardware_sensor_names: row
+            .get::<_, Vec<&str>>(6)
+            .iter()
+            .map(|s| Owned(s.to_string()))
+            .collect(),
+        ispublic: row.get(7),
+        feeds_retention_days: row.get(9),
+        heartbeat_interval_secs: row.get(10),
+        last_seen_at,
+        last_value: row.get(12),
+        status,
+        coordinates: match (row.get(13), row.get(14)) {
+            (Some(latitude), Some(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+                altitude: row.get(15),
+            }),
+            _ => None,
+        },
+        address: row.get::<_, Option<&str>>(16).map(|s| Owned(s.to_string())),
+        virtual_sensors: row
+            .get::<_, Vec<&str>>(17)
+            .iter()
+            .zip(row.get::<_, Vec<&str>>(18))
+            .map(|(name, expression)| VirtualSensor {
+                name: Owned(name.to_string()),
+                expression: Owned(expression.to_string()),
+            })
+            .collect(),
     }
-    let id_nodes: Vec<i32> = nodes.iter().map(|node| node.id).collect();
-    let stmt = client
-        .prepare_typed_cached(query::FEEDS_SELECT_BY_NODE_IDS, &[Type::INT4_ARRAY])
-        .await
-        .unwrap();
-    let feed_rows = client.query(&stmt, &[&id_nodes]).await.unwrap();
-    let mut feeds_by_node: HashMap<i32, Vec<Feed>> = HashMap::new();
-    for row in feed_rows {
-        let feed = Feed {
-            time: row.get::<_, NaiveDateTime>(0),
-            value: row.get::<_, Vec<f64>>(1),
-            node_id: row.get(2),
-        };
-        feeds_by_node.entry(feed.node_id).or_default().push(feed);
+}
+
+fn validate_virtual_sensors(data: &NodePayload) -> Result<(), &'static str> {
+    for (offset, sensor) in data.virtual_sensors.iter().enumerate() {
+        let expression = Expression::parse(&sensor.expression)?;
+        if expression
+            .max_index()
+            .is_some_and(|index| index >= data.hardware_sensor_ids.len() + offset)
+        {
+            return Err(messages::INVALID_VIRTUAL_SENSOR);
+        }
     }
+    Ok(())
+}
 
-    let mut node_with_feed = Vec::with_capacity(nodes.len());
-    for node in nodes {
-        let node_feeds = feeds_by_node.remove(&node.id).unwrap_or_default();
-        node_with_feed.push(NodeWithFeed {
-            node,
-            feeds: node_feeds,
-        });
+fn node_order(sort: NodeSort) -> &'static str {
+    match sort {
+        NodeSort::IdAsc => "id",
+        NodeSort::IdDesc => "id DESC",
+        NodeSort::NameAsc => "name, id",
+        NodeSort::NameDesc => "name DESC, id",
+        NodeSort::LastSeenAtAsc => "last_seen_at, id",
+        NodeSort::LastSeenAtDesc => "last_seen_at DESC NULLS LAST, id",
     }
+}
 
-    let response = ApiResponse {
-        message: messages::OK,
-        data: Data::Multiple(node_with_feed),
+fn node_filters(builder: &mut QueryBuilder, filter: &NodeListQuery) {
+    let bbox_clause = match filter.bbox {
+        Some([min_lon, _, max_lon, _]) if min_lon > max_lon => {
+            query::NODES_FILTER_BBOX_ANTIMERIDIAN
+        }
+        _ => query::NODES_FILTER_BBOX,
     };
-
-    serialize_response(response, StatusCode::OK)
+    let near = filter.near.map(|[lat, lon]| {
+        [
+            lat,
+            lon,
+            filter.radius.unwrap_or(config::NEAR_DEFAULT_RADIUS_KM),
+        ]
+    });
+    let (tag_key, tag_pair) = filter.tag_filter();
+    builder
+        .filter("user_id = $?", Type::INT4, filter.owner)
+        .filter("hardware_id = $?", Type::INT4, filter.hardware_id)
+        .filter("ispublic = $?", Type::BOOL, filter.ispublic)
+        .filter(
+            "hardware_sensor_ids @> ARRAY[$?]",
+            Type::INT4,
+            filter.has_sensor,
+        )
+        .filter(query::NODES_FILTER_GROUP, Type::INT4, filter.group)
+        .filter(query::NODES_FILTER_TAG_KEY, Type::VARCHAR, tag_key)
+        .filter_all(query::NODES_FILTER_TAG, Type::VARCHAR, tag_pair)
+        .filter_all(bbox_clause, Type::FLOAT8, filter.bbox)
+        .filter_all(query::NODES_FILTER_NEAR, Type::FLOAT8, near)
+        .search(&["name", "location", "address"], filter.q.as_deref())
+        .order_by(node_order(filter.sort.unwrap_or(NodeSort::IdAsc)))
+        .paginate(filter.limit, filter.offset, config::LIST_MAX_LIMIT);
 }
 
-pub async fn get_node_with_feeds(
-    client: &Object,
-    id: i32,
+#[instrument(level = "debug", skip_all)]
+pub async fn stream_all_nodes(
+    client: Object,
     user_id: i32,
     is_admin: bool,
-) -> (Bytes, StatusCode) {
-    let rows = if is_admin {
+    filter: NodeListQuery,
+    tx: Sender<io::Result<Bytes>>,
+) {
+    let mut out = JsonStream::new(tx);
+    let result: io::Result<()> = async {
+        let mut builder = QueryBuilder::new(query::NODES_SELECT);
+        if !is_admin {
+            builder.filter("(user_id = $? OR ispublic)", Type::INT4, Some(user_id));
+        }
+        node_filters(&mut builder, &filter);
+        let stmt = client
+            .prepare_typed_cached(builder.sql(), builder.types())
+            .await
+            .map_err(io::Error::other)?;
+        let mut rows = pin!(client
+            .query_raw(&stmt, builder.params())
+            .await
+            .map_err(io::Error::other)?);
+
+        out.write_raw(b"{\"message\":").await?;
+        out.write(&messages::OK).await?;
+        out.write_raw(b",\"data\":[").await?;
+        let mut first = true;
+        while let Some(row) = rows.try_next().await.map_err(io::Error::other)? {
+            if !first {
+                out.write_raw(b",").await?;
+            }
+            first = false;
+            out.write_raw(b"{\"node\":").await?;
+            out.write(&node_from_row(&row)).await?;
+            out.write_raw(b"}").await?;
+        }
+        out.write_raw(b"]}").await
+    }
+    .await;
+
+    out.finish(result).await;
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn stream_public_nodes_geojson(
+    client: Object,
+    filter: NodeListQuery,
+    tx: Sender<io::Result<Bytes>>,
+) {
+    let mut out = JsonStream::new(tx);
+    let result: io::Result<()> = async {
+        let mut builder = QueryBuilder::new(query::NODES_SELECT_PUBLIC_LOCATED);
+        node_filters(&mut builder, &filter);
+        let stmt = client
+            .prepare_typed_cached(builder.sql(), builder.types())
+            .await
+            .map_err(io::Error::other)?;
+        let mut rows = pin!(client
+            .query_raw(&stmt, builder.params())
+            .await
+            .map_err(io::Error::other)?);
+
+        out.write_raw(b"{\"type\":\"FeatureCollection\",\"features\":[")
+            .await?;
+        let mut first = true;
+        while let Some(row) = rows.try_next().await.map_err(io::Error::other)? {
+            let node = node_from_row(&row);
+            let Some(coordinates) = node.coordinates else {
+                continue;
+            };
+            if !first {
+                out.write_raw(b",").await?;
+            }
+            first = false;
+            let mut position = vec![coordinates.longitude, coordinates.latitude];
+            position.extend(coordinates.altitude);
+            out.write(&GeoJsonFeature {
+                type_: "Feature",
+                id: node.id,
+                geometry: GeoJsonPoint {
+                    type_: "Point",
+                    coordinates: position,
+                },
+                properties: node,
+            })
+            .await?;
+        }
+        out.write_raw(b"]}").await
+    }
+    .await;
+
+    out.finish(result).await;
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_node(client: &Object, id: i32, user_id: i32, is_admin: bool) -> Option<Node> {
+    let row = if is_admin {
         let stmt = client
             .prepare_typed_cached(query::NODES_SELECT_BY_ID, &[Type::INT4])
             .await
             .unwrap();
-        client.query(&stmt, &[&id]).await.unwrap()
+        client.query_opt(&stmt, &[&id]).await.unwrap()
     } else {
         let stmt = client
             .prepare_typed_cached(
@@ -105,74 +250,187 @@ pub async fn get_node_with_feeds(
             )
             .await
             .unwrap();
-        client.query(&stmt, &[&id, &user_id]).await.unwrap()
+        client.query_opt(&stmt, &[&id, &user_id]).await.unwrap()
     };
 
-    if rows.is_empty() {
-        let error_response: ApiResponse<NodePayload> = ApiResponse {
-            message: messages::NODE_NOT_FOUND,
-            data: Data::None,
-        };
-        return serialize_response(error_response, StatusCode::NOT_FOUND);
-    }
+    row.as_ref().map(node_from_row)
+}
 
-    let node = Node {
-        id: rows[0].get(0),
-        user_id: rows[0].get(1),
-        hardware_id: rows[0].get(2),
-        name: Owned(rows[0].get::<_, &str>(3).to_string()),
-        location: Owned(rows[0].get::<_, &str>(4).to_string()),
-        hardware_sensor_ids: rows[0].get::<_, Vec<i32>>(5),
-        hardware_sensor_names: rows[0]
-            .get::<_, Vec<&str>>(6)
-            .iter()
-            .map(|s| Owned(s.to_string()))
-            .collect(),
-        ispublic: rows[0].get(7),
+fn resolution(filter: &NodeFeedsQuery) -> Resolution {
+    if let Some(resolution) = filter.resolution {
+        return resolution;
+    }
+    let Some(from) = filter.from else {
+        return Resolution::Raw;
     };
+    let span = filter.to.unwrap_or_else(|| Utc::now().naive_utc()) - from;
+    if span >= Duration::days(config::ROLLUP_DAILY_MIN_RANGE_DAYS) {
+        Resolution::Daily
+    } else if span >= Duration::hours(config::ROLLUP_HOURLY_MIN_RANGE_HOURS) {
+        Resolution::Hourly
+    } else {
+        Resolution::Raw
+    }
+}
 
-    let stmt = client
-        .prepare_typed_cached(query::FEEDS_SELECT_BY_NODE_ID, &[Type::INT4])
-        .await
-        .unwrap();
+#[instrument(level = "debug", skip_all)]
+pub async fn stream_node_with_feeds(
+    client: Object,
+    node: Node,
+    filter: NodeFeedsQuery,
+    tx: Sender<io::Result<Bytes>>,
+) {
+    let mut out = JsonStream::new(tx);
+    let resolution = resolution(&filter);
+    let channels = node.hardware_sensor_ids.len() + node.virtual_sensors.len();
+    let result: io::Result<()> = async {
+        let stmt = match resolution {
+            Resolution::Raw => query::FEEDS_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+            Resolution::Hourly => query::FEEDS_HOURLY_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+            Resolution::Daily => query::FEEDS_DAILY_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+        };
+        let stmt = client
+            .prepare_typed_cached(stmt, &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP])
+            .await
+            .map_err(io::Error::other)?;
+        let params: [&(dyn ToSql + Sync); 3] = [&node.id, &filter.from, &filter.to];
+        let mut feed_rows = pin!(client
+            .query_raw(&stmt, params)
+            .await
+            .map_err(io::Error::other)?);
 
-    let feeds = client.query(&stmt, &[&id]).await.unwrap();
-    let mut feeds_data = Vec::with_capacity(feeds.len());
-    for row in feeds {
-        feeds_data.push(Feed {
-            node_id: row.get(0),
-            time: row.get::<_, NaiveDateTime>(1),
-            value: row.get::<_, Vec<f64>>(2),
-        });
+        out.write_raw(b"{\"message\":").await?;
+        out.write(&messages::OK).await?;
+        out.write_raw(b",\"data\":{\"node\":").await?;
+        out.write(&node).await?;
+        out.write_raw(b",\"resolution\":").await?;
+        out.write(&resolution).await?;
+        out.write_raw(b",\"feeds\":[").await?;
+        let mut first = true;
+        while let Some(row) = feed_rows.try_next().await.map_err(io::Error::other)? {
+            if !first {
+                out.write_raw(b",").await?;
+            }
+            first = false;
+            if resolution == Resolution::Raw {
+                out.write(&Feed {
+                    node_id: node.id,
+                    time: row.get::<_, NaiveDateTime>(0),
+                    value: row.get::<_, Vec<f64>>(1),
+                })
+                .await?;
+            } else {
+                let sensors: Vec<i32> = row.get(1);
+                let (min, max, avg, count): (Vec<f64>, Vec<f64>, Vec<f64>, Vec<i64>) =
+                    (row.get(2), row.get(3), row.get(4), row.get(5));
+                let rows: Vec<(i32, f64, f64, f64, i64)> = sensors
+                    .into_iter()
+                    .zip(min)
+                    .zip(max)
+                    .zip(avg)
+                    .zip(count)
+                    .map(|((((sensor, min), max), avg), count)| (sensor, min, max, avg, count))
+                    .collect();
+                out.write(&FeedRollup::new(row.get(0), channels, &rows))
+                    .await?;
+            }
+        }
+        out.write_raw(b"]}}").await
     }
-    let response = ApiResponse {
-        message: messages::OK,
-        data: Data::Single(NodeWithFeed {
-            node,
-            feeds: feeds_data,
-        }),
-    };
-    serialize_response(response, StatusCode::OK)
+    .await;
+
+    out.finish(result).await;
 }
 
-pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
+#[instrument(level = "debug", skip_all)]
+pub async fn stream_node_track(
+    client: Object,
+    node: Node,
+    filter: NodeTrackQuery,
+    tx: Sender<io::Result<Bytes>>,
+) {
+    let mut out = JsonStream::new(tx);
+    let result: io::Result<()> = async {
+        let stmt = client
+            .prepare_typed_cached(
+                query::NODE_LOCATIONS_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+                &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP],
+            )
+            .await
+            .map_err(io::Error::other)?;
+        let params: [&(dyn ToSql + Sync); 3] = [&node.id, &filter.from, &filter.to];
+        let mut rows = pin!(client
+            .query_raw(&stmt, params)
+            .await
+            .map_err(io::Error::other)?);
 
-    let data = str::from_utf8(&buf).unwrap();
-    let data: NodePayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<NodePayload> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
+        out.write_raw(b"{\"type\":\"FeatureCollection\",\"id\":")
+            .await?;
+        out.write(&node.id).await?;
+        out.write_raw(b",\"name\":").await?;
+        out.write(&node.name).await?;
+        out.write_raw(b",\"features\":[").await?;
+        let mut first = true;
+        while let Some(row) = rows.try_next().await.map_err(io::Error::other)? {
+            if !first {
+                out.write_raw(b",").await?;
+            }
+            first = false;
+            let mut position = vec![row.get::<_, f64>(2), row.get::<_, f64>(1)];
+            position.extend(row.get::<_, Option<f64>>(3));
+            out.write(&GeoJsonTrackFeature {
+                type_: "Feature",
+                geometry: GeoJsonPoint {
+                    type_: "Point",
+                    coordinates: position,
+                },
+                properties: TrackPoint { time: row.get(0) },
+            })
+            .await?;
         }
-    };
+        out.write_raw(b"]}").await
+    }
+    .await;
+
+    out.finish(result).await;
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_node_sensor_names(
+    client: &Object,
+    id: i32,
+    user_id: i32,
+    is_admin: bool,
+) -> Option<Vec<String>> {
+    get_node(client, id, user_id, is_admin).await.map(|node| {
+        node.hardware_sensor_names
+            .into_iter()
+            .chain(node.virtual_sensors.into_iter().map(|sensor| sensor.name))
+            .map(|name| name.into_owned())
+            .collect()
+    })
+}
 
+#[instrument(level = "debug", skip_all)]
+pub async fn get_group_nodes(
+    client: &Object,
+    group_id: i32,
+    owner: Option<i32>,
+) -> Result<Vec<Node>, tokio_postgres::Error> {
+    let stmt = client
+        .prepare_typed_cached(query::NODES_SELECT_BY_GROUP_ID, &[Type::INT4, Type::INT4])
+        .await?;
+    let rows = client.query(&stmt, &[&group_id, &owner]).await?;
+    Ok(rows.iter().map(node_from_row).collect())
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn add_node(
+    client: &mut Object,
+    data: NodePayload,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::HARDWARES_SELECT_BY_ID, &[Type::INT4])
         .await
@@ -208,6 +466,40 @@ pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (
         return serialize_response(error_response, StatusCode::BAD_REQUEST);
     }
 
+    if data.feeds_retention_days.is_some_and(|days| days <= 0) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_RETENTION_DAYS,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    if data.heartbeat_interval_secs.is_some_and(|secs| secs <= 0) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_HEARTBEAT_INTERVAL,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    if data.coordinates.is_some_and(|c| !c.is_valid()) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_COORDINATES,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    if let Err(message) = validate_virtual_sensors(&data) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    let (virtual_names, virtual_expressions): (Vec<&str>, Vec<&str>) = data
+        .virtual_sensors
+        .iter()
+        .map(|sensor| (sensor.name.as_ref(), sensor.expression.as_ref()))
+        .unzip();
+
     for id in &data.hardware_sensor_ids {
         let rows = client.query(&stmt, &[id]).await.unwrap();
         if rows.is_empty() {
@@ -232,7 +524,8 @@ pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (
         }
     }
 
-    let stmt = client
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(
             query::NODES_INSERT,
             &[
@@ -243,13 +536,21 @@ pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (
                 Type::INT4_ARRAY,
                 Type::TEXT_ARRAY,
                 Type::BOOL,
+                Type::INT4,
+                Type::INT4,
+                Type::FLOAT8,
+                Type::FLOAT8,
+                Type::FLOAT8,
+                Type::TEXT,
+                Type::TEXT_ARRAY,
+                Type::TEXT_ARRAY,
             ],
         )
         .await
         .unwrap();
 
-    match client
-        .execute(
+    match transaction
+        .query_one(
             &stmt,
             &[
                 &user_id,
@@ -259,11 +560,32 @@ pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (
                 &data.hardware_sensor_ids,
                 &data.hardware_sensor_names,
                 &data.ispublic,
+                &data.feeds_retention_days,
+                &data.heartbeat_interval_secs,
+                &data.coordinates.map(|c| c.latitude),
+                &data.coordinates.map(|c| c.longitude),
+                &data.coordinates.and_then(|c| c.altitude),
+                &data.address.as_deref(),
+                &virtual_names,
+                &virtual_expressions,
             ],
         )
         .await
     {
-        Ok(_) => {
+        Ok(row) => {
+            let id: i32 = row.get(0);
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::CREATE,
+                target_type: action::TARGET_NODE,
+                target_id: Some(id),
+                before: None,
+                after: audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             let response: ApiResponse<NodePayload> = ApiResponse {
                 message: messages::CREATED,
                 data: Data::None,
@@ -280,32 +602,68 @@ pub async fn add_node(client: &Object, payload: &mut Payload, user_id: i32) -> (
     }
 }
 
+async fn prune_detectors(
+    transaction: &Transaction<'_>,
+    id: i32,
+    data: &NodePayload,
+) -> Result<u64, tokio_postgres::Error> {
+    let channels = (data.hardware_sensor_ids.len() + data.virtual_sensors.len()) as i32;
+    let stmt = transaction
+        .prepare_typed_cached(
+            query::ANOMALY_DETECTORS_DELETE_OUT_OF_RANGE,
+            &[Type::INT4, Type::INT4],
+        )
+        .await?;
+    transaction.execute(&stmt, &[&id, &channels]).await
+}
+
+#[instrument(level = "debug", skip_all)]
 pub async fn update_node(
-    client: &Object,
+    client: &mut Object,
     id: i32,
-    payload: &mut Payload,
+    data: NodePayload,
     user_id: i32,
     is_admin: bool,
+    ip: Option<IpAddr>,
 ) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
+    if data.feeds_retention_days.is_some_and(|days| days <= 0) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_RETENTION_DAYS,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
     }
+    if data.heartbeat_interval_secs.is_some_and(|secs| secs <= 0) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_HEARTBEAT_INTERVAL,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    if data.coordinates.is_some_and(|c| !c.is_valid()) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message: messages::INVALID_COORDINATES,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    if let Err(message) = validate_virtual_sensors(&data) {
+        let error_response: ApiResponse<NodePayload> = ApiResponse {
+            message,
+            data: Data::None,
+        };
+        return serialize_response(error_response, StatusCode::BAD_REQUEST);
+    }
+    let (virtual_names, virtual_expressions): (Vec<&str>, Vec<&str>) = data
+        .virtual_sensors
+        .iter()
+        .map(|sensor| (sensor.name.as_ref(), sensor.expression.as_ref()))
+        .unzip();
 
-    let data = str::from_utf8(&buf).unwrap();
-    let data: NodePayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<NodePayload> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await;
     if is_admin {
-        let stmt = client
+        let stmt = transaction
             .prepare_typed_cached(
                 query::NODES_UPDATE_BY_ID,
                 &[
@@ -316,11 +674,19 @@ pub async fn update_node(
                     Type::TEXT_ARRAY,
                     Type::BOOL,
                     Type::INT4,
+                    Type::INT4,
+                    Type::INT4,
+                    Type::FLOAT8,
+                    Type::FLOAT8,
+                    Type::FLOAT8,
+                    Type::TEXT,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
                 ],
             )
             .await
             .unwrap();
-        match client
+        match transaction
             .execute(
                 &stmt,
                 &[
@@ -331,6 +697,14 @@ pub async fn update_node(
                     &data.hardware_sensor_names,
                     &data.ispublic,
                     &id,
+                    &data.feeds_retention_days,
+                    &data.heartbeat_interval_secs,
+                    &data.coordinates.map(|c| c.latitude),
+                    &data.coordinates.map(|c| c.longitude),
+                    &data.coordinates.and_then(|c| c.altitude),
+                    &data.address.as_deref(),
+                    &virtual_names,
+                    &virtual_expressions,
                 ],
             )
             .await
@@ -343,6 +717,26 @@ pub async fn update_node(
                     };
                     return serialize_response(error_response, StatusCode::NOT_FOUND);
                 }
+                if let Err(e) = prune_detectors(&transaction, id, &data).await {
+                    let error_response: ApiResponse<NodePayload> = ApiResponse {
+                        message: &e.to_string(),
+                        data: Data::None,
+                    };
+                    return serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR);
+                }
+                let entry = AuditEntry {
+                    user_id: Some(user_id),
+                    ip,
+                    action: action::UPDATE,
+                    target_type: action::TARGET_NODE,
+                    target_id: Some(id),
+                    before,
+                    after: audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await,
+                };
+                if let Err(res) = audit::commit(transaction, entry).await {
+                    return res;
+                }
+                anomaly::reset(id);
                 let response: ApiResponse<NodePayload> = ApiResponse {
                     message: messages::OK,
                     data: Data::None,
@@ -358,7 +752,7 @@ pub async fn update_node(
             }
         }
     } else {
-        let stmt = client
+        let stmt = transaction
             .prepare_typed_cached(
                 query::NODES_UPDATE_BY_ID_AND_USER_ID,
                 &[
@@ -370,12 +764,20 @@ pub async fn update_node(
                     Type::BOOL,
                     Type::INT4,
                     Type::INT4,
+                    Type::INT4,
+                    Type::INT4,
+                    Type::FLOAT8,
+                    Type::FLOAT8,
+                    Type::FLOAT8,
+                    Type::TEXT,
+                    Type::TEXT_ARRAY,
+                    Type::TEXT_ARRAY,
                 ],
             )
             .await
             .unwrap();
 
-        match client
+        match transaction
             .execute(
                 &stmt,
                 &[
@@ -387,6 +789,14 @@ pub async fn update_node(
                     &data.ispublic,
                     &id,
                     &user_id,
+                    &data.feeds_retention_days,
+                    &data.heartbeat_interval_secs,
+                    &data.coordinates.map(|c| c.latitude),
+                    &data.coordinates.map(|c| c.longitude),
+                    &data.coordinates.and_then(|c| c.altitude),
+                    &data.address.as_deref(),
+                    &virtual_names,
+                    &virtual_expressions,
                 ],
             )
             .await
@@ -399,6 +809,26 @@ pub async fn update_node(
                     };
                     return serialize_response(error_response, StatusCode::NOT_FOUND);
                 }
+                if let Err(e) = prune_detectors(&transaction, id, &data).await {
+                    let error_response: ApiResponse<NodePayload> = ApiResponse {
+                        message: &e.to_string(),
+                        data: Data::None,
+                    };
+                    return serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR);
+                }
+                let entry = AuditEntry {
+                    user_id: Some(user_id),
+                    ip,
+                    action: action::UPDATE,
+                    target_type: action::TARGET_NODE,
+                    target_id: Some(id),
+                    before,
+                    after: audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await,
+                };
+                if let Err(res) = audit::commit(transaction, entry).await {
+                    return res;
+                }
+                anomaly::reset(id);
                 let response: ApiResponse<NodePayload> = ApiResponse {
                     message: messages::OK,
                     data: Data::Single(data),
@@ -416,56 +846,139 @@ pub async fn update_node(
     }
 }
 
+#[instrument(level = "debug", skip_all)]
 pub async fn delete_node(
-    client: &Object,
+    client: &mut Object,
     id: i32,
     user_id: i32,
     is_admin: bool,
+    ip: Option<IpAddr>,
 ) -> (Bytes, StatusCode) {
-    if is_admin {
-        let stmt = client
-            .prepare_typed_cached(query::NODES_DELETE_BY_ID, &[Type::INT4])
+    let deleted_at = Utc::now().naive_utc();
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await;
+    let result = if is_admin {
+        let stmt = transaction
+            .prepare_typed_cached(query::NODES_DELETE_BY_ID, &[Type::INT4, Type::TIMESTAMP])
             .await
             .unwrap();
-        match client.execute(&stmt, &[&id]).await {
-            Ok(_) => {
-                let response: ApiResponse<NodePayload> = ApiResponse {
-                    message: messages::OK,
-                    data: Data::None,
-                };
-                serialize_response(response, StatusCode::OK)
-            }
-            Err(e) => {
-                let error_response: ApiResponse<NodePayload> = ApiResponse {
-                    message: &e.to_string(),
-                    data: Data::None,
-                };
-                serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
-            }
-        }
+        transaction.execute(&stmt, &[&id, &deleted_at]).await
     } else {
-        let stmt = client
+        let stmt = transaction
             .prepare_typed_cached(
                 query::NODES_DELETE_BY_ID_AND_USER_ID,
-                &[Type::INT4, Type::INT4],
+                &[Type::INT4, Type::INT4, Type::TIMESTAMP],
             )
             .await
             .unwrap();
-        match client.execute(&stmt, &[&id, &user_id]).await {
-            Ok(_) => {
-                let response: ApiResponse<NodePayload> = ApiResponse {
-                    message: messages::OK,
-                    data: Data::None,
+        transaction
+            .execute(&stmt, &[&id, &user_id, &deleted_at])
+            .await
+    };
+
+    match result {
+        Ok(rows_deleted) => {
+            if rows_deleted > 0 {
+                let entry = AuditEntry {
+                    user_id: Some(user_id),
+                    ip,
+                    action: action::DELETE,
+                    target_type: action::TARGET_NODE,
+                    target_id: Some(id),
+                    before,
+                    after: audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await,
                 };
-                serialize_response(response, StatusCode::OK)
+                if let Err(res) = audit::commit(transaction, entry).await {
+                    return res;
+                }
+                anomaly::evict(id);
             }
-            Err(e) => {
-                let error_response: ApiResponse<NodePayload> = ApiResponse {
-                    message: &e.to_string(),
+            let response: ApiResponse<NodePayload> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<NodePayload> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_deleted_nodes(client: &Object) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::NODES_SELECT_DELETED, &[])
+        .await
+        .unwrap();
+    let rows = client.query(&stmt, &[]).await.unwrap();
+
+    let mut nodes = Vec::with_capacity(rows.len());
+    for row in rows {
+        nodes.push(Deleted {
+            item: node_from_row(&row),
+            deleted_at: row.get::<_, NaiveDateTime>(8),
+        });
+    }
+
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(nodes),
+    };
+
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn restore_node(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::NODES_RESTORE_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    match transaction.execute(&stmt, &[&id]).await {
+        Ok(rows_updated) => {
+            if rows_updated == 0 {
+                let error_response: ApiResponse<Node> = ApiResponse {
+                    message: messages::NOTHING_TO_RESTORE,
                     data: Data::None,
                 };
-                serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+                return serialize_response(error_response, StatusCode::NOT_FOUND);
+            }
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::RESTORE,
+                target_type: action::TARGET_NODE,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::NODES_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
             }
+            let response: ApiResponse<Node> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<Node> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
@@ -0,0 +1,149 @@
+use std::{
+    env, fs,
+    fs::File,
+    io::BufReader,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+    time::Duration,
+};
+
+use deadpool_postgres::{
+    tokio_postgres::{
+        tls::{MakeTlsConnect, TlsConnect},
+        NoTls, Socket,
+    },
+    ClientWrapper, Config, Hook, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime,
+    SslMode, Timeouts,
+};
+use rustls::{ClientConfig, RootCertStore};
+use tokio_postgres::types::Type;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::constant::{config, query};
+
+static WARMUP_STATEMENTS: &[(&str, &[Type])] = &[
+    (query::NODES_SELECT_BY_ID, &[Type::INT4]),
+    (
+        query::NODES_SELECT_BY_ID_AND_BY_USER_OR_ISPUBLIC,
+        &[Type::INT4, Type::INT4],
+    ),
+    (
+        query::FEEDS_SELECT_BY_NODE_ID_AND_TIME_RANGE,
+        &[Type::INT4, Type::TIMESTAMP, Type::TIMESTAMP],
+    ),
+    (
+        query::FEEDS_INSERT,
+        &[Type::INT4, Type::TIMESTAMP, Type::FLOAT8_ARRAY],
+    ),
+    (query::USERS_SELECT_BY_USERNAME, &[Type::VARCHAR]),
+];
+
+static REPLICA_HEALTHY: AtomicBool = AtomicBool::new(false);
+static REPLICA_LAG_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn create() -> Pool {
+    create_for(config::DB_HOST, config::DB_PORT)
+}
+
+pub fn create_replica() -> Option<Pool> {
+    config::DB_REPLICA_HOST.map(|host| create_for(host, config::DB_REPLICA_PORT))
+}
+
+pub fn replica_healthy() -> bool {
+    REPLICA_HEALTHY.load(Relaxed)
+}
+
+pub fn replica_lag() -> Duration {
+    Duration::from_millis(REPLICA_LAG_MS.load(Relaxed))
+}
+
+pub fn set_replica_status(healthy: bool, lag: Duration) {
+    REPLICA_HEALTHY.store(healthy, Relaxed);
+    REPLICA_LAG_MS.store(lag.as_millis() as u64, Relaxed);
+}
+
+fn create_for(host: &str, port: u16) -> Pool {
+    let mut cfg_pool = Config::new();
+    cfg_pool.dbname = Some(config::DB_NAME.to_string());
+    cfg_pool.user = Some(env::var(config::DB_USER_ENV).unwrap_or(config::DB_USER.to_string()));
+    cfg_pool.password = password();
+    cfg_pool.host = Some(host.to_string());
+    cfg_pool.port = Some(port);
+    cfg_pool.connect_timeout = Some(Duration::from_secs(config::DB_CONNECT_TIMEOUT_SECS));
+    cfg_pool.options = Some(format!(
+        "-c statement_timeout={}",
+        config::DB_STATEMENT_TIMEOUT_MS
+    ));
+    cfg_pool.manager = Some(ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    });
+    cfg_pool.pool = Some(PoolConfig {
+        max_size: config::DB_POOL_MAX_SIZE,
+        timeouts: Timeouts {
+            wait: Some(Duration::from_millis(config::DB_POOL_WAIT_TIMEOUT_MS)),
+            create: Some(Duration::from_secs(config::DB_CONNECT_TIMEOUT_SECS)),
+            recycle: Some(Duration::from_millis(config::DB_POOL_WAIT_TIMEOUT_MS)),
+        },
+        ..Default::default()
+    });
+
+    match config::DB_TLS_CA_PATH {
+        Some(path) => {
+            cfg_pool.ssl_mode = Some(SslMode::Require);
+            build(&cfg_pool, tls_connector(path))
+        }
+        None => build(&cfg_pool, NoTls),
+    }
+}
+
+fn password() -> Option<String> {
+    if let Ok(password) = env::var(config::DB_PASSWORD_ENV) {
+        return Some(password);
+    }
+    let path = env::var(config::DB_PASSWORD_FILE_ENV).ok()?;
+    let password = fs::read_to_string(&path).expect("Failed to read database password file");
+    Some(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn tls_connector(path: &str) -> MakeRustlsConnect {
+    let file = File::open(path).expect("Failed to open database CA file");
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(file)) {
+        roots
+            .add(cert.expect("Failed to read database CA file"))
+            .expect("Invalid database CA certificate");
+    }
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    MakeRustlsConnect::new(tls_config)
+}
+
+fn build<T>(cfg_pool: &Config, tls: T) -> Pool
+where
+    T: MakeTlsConnect<Socket> + Clone + Sync + Send + 'static,
+    T::Stream: Sync + Send,
+    T::TlsConnect: Sync + Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let mut builder = cfg_pool
+        .builder(tls)
+        .expect("Failed to create pool")
+        .runtime(Runtime::Tokio1);
+    if config::DB_WARMUP_STATEMENTS {
+        builder = builder.post_create(Hook::async_fn(|client, _| {
+            Box::pin(async move {
+                warm_up(client).await;
+                Ok(())
+            })
+        }));
+    }
+    builder.build().expect("Failed to create pool")
+}
+
+async fn warm_up(client: &ClientWrapper) {
+    for (query, types) in WARMUP_STATEMENTS {
+        if let Err(e) = client.prepare_typed_cached(query, types).await {
+            tracing::warn!(error = %e, query, "failed to prepare warm-up statement");
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use chrono::{NaiveDateTime, Utc};
+use deadpool_postgres::Object;
+use ntex::{http::StatusCode, util::Bytes};
+use sonic_rs::{JsonValueTrait, Value};
+use tokio_postgres::{types::Type, Row};
+use tracing::instrument;
+
+use crate::{
+    constant::{config, messages, query},
+    models::{
+        response::{ApiResponse, Data},
+        shadows::Shadow,
+    },
+    utils::{http::serialize_response, shadow},
+};
+
+#[derive(Clone, Copy)]
+enum Section {
+    Desired,
+    Reported,
+}
+
+fn build_shadow(
+    node_id: i32,
+    desired: Value,
+    reported: Value,
+    versions: (i32, i32),
+    updated_at: (Option<NaiveDateTime>, Option<NaiveDateTime>),
+) -> Shadow {
+    Shadow {
+        node_id,
+        delta: shadow::delta(&desired, &reported).unwrap_or_else(shadow::empty),
+        desired,
+        reported,
+        desired_version: versions.0,
+        reported_version: versions.1,
+        desired_updated_at: updated_at.0,
+        reported_updated_at: updated_at.1,
+    }
+}
+
+fn shadow_from_row(node_id: i32, row: &Row) -> Shadow {
+    let document =
+        |index| sonic_rs::from_str(row.get::<_, &str>(index)).unwrap_or_else(|_| shadow::empty());
+    build_shadow(
+        node_id,
+        document(0),
+        document(1),
+        (row.get(2), row.get(3)),
+        (row.get(4), row.get(5)),
+    )
+}
+
+async fn load(client: &Object, node_id: i32) -> Result<Shadow, tokio_postgres::Error> {
+    let stmt = client
+        .prepare_typed_cached(query::NODE_SHADOWS_SELECT_BY_NODE_ID, &[Type::INT4])
+        .await?;
+    Ok(match client.query_opt(&stmt, &[&node_id]).await? {
+        Some(row) => shadow_from_row(node_id, &row),
+        None => build_shadow(
+            node_id,
+            shadow::empty(),
+            shadow::empty(),
+            (0, 0),
+            (None, None),
+        ),
+    })
+}
+
+fn error_response(message: &str, status: StatusCode) -> (Bytes, StatusCode) {
+    let error_response: ApiResponse<Shadow> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    serialize_response(error_response, status)
+}
+
+fn conflict(current: Option<Shadow>) -> (Bytes, StatusCode) {
+    let response = ApiResponse {
+        message: messages::SHADOW_VERSION_CONFLICT,
+        data: current.map_or(Data::None, Data::Single),
+    };
+    serialize_response(response, StatusCode::CONFLICT)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_shadow(client: &Object, node_id: i32) -> (Bytes, StatusCode) {
+    match load(client, node_id).await {
+        Ok(shadow) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(shadow),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn update_desired(
+    client: &Object,
+    node_id: i32,
+    state: Value,
+    version: Option<i32>,
+) -> (Bytes, StatusCode) {
+    update(client, node_id, Section::Desired, state, version).await
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn update_reported(
+    client: &Object,
+    node_id: i32,
+    state: Value,
+    version: Option<i32>,
+) -> (Bytes, StatusCode) {
+    update(client, node_id, Section::Reported, state, version).await
+}
+
+async fn update(
+    client: &Object,
+    node_id: i32,
+    section: Section,
+    state: Value,
+    expected: Option<i32>,
+) -> (Bytes, StatusCode) {
+    if !state.is_object() {
+        return error_response(messages::INVALID_SHADOW, StatusCode::BAD_REQUEST);
+    }
+    let current = match load(client, node_id).await {
+        Ok(current) => current,
+        Err(e) => return error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    let (mut document, version, upsert) = match section {
+        Section::Desired => (
+            current.desired.clone(),
+            current.desired_version,
+            query::NODE_SHADOWS_UPSERT_DESIRED,
+        ),
+        Section::Reported => (
+            current.reported.clone(),
+            current.reported_version,
+            query::NODE_SHADOWS_UPSERT_REPORTED,
+        ),
+    };
+    if expected.is_some_and(|expected| expected != version) {
+        return conflict(Some(current));
+    }
+    shadow::merge(&mut document, &state);
+    let document = match sonic_rs::to_string(&document) {
+        Ok(document) if document.len() <= config::SHADOW_MAX_BYTES => document,
+        _ => return error_response(messages::INVALID_SHADOW, StatusCode::BAD_REQUEST),
+    };
+
+    let stmt = client
+        .prepare_typed_cached(
+            upsert,
+            &[Type::INT4, Type::TEXT, Type::INT4, Type::TIMESTAMP],
+        )
+        .await
+        .unwrap();
+    match client
+        .query_opt(
+            &stmt,
+            &[&node_id, &document, &version, &Utc::now().naive_utc()],
+        )
+        .await
+    {
+        Ok(Some(row)) => {
+            let response = ApiResponse {
+                message: messages::OK,
+                data: Data::Single(shadow_from_row(node_id, &row)),
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Ok(None) => conflict(load(client, node_id).await.ok()),
+        Err(e) => error_response(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
@@ -1,22 +1,20 @@
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
 use deadpool_postgres::Object;
-use futures::StreamExt;
 use jsonwebtoken::{encode, DecodingKey, EncodingKey, Header};
-use lettre::{
-    message::header::ContentType, transport::smtp::authentication::Credentials, SmtpTransport,
-    Transport,
-};
-use std::{borrow::Cow::Owned, str};
+use lettre::message::header::ContentType;
+use std::{borrow::Cow::Owned, net::IpAddr, str};
 use tokio_postgres::{error::SqlState, types::Type};
 
-use ntex::{
-    http::{Payload, StatusCode},
-    util::Bytes,
-};
+use ntex::{http::StatusCode, util::Bytes};
+use tracing::instrument;
 
 use crate::{
-    constant::{config, messages, query},
+    constant::{audit as action, config, messages, query},
+    database::audit,
+    metrics,
     models::{
+        audit::AuditEntry,
+        deleted::Deleted,
         jwt::{ActivationClaims, Claims},
         response::{ApiResponse, Data},
         users::{
@@ -24,9 +22,11 @@ use crate::{
             UserDTO,
         },
     },
-    utils::{generate_string, http::serialize_response},
+    tasks::mail,
+    utils::{auth, generate_string, http::serialize_response},
 };
 
+#[instrument(level = "debug", skip_all)]
 pub async fn get_all_users(client: &Object) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::USERS_SELECT, &[])
@@ -53,35 +53,26 @@ pub async fn get_all_users(client: &Object) -> (Bytes, StatusCode) {
     serialize_response(response, StatusCode::OK)
 }
 
-pub async fn register_user(client: &Object, payload: &mut Payload) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = std::str::from_utf8(&buf).unwrap();
-    let data: RegisterPayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<User> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
-    let hashed_password = bcrypt::hash(data.password.as_ref(), bcrypt::DEFAULT_COST).unwrap();
-
-    let stmt = client
+#[instrument(level = "debug", skip_all)]
+pub async fn register_user(
+    client: &mut Object,
+    data: RegisterPayload,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let hashed_password =
+        metrics::time_bcrypt(|| bcrypt::hash(data.password.as_ref(), bcrypt::DEFAULT_COST))
+            .unwrap();
+
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(
             query::USERS_INSERT,
             &[Type::VARCHAR, Type::VARCHAR, Type::VARCHAR],
         )
         .await
         .unwrap();
-    match client
-        .execute(
+    match transaction
+        .query_one(
             &stmt,
             &[
                 &data.username.as_ref(),
@@ -91,7 +82,20 @@ pub async fn register_user(client: &Object, payload: &mut Payload) -> (Bytes, St
         )
         .await
     {
-        Ok(_) => {
+        Ok(row) => {
+            let id: i32 = row.get(0);
+            let entry = AuditEntry {
+                user_id: Some(id),
+                ip,
+                action: action::CREATE,
+                target_type: action::TARGET_USER,
+                target_id: Some(id),
+                before: None,
+                after: audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             if config::ENVIROMENT == "development" {
                 let response: ApiResponse<UserDTO> = ApiResponse {
                     message: messages::CREATED,
@@ -121,21 +125,7 @@ pub async fn register_user(client: &Object, payload: &mut Payload) -> (Bytes, St
                 .header(ContentType::TEXT_PLAIN)
                 .body(body)
                 .unwrap();
-
-            let creds = Credentials::new(
-                config::EMAIL_USERNAME.to_string(),
-                config::EMAIL_PASSWORD.to_string(),
-            );
-
-            let mailer = SmtpTransport::relay(config::EMAIL_RELAY)
-                .unwrap()
-                .credentials(creds)
-                .build();
-
-            match mailer.send(&email) {
-                Ok(_) => (),
-                Err(e) => eprintln!("{}", e),
-            }
+            mail::send(email);
 
             let response: ApiResponse<UserDTO> = ApiResponse {
                 message: messages::CREATED,
@@ -144,17 +134,12 @@ pub async fn register_user(client: &Object, payload: &mut Payload) -> (Bytes, St
             serialize_response(response, StatusCode::CREATED)
         }
         Err(e) => {
-            match e.code() {
-                Some(code) => {
-                    if *code == SqlState::UNIQUE_VIOLATION {
-                        let error_response: ApiResponse<User> = ApiResponse {
-                            message: messages::USER_EXISTS,
-                            data: Data::None,
-                        };
-                        return serialize_response(error_response, StatusCode::CONFLICT);
-                    }
-                }
-                None => (),
+            if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+                let error_response: ApiResponse<User> = ApiResponse {
+                    message: messages::USER_EXISTS,
+                    data: Data::None,
+                };
+                return serialize_response(error_response, StatusCode::CONFLICT);
             }
             let error_response: ApiResponse<User> = ApiResponse {
                 message: &e.to_string(),
@@ -165,30 +150,19 @@ pub async fn register_user(client: &Object, payload: &mut Payload) -> (Bytes, St
     }
 }
 
-pub async fn login_user(client: &Object, payload: &mut Payload) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = std::str::from_utf8(&buf).unwrap();
-    let data: LoginPayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<User> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
-    let stmt = client
+#[instrument(level = "debug", skip_all)]
+pub async fn login_user(
+    client: &mut Object,
+    data: LoginPayload,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(query::USERS_SELECT_BY_USERNAME, &[Type::VARCHAR])
         .await
         .unwrap();
 
-    let rows = client
+    let rows = transaction
         .query(&stmt, &[&data.username.as_ref()])
         .await
         .unwrap();
@@ -205,14 +179,50 @@ pub async fn login_user(client: &Object, payload: &mut Payload) -> (Bytes, Statu
         });
     }
 
-    if users.is_empty() || !bcrypt::verify(data.password.as_ref(), &users[0].password).unwrap() {
+    if users.is_empty()
+        || !metrics::time_bcrypt(|| bcrypt::verify(data.password.as_ref(), &users[0].password))
+            .unwrap()
+    {
+        let target_id = users.first().map(|user| user.id);
+        let before = match target_id {
+            Some(id) => audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+            None => None,
+        };
+        let attempt = sonic_rs::json!({ "username": data.username.as_ref() }).to_string();
+        let entry = AuditEntry {
+            user_id: None,
+            ip,
+            action: action::LOGIN_FAILED,
+            target_type: action::TARGET_USER,
+            target_id,
+            before,
+            after: Some(attempt),
+        };
+        if let Err(res) = audit::commit(transaction, entry).await {
+            return res;
+        }
         let error_response: ApiResponse<User> = ApiResponse {
             message: messages::LOGIN_FAILED,
             data: Data::None,
         };
         return serialize_response(error_response, StatusCode::UNAUTHORIZED);
     }
-    if users[0].status == false {
+
+    let id = users[0].id;
+    let before = audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await;
+    if !users[0].status {
+        let entry = AuditEntry {
+            user_id: None,
+            ip,
+            action: action::LOGIN_FAILED,
+            target_type: action::TARGET_USER,
+            target_id: Some(id),
+            after: before.clone(),
+            before,
+        };
+        if let Err(res) = audit::commit(transaction, entry).await {
+            return res;
+        }
         let error_response: ApiResponse<User> = ApiResponse {
             message: messages::ACCOUNT_NOT_ACTIVATED,
             data: Data::None,
@@ -223,7 +233,7 @@ pub async fn login_user(client: &Object, payload: &mut Payload) -> (Bytes, Statu
     let token = encode(
         &Header::default(),
         &Claims {
-            user_id: users[0].id,
+            user_id: id,
             isadmin: users[0].isadmin,
             exp: chrono::Utc::now().timestamp() as usize + 60 * 60,
         },
@@ -231,6 +241,19 @@ pub async fn login_user(client: &Object, payload: &mut Payload) -> (Bytes, Statu
     )
     .unwrap();
 
+    let entry = AuditEntry {
+        user_id: Some(id),
+        ip,
+        action: action::LOGIN,
+        target_type: action::TARGET_USER,
+        target_id: Some(id),
+        after: audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+        before,
+    };
+    if let Err(res) = audit::commit(transaction, entry).await {
+        return res;
+    }
+
     let response = ApiResponse {
         message: messages::OK,
         data: Data::Single(token),
@@ -239,6 +262,7 @@ pub async fn login_user(client: &Object, payload: &mut Payload) -> (Bytes, Statu
     serialize_response(response, StatusCode::OK)
 }
 
+#[instrument(level = "debug", skip_all)]
 pub async fn get_one_user(client: &Object, id: i32) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::USERS_SELECT_BY_ID, &[Type::INT4])
@@ -270,29 +294,48 @@ pub async fn get_one_user(client: &Object, id: i32) -> (Bytes, StatusCode) {
     serialize_response(response, StatusCode::OK)
 }
 
-pub async fn activate_user(client: &Object, token: String) -> (Bytes, StatusCode) {
+#[instrument(level = "debug", skip_all)]
+pub async fn activate_user(
+    client: &mut Object,
+    token: String,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
     match jsonwebtoken::decode::<ActivationClaims>(
         &token,
         &DecodingKey::from_secret(config::ACTIVATION_JWT_SECRET.as_ref()),
         &jsonwebtoken::Validation::default(),
     ) {
         Ok(token_data) => {
-            let stmt = client
+            let transaction = client.transaction().await.unwrap();
+            let stmt = transaction
                 .prepare_typed_cached(query::USERS_UPDATE_STATUS_BY_USERNAME, &[Type::VARCHAR])
                 .await
                 .unwrap();
 
-            let rows = client
-                .execute(&stmt, &[&token_data.claims.username])
+            let row = transaction
+                .query_opt(&stmt, &[&token_data.claims.username])
                 .await
                 .unwrap();
 
-            if rows == 0 {
+            let Some(row) = row else {
                 let error_response: ApiResponse<User> = ApiResponse {
                     message: messages::USER_NOT_FOUND,
                     data: Data::None,
                 };
                 return serialize_response(error_response, StatusCode::NOT_FOUND);
+            };
+            let id: i32 = row.get(0);
+            let entry = AuditEntry {
+                user_id: Some(id),
+                ip,
+                action: action::ACTIVATE,
+                target_type: action::TARGET_USER,
+                target_id: Some(id),
+                before: None,
+                after: audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
             }
 
             let response: ApiResponse<User> = ApiResponse {
@@ -312,24 +355,12 @@ pub async fn activate_user(client: &Object, token: String) -> (Bytes, StatusCode
     }
 }
 
-pub async fn forgot_password(client: &Object, payload: &mut Payload) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = str::from_utf8(&buf).unwrap();
-    let data: ForgotPasswordPayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<User> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
+#[instrument(level = "debug", skip_all)]
+pub async fn forgot_password(
+    client: &mut Object,
+    data: ForgotPasswordPayload,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(
             query::USERS_SELECT_BY_USERNAME_AND_EMAIL,
@@ -351,10 +382,13 @@ pub async fn forgot_password(client: &Object, payload: &mut Payload) -> (Bytes,
         return serialize_response(error_response, StatusCode::NOT_FOUND);
     }
 
+    let id: i32 = rows[0].get(0);
     let new_password = generate_string(16);
-    let hashed_password = bcrypt::hash(&new_password, bcrypt::DEFAULT_COST).unwrap();
+    let hashed_password =
+        metrics::time_bcrypt(|| bcrypt::hash(&new_password, bcrypt::DEFAULT_COST)).unwrap();
 
-    let stmt = client
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(
             query::USERS_UPDATE_PASSWORD_BY_USERNAME,
             &[Type::VARCHAR, Type::VARCHAR],
@@ -362,11 +396,23 @@ pub async fn forgot_password(client: &Object, payload: &mut Payload) -> (Bytes,
         .await
         .unwrap();
 
-    match client
+    match transaction
         .execute(&stmt, &[&hashed_password, &data.username.as_ref()])
         .await
     {
         Ok(_) => {
+            let entry = AuditEntry {
+                user_id: None,
+                ip,
+                action: action::PASSWORD_RESET,
+                target_type: action::TARGET_USER,
+                target_id: Some(id),
+                before: None,
+                after: None,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             if config::ENVIROMENT == "development" {
                 let response: ApiResponse<User> = ApiResponse {
                     message: messages::OK,
@@ -382,21 +428,7 @@ pub async fn forgot_password(client: &Object, payload: &mut Payload) -> (Bytes,
                 .header(ContentType::TEXT_PLAIN)
                 .body(body)
                 .unwrap();
-
-            let creds = Credentials::new(
-                config::EMAIL_USERNAME.to_string(),
-                config::EMAIL_PASSWORD.to_string(),
-            );
-
-            let mailer = SmtpTransport::relay(config::EMAIL_RELAY)
-                .unwrap()
-                .credentials(creds)
-                .build();
-
-            match mailer.send(&email) {
-                Ok(_) => (),
-                Err(e) => eprintln!("{}", e),
-            }
+            mail::send(email);
 
             let response: ApiResponse<User> = ApiResponse {
                 message: messages::OK,
@@ -414,24 +446,12 @@ pub async fn forgot_password(client: &Object, payload: &mut Payload) -> (Bytes,
     }
 }
 
-pub async fn change_password(client: &Object, payload: &mut Payload) -> (Bytes, StatusCode) {
-    let mut buf = Vec::new();
-    while let Some(chunk) = payload.next().await {
-        buf.extend_from_slice(&chunk.unwrap());
-    }
-
-    let data = str::from_utf8(&buf).unwrap();
-    let data: ChangePasswordPayload = match sonic_rs::from_str(data) {
-        Ok(data) => data,
-        Err(_) => {
-            let error_response: ApiResponse<User> = ApiResponse {
-                message: messages::INVALID_PAYLOAD,
-                data: Data::None,
-            };
-            return serialize_response(error_response, StatusCode::BAD_REQUEST);
-        }
-    };
-
+#[instrument(level = "debug", skip_all)]
+pub async fn change_password(
+    client: &mut Object,
+    data: ChangePasswordPayload,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
     let stmt = client
         .prepare_typed_cached(query::USERS_SELECT_BY_USERNAME, &[Type::VARCHAR])
         .await
@@ -454,7 +474,10 @@ pub async fn change_password(client: &Object, payload: &mut Payload) -> (Bytes,
         });
     }
 
-    if users.is_empty() || !bcrypt::verify(data.password.as_ref(), &users[0].password).unwrap() {
+    if users.is_empty()
+        || !metrics::time_bcrypt(|| bcrypt::verify(data.password.as_ref(), &users[0].password))
+            .unwrap()
+    {
         let error_response: ApiResponse<User> = ApiResponse {
             message: messages::LOGIN_FAILED,
             data: Data::None,
@@ -462,9 +485,12 @@ pub async fn change_password(client: &Object, payload: &mut Payload) -> (Bytes,
         return serialize_response(error_response, StatusCode::UNAUTHORIZED);
     }
 
-    let hashed_password = bcrypt::hash(data.new_password.as_ref(), bcrypt::DEFAULT_COST).unwrap();
+    let hashed_password =
+        metrics::time_bcrypt(|| bcrypt::hash(data.new_password.as_ref(), bcrypt::DEFAULT_COST))
+            .unwrap();
 
-    let stmt = client
+    let transaction = client.transaction().await.unwrap();
+    let stmt = transaction
         .prepare_typed_cached(
             query::USERS_UPDATE_PASSWORD_BY_USERNAME,
             &[Type::VARCHAR, Type::VARCHAR],
@@ -472,17 +498,171 @@ pub async fn change_password(client: &Object, payload: &mut Payload) -> (Bytes,
         .await
         .unwrap();
 
-    match client
+    match transaction
         .execute(&stmt, &[&hashed_password, &data.username.as_ref()])
         .await
     {
         Ok(_) => {
+            let entry = AuditEntry {
+                user_id: Some(users[0].id),
+                ip,
+                action: action::PASSWORD_CHANGE,
+                target_type: action::TARGET_USER,
+                target_id: Some(users[0].id),
+                before: None,
+                after: None,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            let response: ApiResponse<User> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<User> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn delete_user(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::USERS_DELETE_BY_ID, &[Type::INT4, Type::TIMESTAMP])
+        .await
+        .unwrap();
+    match transaction
+        .execute(&stmt, &[&id, &Utc::now().naive_utc()])
+        .await
+    {
+        Ok(rows_updated) => {
+            if rows_updated == 0 {
+                let error_response: ApiResponse<User> = ApiResponse {
+                    message: messages::USER_NOT_FOUND,
+                    data: Data::None,
+                };
+                return serialize_response(error_response, StatusCode::NOT_FOUND);
+            }
+            auth::mark_user_deleted(id, true);
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::DELETE,
+                target_type: action::TARGET_USER,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
+            let response: ApiResponse<User> = ApiResponse {
+                message: messages::OK,
+                data: Data::None,
+            };
+            serialize_response(response, StatusCode::OK)
+        }
+        Err(e) => {
+            let error_response: ApiResponse<User> = ApiResponse {
+                message: &e.to_string(),
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn get_deleted_users(client: &Object) -> (Bytes, StatusCode) {
+    let stmt = client
+        .prepare_typed_cached(query::USERS_SELECT_DELETED, &[])
+        .await
+        .unwrap();
+    let rows = client.query(&stmt, &[]).await.unwrap();
+
+    let mut users = Vec::with_capacity(rows.len());
+    for row in rows {
+        users.push(Deleted {
+            item: UserDTO {
+                id: row.get(0),
+                username: Owned(row.get::<_, &str>(1).to_string()),
+                email: Owned(row.get::<_, &str>(2).to_string()),
+                status: row.get(3),
+                isadmin: row.get(4),
+            },
+            deleted_at: row.get::<_, NaiveDateTime>(5),
+        });
+    }
+
+    let response = ApiResponse {
+        message: messages::OK,
+        data: Data::Multiple(users),
+    };
+
+    serialize_response(response, StatusCode::OK)
+}
+
+#[instrument(level = "debug", skip_all)]
+pub async fn restore_user(
+    client: &mut Object,
+    id: i32,
+    user_id: i32,
+    ip: Option<IpAddr>,
+) -> (Bytes, StatusCode) {
+    let transaction = client.transaction().await.unwrap();
+    let before = audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await;
+    let stmt = transaction
+        .prepare_typed_cached(query::USERS_RESTORE_BY_ID, &[Type::INT4])
+        .await
+        .unwrap();
+    match transaction.execute(&stmt, &[&id]).await {
+        Ok(rows_updated) => {
+            if rows_updated == 0 {
+                let error_response: ApiResponse<User> = ApiResponse {
+                    message: messages::NOTHING_TO_RESTORE,
+                    data: Data::None,
+                };
+                return serialize_response(error_response, StatusCode::NOT_FOUND);
+            }
+            auth::mark_user_deleted(id, false);
+            let entry = AuditEntry {
+                user_id: Some(user_id),
+                ip,
+                action: action::RESTORE,
+                target_type: action::TARGET_USER,
+                target_id: Some(id),
+                before,
+                after: audit::snapshot(&transaction, query::USERS_SNAPSHOT_BY_ID, id).await,
+            };
+            if let Err(res) = audit::commit(transaction, entry).await {
+                return res;
+            }
             let response: ApiResponse<User> = ApiResponse {
                 message: messages::OK,
                 data: Data::None,
             };
             serialize_response(response, StatusCode::OK)
         }
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            let error_response: ApiResponse<User> = ApiResponse {
+                message: messages::USER_EXISTS,
+                data: Data::None,
+            };
+            serialize_response(error_response, StatusCode::CONFLICT)
+        }
         Err(e) => {
             let error_response: ApiResponse<User> = ApiResponse {
                 message: &e.to_string(),
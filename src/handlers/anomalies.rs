@@ -0,0 +1,89 @@
+use ntex::http::{Request, Response};
+use ntex::web::Error;
+
+use crate::database::{anomalies, nodes};
+use crate::models::anomalies::{AnomalyQuery, Detector};
+use crate::utils::auth::authenticate;
+use crate::utils::http::{extract_id_from_subpath, parse_query, read_json};
+use crate::{app::App, utils::http::response_json};
+
+impl App {
+    pub async fn handle_get_detectors(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/detectors/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    if nodes::get_node(&client, id, claims.user_id, claims.isadmin)
+                        .await
+                        .is_none()
+                    {
+                        return self.handle_node_not_found(req).await;
+                    }
+                    let (data, status) = anomalies::get_detectors(&client, id).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_put_detectors(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/detectors/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: Vec<Detector> = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    if node.user_id != claims.user_id && !claims.isadmin {
+                        return self.handle_not_authorized(req).await;
+                    }
+                    let (data, status) =
+                        anomalies::set_detectors(&mut client, &node, payload).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_anomalies(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<AnomalyQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match extract_id_from_subpath(req.path(), "/nodes/", "/anomalies/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    if nodes::get_node(&client, id, claims.user_id, claims.isadmin)
+                        .await
+                        .is_none()
+                    {
+                        return self.handle_node_not_found(req).await;
+                    }
+                    let (data, status) = anomalies::get_anomalies(&client, id, filter).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+}
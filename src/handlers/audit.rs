@@ -0,0 +1,29 @@
+use ntex::http::{Request, Response};
+use ntex::web::Error;
+
+use crate::constant::messages;
+use crate::database::audit;
+use crate::models::audit::AuditLogQuery;
+use crate::utils::auth::authenticate_admin;
+use crate::utils::http::parse_query;
+use crate::{app::App, utils::http::response_json};
+
+impl App {
+    pub async fn handle_get_audit_log(&self, req: Request) -> Result<Response, Error> {
+        match authenticate_admin(&req).await {
+            Ok(_) => match parse_query::<AuditLogQuery>(&req) {
+                Some(filter) => {
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = audit::get_audit_log(&client, filter).await;
+                    Ok(response_json(data, status))
+                }
+                None => self.handle_bad_request(req).await,
+            },
+            Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use ntex::http::{Request, Response};
+use ntex::time::{timeout, Millis};
+use ntex::web::Error;
+
+use crate::constant::config;
+use crate::database::{commands, nodes};
+use crate::models::commands::{CommandAck, CommandListQuery, CommandPayload, CommandPollQuery};
+use crate::tasks;
+use crate::utils::auth::authenticate;
+use crate::utils::http::{extract_id_from_subpath, parse_query, read_json};
+use crate::{app::App, utils::http::response_json};
+
+impl App {
+    pub async fn handle_get_commands(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<CommandListQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match extract_id_from_subpath(req.path(), "/nodes/", "/commands/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    if node.user_id != claims.user_id && !claims.isadmin {
+                        return self.handle_not_authorized(req).await;
+                    }
+                    let (data, status) = commands::get_commands(&client, id, filter).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_post_command(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/commands/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: CommandPayload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    if node.user_id != claims.user_id && !claims.isadmin {
+                        return self.handle_not_authorized(req).await;
+                    }
+                    let (data, status) =
+                        commands::add_command(&client, id, claims.user_id, payload).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_poll_commands(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<CommandPollQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let wait = filter.wait.unwrap_or_default();
+                let deadline =
+                    Instant::now() + Duration::from_secs(wait.min(config::COMMAND_MAX_WAIT_SECS));
+                let subscription = tasks::commands::subscribe(filter.node_id);
+                let mut checked = false;
+                let result = loop {
+                    let mut notified = pin!(subscription.notified());
+                    notified.as_mut().enable();
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    if !checked {
+                        let Some(node) =
+                            nodes::get_node(&client, filter.node_id, claims.user_id, false).await
+                        else {
+                            return self.handle_node_not_found(req).await;
+                        };
+                        if node.user_id != claims.user_id {
+                            return self.handle_not_authorized(req).await;
+                        }
+                        checked = true;
+                    }
+                    let result = commands::take_pending(&client, filter.node_id).await;
+                    drop(client);
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if !matches!(&result, Ok(pending) if pending.is_empty()) || remaining.is_zero()
+                    {
+                        break result;
+                    }
+                    let interval = Duration::from_secs(config::COMMAND_POLL_INTERVAL_SECS);
+                    let _ = timeout(Millis::from(remaining.min(interval)), notified).await;
+                };
+                let (data, status) = commands::pending_response(result);
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_ack_command(&self, mut req: Request) -> Result<Response, Error> {
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let payload: CommandAck = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) =
+                    nodes::get_node(&client, payload.node_id, claims.user_id, false).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                if node.user_id != claims.user_id {
+                    return self.handle_not_authorized(req).await;
+                }
+                let (data, status) = commands::ack_command(&client, payload).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+}
@@ -1,18 +1,179 @@
-use ntex::http::{Request, Response};
+use chrono::{Duration, Utc};
+use futures::channel::mpsc;
+use ntex::http::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE, SERVER};
+use ntex::http::{Request, Response, StatusCode};
 use ntex::web::Error;
+use tracing::Instrument;
 
-use crate::database;
+use crate::constant::{config, messages};
+use crate::database::{self, feeds, nodes};
+use crate::models::feeds::{
+    CompletenessQuery, FeedExportQuery, FeedImportQuery, FeedIngestQuery, ImportFormat,
+};
+use crate::models::response::{ApiResponse, Data};
 use crate::utils::auth::authenticate;
+use crate::utils::export::{group_columns, FeedEncoder};
+use crate::utils::http::{
+    extract_id_from_subpath, parse_query, read_json, response_json_stream, serialize_response,
+};
+use crate::utils::HDR_SERVER;
 use crate::{app::App, utils::http::response_json};
 
 impl App {
     pub async fn handle_add_feed(&self, mut req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<FeedIngestQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        let commands = filter.commands.is_some_and(|commands| commands != 0);
         match authenticate(&req).await {
             Ok(claims) => {
-                let payload = req.payload();
-                let client = self.pool.get().await.unwrap();
+                let payload = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
                 let (data, status) =
-                    database::feeds::add_feed(&client, payload, claims.user_id).await;
+                    database::feeds::add_feed(&client, payload, claims.user_id, commands).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_get_completeness(&self, req: Request) -> Result<Response, Error> {
+        let id = extract_id_from_subpath(req.path(), "/nodes/", "/completeness/");
+        let (Some(id), Some(filter)) = (id, parse_query::<CompletenessQuery>(&req)) else {
+            return self.handle_bad_request(req).await;
+        };
+        let to = filter.to.unwrap_or_else(|| Utc::now().naive_utc());
+        let from = filter
+            .from
+            .unwrap_or(to - Duration::hours(config::COMPLETENESS_DEFAULT_RANGE_HOURS));
+        if from >= to
+            || to - from > Duration::days(config::COMPLETENESS_MAX_RANGE_DAYS)
+            || filter.interval.is_some_and(|secs| secs <= 0)
+        {
+            let response: ApiResponse<()> = ApiResponse {
+                message: messages::INVALID_TIME_RANGE,
+                data: Data::None,
+            };
+            let (data, status) = serialize_response(response, StatusCode::BAD_REQUEST);
+            return Ok(response_json(data, status));
+        }
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) = nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                let interval = filter
+                    .interval
+                    .or(node.heartbeat_interval_secs)
+                    .unwrap_or(config::NODE_HEARTBEAT_INTERVAL_SECS);
+                let (tx, rx) = mpsc::channel(2);
+                ntex::rt::spawn(
+                    feeds::stream_completeness(client, node.id, from, to, interval, tx)
+                        .in_current_span(),
+                );
+                Ok(response_json_stream(rx))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_export_feeds(&self, req: Request) -> Result<Response, Error> {
+        let id =
+            extract_id_from_subpath(req.path().trim_end_matches('/'), "/nodes/", "/feeds/export");
+        let (Some(id), Some(filter)) = (id, parse_query::<FeedExportQuery>(&req)) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(sensor_names) =
+                    nodes::get_node_sensor_names(&client, id, claims.user_id, claims.isadmin).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+
+                let (columns, layouts) = group_columns(&[(id, sensor_names)]);
+                let width = columns.len();
+                let encoder = match FeedEncoder::new(filter.format, columns, false) {
+                    Ok(encoder) => encoder,
+                    Err(_) => return self.handle_bad_request(req).await,
+                };
+                let (tx, rx) = mpsc::channel(2);
+                ntex::rt::spawn(
+                    feeds::export_feeds(
+                        client,
+                        layouts,
+                        width,
+                        filter.from,
+                        filter.to,
+                        encoder,
+                        tx,
+                    )
+                    .in_current_span(),
+                );
+
+                let mut res = Response::Ok().streaming(rx);
+                res.headers_mut()
+                    .insert(CONTENT_TYPE, filter.format.content_type());
+                if let Ok(disposition) = HeaderValue::from_str(&format!(
+                    "attachment; filename=\"node-{}-feeds.{}\"",
+                    id,
+                    filter.format.extension()
+                )) {
+                    res.headers_mut().insert(CONTENT_DISPOSITION, disposition);
+                }
+                res.headers_mut().insert(SERVER, HDR_SERVER);
+                Ok(res)
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_import_feeds(&self, mut req: Request) -> Result<Response, Error> {
+        let id =
+            extract_id_from_subpath(req.path().trim_end_matches('/'), "/nodes/", "/feeds/import");
+        let (Some(id), Some(filter)) = (id, parse_query::<FeedImportQuery>(&req)) else {
+            return self.handle_bad_request(req).await;
+        };
+        let format = filter.format.or_else(|| {
+            let content_type = req.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+            match content_type.split(';').next()?.trim() {
+                "text/csv" => Some(ImportFormat::Csv),
+                "application/x-ndjson" | "application/ndjson" => Some(ImportFormat::Ndjson),
+                _ => None,
+            }
+        });
+        let Some(format) = format else {
+            let response: ApiResponse<()> = ApiResponse {
+                message: messages::UNSUPPORTED_FORMAT,
+                data: Data::None,
+            };
+            let (data, status) = serialize_response(response, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            return Ok(response_json(data, status));
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let owner = (!claims.isadmin).then_some(claims.user_id);
+                let payload = req.payload();
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) = feeds::import_feeds(&client, id, format, owner, payload).await;
                 Ok(response_json(data, status))
             }
             Err(err) => self.handle_not_authenticated_with_message(req, err).await,
@@ -0,0 +1,285 @@
+use futures::channel::mpsc;
+use ntex::http::body::SizedStream;
+use ntex::http::header::{
+    HeaderValue, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG, RANGE,
+    SERVER,
+};
+use ntex::http::{Request, Response, StatusCode};
+use ntex::web::Error;
+use tracing::Instrument;
+
+use crate::constant::messages;
+use crate::database::{firmwares, nodes};
+use crate::models::firmwares::{
+    Firmware, FirmwareCheckQuery, FirmwareDownloadQuery, FirmwareReportQuery, FirmwareRollout,
+    FirmwareUploadQuery,
+};
+use crate::models::response::{ApiResponse, Data};
+use crate::utils::auth::{authenticate, authenticate_admin};
+use crate::utils::firmware;
+use crate::utils::http::{
+    client_ip, extract_id_from_path, extract_id_from_subpath, parse_query, read_json,
+    serialize_response,
+};
+use crate::utils::HDR_SERVER;
+use crate::{app::App, utils::http::response_json};
+
+fn firmware_error(message: &str, status: StatusCode) -> Response {
+    let response: ApiResponse<Firmware> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    let (data, status) = serialize_response(response, status);
+    response_json(data, status)
+}
+
+impl App {
+    pub async fn handle_get_firmwares(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/hardwares/", "/firmwares/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(_) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = firmwares::get_firmwares(&client, id).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_post_firmware(&self, mut req: Request) -> Result<Response, Error> {
+        let id = extract_id_from_subpath(req.path(), "/hardwares/", "/firmwares/");
+        let (Some(id), Some(filter)) = (id, parse_query::<FirmwareUploadQuery>(&req)) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate_admin(&req).await {
+            Ok(claims) => {
+                let ip = client_ip(&req);
+                let upload = match firmwares::receive_firmware(&filter, req.payload()).await {
+                    Ok(upload) => upload,
+                    Err((data, status)) => return Ok(response_json(data, status)),
+                };
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => {
+                        upload.discard().await;
+                        return Ok(res);
+                    }
+                };
+                let (data, status) =
+                    firmwares::add_firmware(&mut client, id, filter, upload, claims.user_id, ip)
+                        .await;
+                Ok(response_json(data, status))
+            }
+            Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_get_firmware_by_id(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/firmwares/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(_) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = firmwares::get_firmware(&client, id).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_delete_firmware(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/firmwares/") {
+            Some(id) => match authenticate_admin(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = firmwares::delete_firmware(
+                        &mut client,
+                        id,
+                        claims.user_id,
+                        client_ip(&req),
+                    )
+                    .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_rollout_firmware(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/firmwares/", "/rollout/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: FirmwareRollout = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let (data, status) =
+                        firmwares::rollout_firmware(&client, id, payload, owner).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_firmware_report(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<FirmwareReportQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let owner = (!claims.isadmin).then_some(claims.user_id);
+                let (data, status) = firmwares::get_firmware_report(&client, filter, owner).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_check_firmware(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<FirmwareCheckQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) =
+                    nodes::get_node(&client, filter.node_id, claims.user_id, false).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                if node.user_id != claims.user_id {
+                    return self.handle_not_authorized(req).await;
+                }
+                let (data, status) =
+                    firmwares::check_firmware(&client, filter.node_id, filter.version).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_download_firmware(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<FirmwareDownloadQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) =
+                    nodes::get_node(&client, filter.node_id, claims.user_id, false).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                if node.user_id != claims.user_id {
+                    return self.handle_not_authorized(req).await;
+                }
+                let firmware = match firmwares::assigned_firmware(&client, filter.node_id).await {
+                    Ok(Some(firmware)) => firmware,
+                    Ok(None) => {
+                        return Ok(firmware_error(
+                            messages::FIRMWARE_NOT_FOUND,
+                            StatusCode::NOT_FOUND,
+                        ))
+                    }
+                    Err(e) => {
+                        return Ok(firmware_error(
+                            &e.to_string(),
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        ))
+                    }
+                };
+                drop(client);
+
+                let path = firmware::path(firmware.id);
+                if tokio::fs::metadata(&path).await.is_err() {
+                    return Ok(firmware_error(
+                        messages::FIRMWARE_NOT_FOUND,
+                        StatusCode::NOT_FOUND,
+                    ));
+                }
+                let size = firmware.size as u64;
+                let range = req
+                    .headers()
+                    .get(RANGE)
+                    .and_then(|value| value.to_str().ok());
+                let (status, start, end) = match firmware::parse_range(range, size) {
+                    Ok(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+                    Ok(None) => (StatusCode::OK, 0, size - 1),
+                    Err(()) => {
+                        let mut res = firmware_error(
+                            messages::RANGE_NOT_SATISFIABLE,
+                            StatusCode::RANGE_NOT_SATISFIABLE,
+                        );
+                        if let Ok(value) = HeaderValue::from_str(&format!("bytes */{size}")) {
+                            res.headers_mut().insert(CONTENT_RANGE, value);
+                        }
+                        return Ok(res);
+                    }
+                };
+
+                let len = end - start + 1;
+                let (tx, rx) = mpsc::channel(2);
+                ntex::rt::spawn(firmware::stream_file(path, start, len, tx).in_current_span());
+
+                let mut res = Response::with_body(status, SizedStream::new(len, rx).into());
+                let headers = res.headers_mut();
+                headers.insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/octet-stream"),
+                );
+                headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+                if let Ok(value) = HeaderValue::from_str(&format!("\"{}\"", firmware.checksum)) {
+                    headers.insert(ETAG, value);
+                }
+                if status == StatusCode::PARTIAL_CONTENT {
+                    if let Ok(value) = HeaderValue::from_str(&format!("bytes {start}-{end}/{size}"))
+                    {
+                        headers.insert(CONTENT_RANGE, value);
+                    }
+                }
+                if let Ok(disposition) = HeaderValue::from_str(&format!(
+                    "attachment; filename=\"firmware-{}-{}.bin\"",
+                    firmware.hardware_id, firmware.version
+                )) {
+                    headers.insert(CONTENT_DISPOSITION, disposition);
+                }
+                headers.insert(SERVER, HDR_SERVER);
+                Ok(res)
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use deadpool_postgres::Object;
+use futures::channel::mpsc;
+use ntex::http::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE, SERVER};
+use ntex::http::{Request, Response, StatusCode};
+use ntex::web::Error;
+use tracing::Instrument;
+
+use crate::constant::messages;
+use crate::database::{anomalies, commands, feeds, groups, nodes};
+use crate::models::anomalies::Detector;
+use crate::models::commands::CommandPayload;
+use crate::models::feeds::FeedExportQuery;
+use crate::models::groups::{GroupMembers, NodeGroup, NodeGroupPayload, TagAssignment};
+use crate::models::nodes::Node;
+use crate::models::response::{ApiResponse, Data};
+use crate::utils::auth::authenticate;
+use crate::utils::export::{group_columns, FeedEncoder};
+use crate::utils::http::{
+    client_ip, extract_id_from_path, extract_id_from_subpath, parse_query, read_json,
+    serialize_response,
+};
+use crate::utils::HDR_SERVER;
+use crate::{app::App, utils::http::response_json};
+
+fn group_error(message: &str, status: StatusCode) -> Response {
+    let response: ApiResponse<NodeGroup> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    let (data, status) = serialize_response(response, status);
+    response_json(data, status)
+}
+
+fn group_not_found() -> Response {
+    group_error(messages::GROUP_NOT_FOUND, StatusCode::NOT_FOUND)
+}
+
+async fn group_nodes(client: &Object, id: i32, owner: Option<i32>) -> Result<Vec<Node>, Response> {
+    if groups::get_group(client, id, owner).await.is_none() {
+        return Err(group_not_found());
+    }
+    nodes::get_group_nodes(client, id, owner)
+        .await
+        .map_err(|e| group_error(&e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+impl App {
+    pub async fn handle_get_groups(&self, req: Request) -> Result<Response, Error> {
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let owner = (!claims.isadmin).then_some(claims.user_id);
+                let (data, status) = groups::get_groups(&client, owner).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_post_group(&self, mut req: Request) -> Result<Response, Error> {
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let payload: NodeGroupPayload = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) =
+                    groups::add_group(&mut client, payload, claims.user_id, client_ip(&req)).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_get_group_by_id(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/groups/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    match groups::get_group(&client, id, owner).await {
+                        Some(group) => {
+                            let response = ApiResponse {
+                                message: messages::OK,
+                                data: Data::Single(group),
+                            };
+                            let (data, status) = serialize_response(response, StatusCode::OK);
+                            Ok(response_json(data, status))
+                        }
+                        None => Ok(group_not_found()),
+                    }
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_update_group(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/groups/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: NodeGroupPayload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let (data, status) = groups::update_group(
+                        &mut client,
+                        id,
+                        payload,
+                        claims.user_id,
+                        owner,
+                        client_ip(&req),
+                    )
+                    .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_delete_group(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/groups/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let (data, status) = groups::delete_group(
+                        &mut client,
+                        id,
+                        claims.user_id,
+                        owner,
+                        client_ip(&req),
+                    )
+                    .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_add_group_members(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/groups/", "/nodes/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: GroupMembers = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let Some(group) = groups::get_group(&client, id, owner).await else {
+                        return Ok(group_not_found());
+                    };
+                    let (data, status) =
+                        groups::add_members(&client, &group, payload, Some(group.user_id)).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_remove_group_members(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/groups/", "/nodes/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: GroupMembers = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let Some(group) = groups::get_group(&client, id, owner).await else {
+                        return Ok(group_not_found());
+                    };
+                    let (data, status) = groups::remove_members(&client, &group, payload).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_post_group_command(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/groups/", "/commands/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: CommandPayload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let node_ids = match group_nodes(&client, id, owner).await {
+                        Ok(nodes) => nodes.iter().map(|node| node.id).collect(),
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) =
+                        commands::add_group_command(&client, node_ids, claims.user_id, payload)
+                            .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_put_group_detectors(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/groups/", "/detectors/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: Vec<Detector> = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let owner = (!claims.isadmin).then_some(claims.user_id);
+                    let nodes = match group_nodes(&client, id, owner).await {
+                        Ok(nodes) => nodes,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) =
+                        anomalies::set_group_detectors(&mut client, id, &nodes, payload).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_export_group_feeds(&self, req: Request) -> Result<Response, Error> {
+        let id = extract_id_from_subpath(
+            req.path().trim_end_matches('/'),
+            "/groups/",
+            "/feeds/export",
+        );
+        let (Some(id), Some(filter)) = (id, parse_query::<FeedExportQuery>(&req)) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let owner = (!claims.isadmin).then_some(claims.user_id);
+                let nodes = match group_nodes(&client, id, owner).await {
+                    Ok(nodes) => nodes,
+                    Err(res) => return Ok(res),
+                };
+                let sensor_names: Vec<(i32, Vec<String>)> = nodes
+                    .into_iter()
+                    .map(|node| {
+                        let names = node
+                            .hardware_sensor_names
+                            .into_iter()
+                            .chain(node.virtual_sensors.into_iter().map(|sensor| sensor.name))
+                            .map(|name| name.into_owned())
+                            .collect();
+                        (node.id, names)
+                    })
+                    .collect();
+
+                let (columns, layouts) = group_columns(&sensor_names);
+                let width = columns.len();
+                let encoder = match FeedEncoder::new(filter.format, columns, true) {
+                    Ok(encoder) => encoder,
+                    Err(_) => return self.handle_bad_request(req).await,
+                };
+                let (tx, rx) = mpsc::channel(2);
+                ntex::rt::spawn(
+                    feeds::export_feeds(
+                        client,
+                        layouts,
+                        width,
+                        filter.from,
+                        filter.to,
+                        encoder,
+                        tx,
+                    )
+                    .in_current_span(),
+                );
+
+                let mut res = Response::Ok().streaming(rx);
+                res.headers_mut()
+                    .insert(CONTENT_TYPE, filter.format.content_type());
+                if let Ok(disposition) = HeaderValue::from_str(&format!(
+                    "attachment; filename=\"group-{}-feeds.{}\"",
+                    id,
+                    filter.format.extension()
+                )) {
+                    res.headers_mut().insert(CONTENT_DISPOSITION, disposition);
+                }
+                res.headers_mut().insert(SERVER, HDR_SERVER);
+                Ok(res)
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_assign_tags(&self, mut req: Request) -> Result<Response, Error> {
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let payload: TagAssignment = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let owner = (!claims.isadmin).then_some(claims.user_id);
+                let (data, status) = groups::assign_tags(&mut client, payload, owner).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_get_node_tags(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/tags/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    if nodes::get_node(&client, id, claims.user_id, claims.isadmin)
+                        .await
+                        .is_none()
+                    {
+                        return self.handle_node_not_found(req).await;
+                    }
+                    let (data, status) = groups::get_node_tags(&client, id).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+}
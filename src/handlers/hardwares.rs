@@ -3,16 +3,25 @@ use ntex::web::Error;
 
 use crate::constant::messages;
 use crate::database::hardwares;
+use crate::models::hardwares::HardwareListQuery;
 use crate::utils::auth::{authenticate, authenticate_admin};
-use crate::utils::http::extract_id_from_path;
+use crate::utils::http::{
+    client_ip, extract_id_from_path, extract_id_from_subpath, parse_query, read_json,
+};
 use crate::{app::App, utils::http::response_json};
 
 impl App {
     pub async fn handle_get_hardwares(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<HardwareListQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
         match authenticate(&req).await {
             Ok(_) => {
-                let client = self.pool.get().await.unwrap();
-                let (data, status) = hardwares::get_all_hardware(&client).await;
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) = hardwares::get_all_hardware(&client, filter).await;
                 Ok(response_json(data, status))
             }
             Err(err) => self.handle_not_authenticated_with_message(req, err).await,
@@ -21,10 +30,18 @@ impl App {
 
     pub async fn handle_post_hardwares(&self, mut req: Request) -> Result<Response, Error> {
         match authenticate_admin(&req).await {
-            Ok(_) => {
-                let payload = req.payload();
-                let client = self.pool.get().await.unwrap();
-                let (data, status) = hardwares::add_hardware(&client, payload).await;
+            Ok(claims) => {
+                let ip = client_ip(&req);
+                let payload = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) =
+                    hardwares::add_hardware(&mut client, payload, claims.user_id, ip).await;
                 Ok(response_json(data, status))
             }
             Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
@@ -36,7 +53,10 @@ impl App {
         match extract_id_from_path(req.path(), "/hardwares/") {
             Some(id) => match authenticate(&req).await {
                 Ok(_) => {
-                    let client = self.pool.get().await.unwrap();
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
                     let (data, status) = hardwares::get_one_hardware(&client, id).await;
                     Ok(response_json(data, status))
                 }
@@ -49,10 +69,19 @@ impl App {
     pub async fn handle_update_hardware(&self, mut req: Request) -> Result<Response, Error> {
         match extract_id_from_path(req.path(), "/hardwares/") {
             Some(id) => match authenticate_admin(&req).await {
-                Ok(_) => {
-                    let payload = req.payload();
-                    let client = self.pool.get().await.unwrap();
-                    let (data, status) = hardwares::update_hardware(&client, id, payload).await;
+                Ok(claims) => {
+                    let ip = client_ip(&req);
+                    let payload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) =
+                        hardwares::update_hardware(&mut client, id, payload, claims.user_id, ip)
+                            .await;
                     Ok(response_json(data, status))
                 }
                 Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
@@ -65,9 +94,57 @@ impl App {
     pub async fn handle_delete_hardware(&self, req: Request) -> Result<Response, Error> {
         match extract_id_from_path(req.path(), "/hardwares/") {
             Some(id) => match authenticate_admin(&req).await {
-                Ok(_) => {
-                    let client = self.pool.get().await.unwrap();
-                    let (data, status) = hardwares::delete_hardware(&client, id).await;
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = hardwares::delete_hardware(
+                        &mut client,
+                        id,
+                        claims.user_id,
+                        client_ip(&req),
+                    )
+                    .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_deleted_hardwares(&self, req: Request) -> Result<Response, Error> {
+        match authenticate_admin(&req).await {
+            Ok(_) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) = hardwares::get_deleted_hardware(&client).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_restore_hardware(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/hardwares/", "/restore/") {
+            Some(id) => match authenticate_admin(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = hardwares::restore_hardware(
+                        &mut client,
+                        id,
+                        claims.user_id,
+                        client_ip(&req),
+                    )
+                    .await;
                     Ok(response_json(data, status))
                 }
                 Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use deadpool_postgres::Timeouts;
+use ntex::http::header::{HeaderValue, CONTENT_TYPE, SERVER};
+use ntex::http::{Request, Response, StatusCode};
+use ntex::web::Error;
+
+use crate::constant::{config, messages, query};
+use crate::metrics;
+use crate::models::response::{ApiResponse, Data};
+use crate::utils::http::serialize_response;
+use crate::utils::HDR_SERVER;
+use crate::{app::App, utils::http::response_json};
+
+impl App {
+    pub async fn handle_healthz(&self, _: Request) -> Result<Response, Error> {
+        let response: ApiResponse<()> = ApiResponse {
+            message: messages::OK,
+            data: Data::None,
+        };
+        let (data, status) = serialize_response(response, StatusCode::OK);
+        Ok(response_json(data, status))
+    }
+
+    pub async fn handle_readyz(&self, _: Request) -> Result<Response, Error> {
+        let timeouts = Timeouts {
+            wait: Some(Duration::from_millis(config::READYZ_TIMEOUT_MS)),
+            ..Timeouts::default()
+        };
+        let ready = match self.pool.timeout_get(&timeouts).await {
+            Ok(client) => client.simple_query(query::HEALTH_CHECK).await.is_ok(),
+            Err(_) => false,
+        };
+        let (message, status) = if ready {
+            (messages::OK, StatusCode::OK)
+        } else {
+            (
+                messages::DATABASE_UNAVAILABLE,
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        };
+        let response: ApiResponse<()> = ApiResponse {
+            message,
+            data: Data::None,
+        };
+        let (data, status) = serialize_response(response, status);
+        Ok(response_json(data, status))
+    }
+
+    pub async fn handle_metrics(&self, _: Request) -> Result<Response, Error> {
+        let mut res = Response::Ok().body(metrics::render(&self.pool, self.replica.as_ref()));
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        );
+        res.headers_mut().insert(SERVER, HDR_SERVER);
+        Ok(res)
+    }
+}
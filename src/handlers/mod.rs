@@ -1,17 +1,52 @@
+pub mod anomalies;
+pub mod audit;
+pub mod commands;
 pub mod feed;
+pub mod firmwares;
+pub mod groups;
 pub mod hardwares;
+pub mod health;
 pub mod nodes;
+pub mod shadows;
 pub mod users;
 
+use deadpool_postgres::Object;
+use ntex::http::header::{HeaderValue, RETRY_AFTER};
 use ntex::http::{Request, Response, StatusCode};
 use ntex::web::Error;
 
 use crate::constant::messages;
+use crate::database::pool;
 use crate::models::response::{ApiResponse, Data};
 use crate::utils::http::serialize_response;
 use crate::{app::App, utils::http::response_json};
 
 impl App {
+    pub async fn client(&self) -> Result<Object, Response> {
+        self.pool.get().await.map_err(|e| {
+            tracing::warn!(error = %e, "Database connection unavailable");
+            let response: ApiResponse<()> = ApiResponse {
+                message: messages::DATABASE_UNAVAILABLE,
+                data: Data::None,
+            };
+            let (data, status) = serialize_response(response, StatusCode::SERVICE_UNAVAILABLE);
+            let mut res = response_json(data, status);
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+            res
+        })
+    }
+
+    pub async fn read_client(&self) -> Result<Object, Response> {
+        if let Some(replica) = self.replica.as_ref().filter(|_| pool::replica_healthy()) {
+            match replica.get().await {
+                Ok(client) => return Ok(client),
+                Err(e) => tracing::warn!(error = %e, "replica connection unavailable"),
+            }
+        }
+        self.client().await
+    }
+
     pub async fn handle_not_found(&self, _: Request) -> Result<Response, Error> {
         let response: ApiResponse<()> = ApiResponse {
             message: messages::NOT_FOUND,
@@ -51,4 +86,13 @@ impl App {
         let (data, status) = serialize_response(response, StatusCode::BAD_REQUEST);
         Ok(response_json(data, status))
     }
+
+    pub async fn handle_node_not_found(&self, _: Request) -> Result<Response, Error> {
+        let response: ApiResponse<()> = ApiResponse {
+            message: messages::NODE_NOT_FOUND,
+            data: Data::None,
+        };
+        let (data, status) = serialize_response(response, StatusCode::NOT_FOUND);
+        Ok(response_json(data, status))
+    }
 }
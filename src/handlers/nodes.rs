@@ -1,33 +1,118 @@
+use futures::channel::mpsc;
+use ntex::http::header::{HeaderValue, CONTENT_TYPE};
 use ntex::http::{Request, Response};
 use ntex::web::Error;
+use tracing::Instrument;
 
+use crate::constant::messages;
 use crate::database::nodes;
-use crate::utils::auth::authenticate;
-use crate::utils::http::extract_id_from_path;
+use crate::models::{
+    feeds::{NodeFeedsQuery, NodeTrackQuery},
+    nodes::NodeListQuery,
+};
+use crate::utils::auth::{authenticate, authenticate_admin};
+use crate::utils::http::{
+    client_ip, extract_id_from_path, extract_id_from_subpath, parse_query, read_json,
+    response_json_stream,
+};
 use crate::{app::App, utils::http::response_json};
 
 impl App {
     pub async fn handle_get_nodes(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<NodeListQuery>(&req).filter(NodeListQuery::is_valid)
+        else {
+            return self.handle_bad_request(req).await;
+        };
         match authenticate(&req).await {
             Ok(claims) => {
-                let client = self.pool.get().await.unwrap();
-                let (data, status) =
-                    nodes::get_all_nodes(&client, claims.user_id, claims.isadmin).await;
-                Ok(response_json(data, status))
+                let client = match self.read_client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (tx, rx) = mpsc::channel(2);
+                ntex::rt::spawn(
+                    nodes::stream_all_nodes(client, claims.user_id, claims.isadmin, filter, tx)
+                        .in_current_span(),
+                );
+                Ok(response_json_stream(rx))
             }
             Err(err) => self.handle_not_authenticated_with_message(req, err).await,
         }
     }
 
+    pub async fn handle_get_nodes_geojson(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<NodeListQuery>(&req).filter(NodeListQuery::is_valid)
+        else {
+            return self.handle_bad_request(req).await;
+        };
+        let client = match self.read_client().await {
+            Ok(client) => client,
+            Err(res) => return Ok(res),
+        };
+        let (tx, rx) = mpsc::channel(2);
+        ntex::rt::spawn(nodes::stream_public_nodes_geojson(client, filter, tx).in_current_span());
+        let mut res = response_json_stream(rx);
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/geo+json"),
+        );
+        Ok(res)
+    }
+
     pub async fn handle_get_node_by_id(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<NodeFeedsQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
         match extract_id_from_path(req.path(), "/nodes/") {
             Some(id) => match authenticate(&req).await {
                 Ok(claims) => {
-                    let client = self.pool.get().await.unwrap();
-                    let (data, status) =
-                        nodes::get_node_with_feeds(&client, id, claims.user_id, claims.isadmin)
-                            .await;
-                    Ok(response_json(data, status))
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    let (tx, rx) = mpsc::channel(2);
+                    ntex::rt::spawn(
+                        nodes::stream_node_with_feeds(client, node, filter, tx).in_current_span(),
+                    );
+                    Ok(response_json_stream(rx))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_node_track(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<NodeTrackQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match extract_id_from_subpath(req.path(), "/nodes/", "/track/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    let (tx, rx) = mpsc::channel(2);
+                    ntex::rt::spawn(
+                        nodes::stream_node_track(client, node, filter, tx).in_current_span(),
+                    );
+                    let mut res = response_json_stream(rx);
+                    res.headers_mut().insert(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("application/geo+json"),
+                    );
+                    Ok(res)
                 }
                 Err(err) => self.handle_not_authenticated_with_message(req, err).await,
             },
@@ -38,9 +123,17 @@ impl App {
     pub async fn handle_post_nodes(&self, mut req: Request) -> Result<Response, Error> {
         match authenticate(&req).await {
             Ok(claims) => {
-                let payload = req.payload();
-                let client = self.pool.get().await.unwrap();
-                let (data, status) = nodes::add_node(&client, payload, claims.user_id).await;
+                let ip = client_ip(&req);
+                let payload = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) =
+                    nodes::add_node(&mut client, payload, claims.user_id, ip).await;
                 Ok(response_json(data, status))
             }
             Err(err) => self.handle_not_authenticated_with_message(req, err).await,
@@ -51,11 +144,24 @@ impl App {
         match extract_id_from_path(req.path(), "/nodes/") {
             Some(id) => match authenticate(&req).await {
                 Ok(claims) => {
-                    let payload = req.payload();
-                    let client = self.pool.get().await.unwrap();
-                    let (data, status) =
-                        nodes::update_node(&client, id, payload, claims.user_id, claims.isadmin)
-                            .await;
+                    let ip = client_ip(&req);
+                    let payload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = nodes::update_node(
+                        &mut client,
+                        id,
+                        payload,
+                        claims.user_id,
+                        claims.isadmin,
+                        ip,
+                    )
+                    .await;
                     Ok(response_json(data, status))
                 }
                 Err(err) => self.handle_not_authenticated_with_message(req, err).await,
@@ -68,11 +174,54 @@ impl App {
         match extract_id_from_path(req.path(), "/nodes/") {
             Some(id) => match authenticate(&req).await {
                 Ok(claims) => {
-                    let client = self.pool.get().await.unwrap();
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) = nodes::delete_node(
+                        &mut client,
+                        id,
+                        claims.user_id,
+                        claims.isadmin,
+                        client_ip(&req),
+                    )
+                    .await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_deleted_nodes(&self, req: Request) -> Result<Response, Error> {
+        match authenticate_admin(&req).await {
+            Ok(_) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) = nodes::get_deleted_nodes(&client).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_restore_node(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/restore/") {
+            Some(id) => match authenticate_admin(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
                     let (data, status) =
-                        nodes::delete_node(&client, id, claims.user_id, claims.isadmin).await;
+                        nodes::restore_node(&mut client, id, claims.user_id, client_ip(&req)).await;
                     Ok(response_json(data, status))
                 }
+                Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
                 Err(err) => self.handle_not_authenticated_with_message(req, err).await,
             },
             None => self.handle_bad_request(req).await,
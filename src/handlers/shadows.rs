@@ -0,0 +1,122 @@
+use ntex::http::{Request, Response};
+use ntex::web::Error;
+
+use crate::database::{nodes, shadows};
+use crate::models::shadows::{ShadowPayload, ShadowQuery, ShadowReport};
+use crate::utils::auth::authenticate;
+use crate::utils::http::{extract_id_from_subpath, parse_query, read_json};
+use crate::{app::App, utils::http::response_json};
+
+impl App {
+    pub async fn handle_get_shadow(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/shadow/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let client = match self.read_client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    if node.user_id != claims.user_id && !claims.isadmin {
+                        return self.handle_not_authorized(req).await;
+                    }
+                    let (data, status) = shadows::get_shadow(&client, id).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_update_desired_shadow(&self, mut req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/nodes/", "/shadow/") {
+            Some(id) => match authenticate(&req).await {
+                Ok(claims) => {
+                    let payload: ShadowPayload = match read_json(&mut req).await {
+                        Ok(payload) => payload,
+                        Err(res) => return Ok(res),
+                    };
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let Some(node) =
+                        nodes::get_node(&client, id, claims.user_id, claims.isadmin).await
+                    else {
+                        return self.handle_node_not_found(req).await;
+                    };
+                    if node.user_id != claims.user_id && !claims.isadmin {
+                        return self.handle_not_authorized(req).await;
+                    }
+                    let (data, status) =
+                        shadows::update_desired(&client, id, payload.state, payload.version).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_device_shadow(&self, req: Request) -> Result<Response, Error> {
+        let Some(filter) = parse_query::<ShadowQuery>(&req) else {
+            return self.handle_bad_request(req).await;
+        };
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) =
+                    nodes::get_node(&client, filter.node_id, claims.user_id, false).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                if node.user_id != claims.user_id {
+                    return self.handle_not_authorized(req).await;
+                }
+                let (data, status) = shadows::get_shadow(&client, filter.node_id).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_report_shadow(&self, mut req: Request) -> Result<Response, Error> {
+        match authenticate(&req).await {
+            Ok(claims) => {
+                let payload: ShadowReport = match read_json(&mut req).await {
+                    Ok(payload) => payload,
+                    Err(res) => return Ok(res),
+                };
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let Some(node) =
+                    nodes::get_node(&client, payload.node_id, claims.user_id, false).await
+                else {
+                    return self.handle_node_not_found(req).await;
+                };
+                if node.user_id != claims.user_id {
+                    return self.handle_not_authorized(req).await;
+                }
+                let (data, status) = shadows::update_reported(
+                    &client,
+                    payload.node_id,
+                    payload.state,
+                    payload.version,
+                )
+                .await;
+                Ok(response_json(data, status))
+            }
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+}
@@ -4,14 +4,19 @@ use ntex::web::Error;
 use crate::constant::messages;
 use crate::database::users;
 use crate::utils::auth::{authenticate, authenticate_admin};
-use crate::utils::http::{extract_id_from_path, extract_jwt_from_path};
+use crate::utils::http::{
+    client_ip, extract_id_from_path, extract_id_from_subpath, extract_jwt_from_path, read_json,
+};
 use crate::{app::App, utils::http::response_json};
 
 impl App {
     pub async fn handle_get_users(&self, req: Request) -> Result<Response, Error> {
         match authenticate_admin(&req).await {
             Ok(_) => {
-                let client = self.pool.get().await.unwrap();
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
                 let (data, status) = users::get_all_users(&client).await;
                 Ok(response_json(data, status))
             }
@@ -21,16 +26,30 @@ impl App {
     }
 
     pub async fn handle_post_signup(&self, mut req: Request) -> Result<Response, Error> {
-        let payload = req.payload();
-        let client = self.pool.get().await.unwrap();
-        let (data, status) = users::register_user(&client, payload).await;
+        let ip = client_ip(&req);
+        let payload = match read_json(&mut req).await {
+            Ok(payload) => payload,
+            Err(res) => return Ok(res),
+        };
+        let mut client = match self.client().await {
+            Ok(client) => client,
+            Err(res) => return Ok(res),
+        };
+        let (data, status) = users::register_user(&mut client, payload, ip).await;
         Ok(response_json(data, status))
     }
 
     pub async fn handle_post_login(&self, mut req: Request) -> Result<Response, Error> {
-        let payload = req.payload();
-        let client = self.pool.get().await.unwrap();
-        let (data, status) = users::login_user(&client, payload).await;
+        let ip = client_ip(&req);
+        let payload = match read_json(&mut req).await {
+            Ok(payload) => payload,
+            Err(res) => return Ok(res),
+        };
+        let mut client = match self.client().await {
+            Ok(client) => client,
+            Err(res) => return Ok(res),
+        };
+        let (data, status) = users::login_user(&mut client, payload, ip).await;
         Ok(response_json(data, status))
     }
 
@@ -41,7 +60,10 @@ impl App {
                     if claims.user_id != id && !claims.isadmin {
                         return self.handle_not_authorized(req).await;
                     }
-                    let client = self.pool.get().await.unwrap();
+                    let client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
                     let (data, status) = users::get_one_user(&client, id).await;
                     Ok(response_json(data, status))
                 }
@@ -54,8 +76,12 @@ impl App {
     pub async fn handle_activate_user(&self, req: Request) -> Result<Response, Error> {
         match extract_jwt_from_path(req.path(), "/activate/") {
             Some(token) => {
-                let client = self.pool.get().await.unwrap();
-                let (data, status) = users::activate_user(&client, token).await;
+                let mut client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) =
+                    users::activate_user(&mut client, token, client_ip(&req)).await;
                 Ok(response_json(data, status))
             }
             None => self.handle_bad_request(req).await,
@@ -63,16 +89,83 @@ impl App {
     }
 
     pub async fn handle_forgot_password(&self, mut req: Request) -> Result<Response, Error> {
-        let payload = req.payload();
-        let client = self.pool.get().await.unwrap();
-        let (data, status) = users::forgot_password(&client, payload).await;
+        let ip = client_ip(&req);
+        let payload = match read_json(&mut req).await {
+            Ok(payload) => payload,
+            Err(res) => return Ok(res),
+        };
+        let mut client = match self.client().await {
+            Ok(client) => client,
+            Err(res) => return Ok(res),
+        };
+        let (data, status) = users::forgot_password(&mut client, payload, ip).await;
         Ok(response_json(data, status))
     }
 
     pub async fn handle_change_password(&self, mut req: Request) -> Result<Response, Error> {
-        let payload = req.payload();
-        let client = self.pool.get().await.unwrap();
-        let (data, status) = users::change_password(&client, payload).await;
+        let ip = client_ip(&req);
+        let payload = match read_json(&mut req).await {
+            Ok(payload) => payload,
+            Err(res) => return Ok(res),
+        };
+        let mut client = match self.client().await {
+            Ok(client) => client,
+            Err(res) => return Ok(res),
+        };
+        let (data, status) = users::change_password(&mut client, payload, ip).await;
         Ok(response_json(data, status))
     }
+
+    pub async fn handle_delete_user(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_path(req.path(), "/users/") {
+            Some(id) => match authenticate_admin(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) =
+                        users::delete_user(&mut client, id, claims.user_id, client_ip(&req)).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
+
+    pub async fn handle_get_deleted_users(&self, req: Request) -> Result<Response, Error> {
+        match authenticate_admin(&req).await {
+            Ok(_) => {
+                let client = match self.client().await {
+                    Ok(client) => client,
+                    Err(res) => return Ok(res),
+                };
+                let (data, status) = users::get_deleted_users(&client).await;
+                Ok(response_json(data, status))
+            }
+            Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+            Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+        }
+    }
+
+    pub async fn handle_restore_user(&self, req: Request) -> Result<Response, Error> {
+        match extract_id_from_subpath(req.path(), "/users/", "/restore/") {
+            Some(id) => match authenticate_admin(&req).await {
+                Ok(claims) => {
+                    let mut client = match self.client().await {
+                        Ok(client) => client,
+                        Err(res) => return Ok(res),
+                    };
+                    let (data, status) =
+                        users::restore_user(&mut client, id, claims.user_id, client_ip(&req)).await;
+                    Ok(response_json(data, status))
+                }
+                Err(err) if err == messages::UNAUTHORIZED => self.handle_not_authorized(req).await,
+                Err(err) => self.handle_not_authenticated_with_message(req, err).await,
+            },
+            None => self.handle_bad_request(req).await,
+        }
+    }
 }
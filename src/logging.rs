@@ -0,0 +1,43 @@
+use std::cell::Cell;
+
+use ntex::http::{
+    header::{HeaderName, HeaderValue},
+    Request,
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::{constant::config, utils::generate_string};
+
+pub const HDR_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    pub static CURRENT_USER: Cell<Option<i32>>;
+}
+
+pub fn init() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(config::LOG_LEVEL));
+    tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(true)
+        .with_env_filter(filter)
+        .init();
+}
+
+pub fn request_id(req: &Request) -> HeaderValue {
+    req.headers()
+        .get(&HDR_REQUEST_ID)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= config::REQUEST_ID_MAX_LEN
+                && id.as_bytes().iter().all(u8::is_ascii_graphic)
+        })
+        .cloned()
+        .unwrap_or_else(|| HeaderValue::from_str(&generate_string(config::REQUEST_ID_LEN)).unwrap())
+}
+
+pub fn set_user(user_id: i32) {
+    let _ = CURRENT_USER.try_with(|user| user.set(Some(user_id)));
+}
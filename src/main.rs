@@ -3,62 +3,118 @@
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 use app::AppFactory;
-use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime};
-use ntex::http::{HttpService, KeepAlive::Os};
+use constant::config;
+use deadpool_postgres::Pool;
+use ntex::http::{body::Body, HttpService, KeepAlive::Os};
+use ntex::io::Filter;
 use ntex::server;
 use ntex::{time::Seconds, util::PoolId, util::Ready};
 use std::io::Result as IoResult;
 use std::sync::{Arc, Mutex};
-use tokio_postgres::NoTls;
+use std::time::Duration;
 
 mod app;
+mod cli;
 mod constant;
 mod database;
 mod handlers;
+mod logging;
+mod metrics;
 mod models;
+mod tasks;
+mod tls;
 mod utils;
 
+fn http_service<F: Filter>(
+    pool: Arc<Pool>,
+    replica: Option<Arc<Pool>>,
+    health_only: bool,
+) -> HttpService<F, AppFactory, Body> {
+    HttpService::build()
+        .keep_alive(Os)
+        .client_timeout(Seconds(config::CLIENT_TIMEOUT_SECS))
+        .headers_read_rate(
+            Seconds(config::CLIENT_TIMEOUT_SECS),
+            Seconds(config::HEADERS_READ_MAX_TIMEOUT_SECS),
+            config::HEADERS_READ_RATE,
+        )
+        .payload_read_rate(
+            Seconds(config::PAYLOAD_READ_TIMEOUT_SECS),
+            Seconds(config::PAYLOAD_READ_MAX_TIMEOUT_SECS),
+            config::PAYLOAD_READ_RATE,
+        )
+        .finish(AppFactory {
+            pool,
+            replica,
+            health_only,
+        })
+}
+
 #[ntex::main]
 async fn main() -> IoResult<()> {
-    println!("Starting http server: http://127.0.0.1:8080");
+    logging::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(database::pool::create(), &args).await;
+    }
 
     let cores = core_affinity::get_core_ids().unwrap();
     let total_cores = cores.len();
     let cores = Arc::new(Mutex::new(cores));
 
-    let mut cfg_pool = Config::new();
-    cfg_pool.dbname = Some("rustdemo".to_string());
-    cfg_pool.user = Some("postgres".to_string());
-    cfg_pool.password = Some("password".to_string());
-    cfg_pool.host = Some("localhost".to_string());
-    cfg_pool.manager = Some(ManagerConfig {
-        recycling_method: RecyclingMethod::Fast,
-    });
-    cfg_pool.get_pool_config().max_size = 50;
+    let pool = database::pool::create();
 
-    let pool: Pool = cfg_pool
-        .create_pool(Some(Runtime::Tokio1), NoTls)
-        .expect("Failed to create pool");
+    ntex::rt::spawn(tasks::purge::run(pool.clone()));
+    ntex::rt::spawn(tasks::purge::watch_deleted_users(pool.clone()));
+    ntex::rt::spawn(tasks::partitions::run(pool.clone()));
+    ntex::rt::spawn(tasks::rollup::run(pool.clone()));
+    ntex::rt::spawn(tasks::commands::run(pool.clone()));
+    tasks::mail::start();
 
     let pool = Arc::new(pool);
+    let replica = database::pool::create_replica().map(|replica| {
+        ntex::rt::spawn(tasks::replica::run(replica.clone()));
+        Arc::new(replica)
+    });
 
-    server::build()
-        .backlog(1024)
-        .bind("techempower", "0.0.0.0:8080", {
-            let pool = pool.clone();
+    let mut builder = server::build().backlog(1024);
+    if tls::enabled() {
+        let store = tls::CertStore::open()?;
+        ntex::rt::spawn(tasks::tls::run(store.clone()));
+        tracing::info!("Starting https server: https://{}", config::HTTPS_ADDR);
+        builder = builder.bind("https", config::HTTPS_ADDR, {
+            let (pool, replica) = (pool.clone(), replica.clone());
             move |cfg| {
                 cfg.memory_pool(PoolId::P1);
                 PoolId::P1.set_read_params(65535, 2048);
                 PoolId::P1.set_write_params(65535, 2048);
 
-                HttpService::build()
-                    .keep_alive(Os)
-                    .client_timeout(Seconds(0))
-                    .headers_read_rate(Seconds::ZERO, Seconds::ZERO, 0)
-                    .payload_read_rate(Seconds::ZERO, Seconds::ZERO, 0)
-                    .h1(AppFactory { pool: pool.clone() })
+                http_service(pool.clone(), replica.clone(), false).rustls(store.server_config())
             }
-        })?
+        })?;
+    } else {
+        tracing::info!("Starting http server: http://{}", config::HTTP_ADDR);
+        builder = builder.bind("techempower", config::HTTP_ADDR, {
+            let (pool, replica) = (pool.clone(), replica.clone());
+            move |cfg| {
+                cfg.memory_pool(PoolId::P1);
+                PoolId::P1.set_read_params(65535, 2048);
+                PoolId::P1.set_write_params(65535, 2048);
+
+                http_service(pool.clone(), replica.clone(), false)
+            }
+        })?;
+    }
+    if let Some(addr) = config::HEALTH_ADDR {
+        tracing::info!("Starting health server: http://{}", addr);
+        builder = builder.bind("health", addr, {
+            let (pool, replica) = (pool.clone(), replica.clone());
+            move |_| http_service(pool.clone(), replica.clone(), true)
+        })?;
+    }
+
+    let srv = builder
         .configure(move |cfg| {
             let cores = cores.clone();
             cfg.on_worker_start(move |_| {
@@ -70,6 +126,18 @@ async fn main() -> IoResult<()> {
             Ok(())
         })?
         .workers(total_cores)
-        .run()
-        .await
+        .disable_signals()
+        .shutdown_timeout(Seconds(config::SHUTDOWN_TIMEOUT_SECS))
+        .run();
+
+    ntex::rt::spawn(tasks::shutdown::on_signal(srv.clone()));
+    let result = srv.await;
+
+    tasks::mail::drain(Duration::from_secs(config::MAIL_DRAIN_TIMEOUT_SECS));
+    if let Err(e) = tasks::rollup::flush(&pool).await {
+        tracing::error!(error = %e, "rollup flush on shutdown failed");
+    }
+    pool.close();
+    tracing::info!("Server stopped");
+    result
 }
@@ -0,0 +1,281 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::{Duration, Instant},
+};
+
+use deadpool_postgres::Pool;
+use ntex::http::{Method, StatusCode};
+
+use crate::{
+    app::{ROUTES, ROUTE_COUNT},
+    database::pool as db_pool,
+};
+
+const OTHER_ROUTE: usize = ROUTE_COUNT;
+const STATUS_COUNT: usize = 500;
+const CLASS_COUNT: usize = 5;
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+const BCRYPT_BUCKETS: [f64; 8] = [0.05, 0.1, 0.2, 0.3, 0.5, 0.75, 1.0, 2.0];
+
+pub struct Histogram<const N: usize> {
+    bounds: [f64; N],
+    buckets: [AtomicU64; N],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(bounds: [f64; N]) -> Self {
+        Histogram {
+            bounds,
+            buckets: [const { AtomicU64::new(0) }; N],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(index) = self.bounds.iter().position(|bound| seconds <= *bound) {
+            self.buckets[index].fetch_add(1, Relaxed);
+        }
+        self.count.fetch_add(1, Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let count = self.count.load(Relaxed);
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(
+            out,
+            "{}_sum{} {}",
+            name,
+            labels,
+            self.sum_micros.load(Relaxed) as f64 / 1e6
+        );
+        let _ = writeln!(out, "{}_count{} {}", name, labels, count);
+    }
+}
+
+static REQUESTS: [[AtomicU64; STATUS_COUNT]; ROUTE_COUNT + 1] =
+    [const { [const { AtomicU64::new(0) }; STATUS_COUNT] }; ROUTE_COUNT + 1];
+static LATENCY: [[Histogram<11>; CLASS_COUNT]; ROUTE_COUNT + 1] =
+    [const { [const { Histogram::new(LATENCY_BUCKETS) }; CLASS_COUNT] }; ROUTE_COUNT + 1];
+static FEEDS_INGESTED: AtomicU64 = AtomicU64::new(0);
+static ANOMALIES_DETECTED: AtomicU64 = AtomicU64::new(0);
+static BCRYPT: Histogram<8> = Histogram::new(BCRYPT_BUCKETS);
+
+fn segment_matches(template: &str, segment: &str) -> bool {
+    match template {
+        "{id}" => !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()),
+        "{token}" => !segment.is_empty(),
+        _ => template == segment,
+    }
+}
+
+fn path_matches(template: &str, path: &str) -> bool {
+    let mut templates = template.trim_end_matches('/').split('/');
+    let mut segments = path.trim_end_matches('/').split('/');
+    loop {
+        match (templates.next(), segments.next()) {
+            (Some(template), Some(segment)) if segment_matches(template, segment) => (),
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+pub fn route(method: &Method, path: &str) -> usize {
+    ROUTES
+        .iter()
+        .position(|(route_method, template, _)| {
+            route_method == method && path_matches(template, path)
+        })
+        .unwrap_or(OTHER_ROUTE)
+}
+
+pub fn record_request(route: usize, status: StatusCode, elapsed: Duration) {
+    let code = status.as_u16() as usize;
+    if (100..100 + STATUS_COUNT).contains(&code) {
+        REQUESTS[route][code - 100].fetch_add(1, Relaxed);
+        LATENCY[route][code / 100 - 1].observe(elapsed);
+    }
+}
+
+pub fn record_feeds_ingested(count: u64) {
+    FEEDS_INGESTED.fetch_add(count, Relaxed);
+}
+
+pub fn record_anomalies_detected(count: u64) {
+    ANOMALIES_DETECTED.fetch_add(count, Relaxed);
+}
+
+pub fn time_bcrypt<T>(f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    BCRYPT.observe(start.elapsed());
+    result
+}
+
+pub fn route_name(route: usize) -> &'static str {
+    route_labels(route).1
+}
+
+fn route_labels(route: usize) -> (&'static str, &'static str) {
+    match ROUTES.get(route) {
+        Some((method, template, _)) => (method.as_str(), template),
+        None => ("OTHER", "other"),
+    }
+}
+
+pub fn render(pool: &Pool, replica: Option<&Pool>) -> String {
+    let mut out = String::with_capacity(16 * 1024);
+
+    out.push_str("# HELP http_requests_total Total HTTP requests by route and status.\n");
+    out.push_str("# TYPE http_requests_total counter\n");
+    for (route, statuses) in REQUESTS.iter().enumerate() {
+        let (method, path) = route_labels(route);
+        for (index, counter) in statuses.iter().enumerate() {
+            let count = counter.load(Relaxed);
+            if count > 0 {
+                let _ = writeln!(
+                    out,
+                    "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method,
+                    path,
+                    index + 100,
+                    count
+                );
+            }
+        }
+    }
+
+    out.push_str(
+        "# HELP http_request_duration_seconds HTTP request latency by route and status class.\n",
+    );
+    out.push_str("# TYPE http_request_duration_seconds histogram\n");
+    for (route, classes) in LATENCY.iter().enumerate() {
+        let (method, path) = route_labels(route);
+        for (index, histogram) in classes.iter().enumerate() {
+            if histogram.count.load(Relaxed) > 0 {
+                let labels = format!(
+                    "method=\"{}\",route=\"{}\",status=\"{}xx\"",
+                    method,
+                    path,
+                    index + 1
+                );
+                histogram.render(&mut out, "http_request_duration_seconds", &labels);
+            }
+        }
+    }
+
+    let status = pool.status();
+    out.push_str("# HELP db_pool_max_size Maximum number of Postgres connections.\n");
+    out.push_str("# TYPE db_pool_max_size gauge\n");
+    let _ = writeln!(out, "db_pool_max_size {}", status.max_size);
+    out.push_str("# HELP db_pool_size Current number of Postgres connections.\n");
+    out.push_str("# TYPE db_pool_size gauge\n");
+    let _ = writeln!(out, "db_pool_size {}", status.size);
+    out.push_str("# HELP db_pool_available Idle Postgres connections.\n");
+    out.push_str("# TYPE db_pool_available gauge\n");
+    let _ = writeln!(out, "db_pool_available {}", status.available);
+    out.push_str("# HELP db_pool_waiting Requests waiting for a Postgres connection.\n");
+    out.push_str("# TYPE db_pool_waiting gauge\n");
+    let _ = writeln!(out, "db_pool_waiting {}", status.waiting);
+
+    if let Some(replica) = replica {
+        out.push_str("# HELP db_replica_healthy Whether reads are routed to the replica.\n");
+        out.push_str("# TYPE db_replica_healthy gauge\n");
+        let _ = writeln!(
+            out,
+            "db_replica_healthy {}",
+            db_pool::replica_healthy() as u8
+        );
+        out.push_str("# HELP db_replica_lag_seconds Replication lag of the read replica.\n");
+        out.push_str("# TYPE db_replica_lag_seconds gauge\n");
+        let _ = writeln!(
+            out,
+            "db_replica_lag_seconds {}",
+            db_pool::replica_lag().as_secs_f64()
+        );
+        out.push_str("# HELP db_replica_pool_size Current number of replica connections.\n");
+        out.push_str("# TYPE db_replica_pool_size gauge\n");
+        let _ = writeln!(out, "db_replica_pool_size {}", replica.status().size);
+    }
+
+    out.push_str("# HELP feeds_ingested_total Feed rows written through the API or import.\n");
+    out.push_str("# TYPE feeds_ingested_total counter\n");
+    let _ = writeln!(out, "feeds_ingested_total {}", FEEDS_INGESTED.load(Relaxed));
+
+    out.push_str("# HELP anomalies_detected_total Feed readings flagged by anomaly detectors.\n");
+    out.push_str("# TYPE anomalies_detected_total counter\n");
+    let _ = writeln!(
+        out,
+        "anomalies_detected_total {}",
+        ANOMALIES_DETECTED.load(Relaxed)
+    );
+
+    out.push_str("# HELP bcrypt_duration_seconds Time spent hashing and verifying passwords.\n");
+    out.push_str("# TYPE bcrypt_duration_seconds histogram\n");
+    BCRYPT.render(&mut out, "bcrypt_duration_seconds", "");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_matches_literal_and_placeholder_segments() {
+        assert!(path_matches("/users/", "/users/"));
+        assert!(path_matches("/users/", "/users"));
+        assert!(path_matches("/users/{id}/", "/users/42/"));
+        assert!(path_matches("/nodes/{id}/feeds/", "/nodes/7/feeds"));
+        assert!(path_matches("/activate/{token}/", "/activate/a1b2-c3/"));
+    }
+
+    #[test]
+    fn path_matches_rejects_mismatches() {
+        assert!(!path_matches("/users/{id}/", "/users/abc/"));
+        assert!(!path_matches("/users/{id}/", "/users//"));
+        assert!(!path_matches("/users/{id}/", "/users/"));
+        assert!(!path_matches("/users/", "/users/42/"));
+        assert!(!path_matches("/users/{id}/", "/users/42/restore/"));
+        assert!(!path_matches("/activate/{token}/", "/activate/"));
+    }
+
+    #[test]
+    fn route_prefers_literal_routes_and_falls_back_to_other() {
+        assert_eq!(
+            route_name(route(&Method::GET, "/users/deleted/")),
+            "/users/deleted/"
+        );
+        assert_eq!(route_name(route(&Method::GET, "/users/5/")), "/users/{id}/");
+        assert_eq!(route_name(route(&Method::PATCH, "/users/5/")), "other");
+        assert_eq!(route(&Method::GET, "/nope/"), OTHER_ROUTE);
+    }
+}
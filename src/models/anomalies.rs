@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use sonic_rs::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorMethod {
+    Zscore,
+    Ewma,
+    Flatline,
+}
+
+impl DetectorMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectorMethod::Zscore => "zscore",
+            DetectorMethod::Ewma => "ewma",
+            DetectorMethod::Flatline => "flatline",
+        }
+    }
+
+    pub fn parse(method: &str) -> Option<Self> {
+        match method {
+            "zscore" => Some(DetectorMethod::Zscore),
+            "ewma" => Some(DetectorMethod::Ewma),
+            "flatline" => Some(DetectorMethod::Flatline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Detector {
+    pub sensor: i32,
+    pub method: DetectorMethod,
+    pub window: Option<i32>,
+    pub threshold: Option<f64>,
+    pub alpha: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Anomaly {
+    pub time: NaiveDateTime,
+    pub sensor: i32,
+    pub method: DetectorMethod,
+    pub value: f64,
+    pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnomalyQuery {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
@@ -0,0 +1,38 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use chrono::NaiveDateTime;
+use sonic_rs::{Deserialize, Serialize, Value};
+
+pub struct AuditEntry<'a> {
+    pub user_id: Option<i32>,
+    pub ip: Option<IpAddr>,
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<i32>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditLog {
+    pub id: i64,
+    pub time: NaiveDateTime,
+    pub user_id: Option<i32>,
+    pub ip: Option<Cow<'static, str>>,
+    pub action: Cow<'static, str>,
+    pub target_type: Cow<'static, str>,
+    pub target_id: Option<i32>,
+    pub diff: Value,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AuditLogQuery {
+    pub user_id: Option<i32>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
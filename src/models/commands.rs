@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+use chrono::NaiveDateTime;
+use sonic_rs::{Deserialize, Serialize, Value};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandStatus {
+    Queued,
+    Delivered,
+    Acked,
+    Failed,
+    Expired,
+}
+
+impl CommandStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandStatus::Queued => "queued",
+            CommandStatus::Delivered => "delivered",
+            CommandStatus::Acked => "acked",
+            CommandStatus::Failed => "failed",
+            CommandStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "queued" => Some(CommandStatus::Queued),
+            "delivered" => Some(CommandStatus::Delivered),
+            "acked" => Some(CommandStatus::Acked),
+            "failed" => Some(CommandStatus::Failed),
+            "expired" => Some(CommandStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Command {
+    pub id: i32,
+    pub node_id: i32,
+    pub user_id: Option<i32>,
+    pub command: String,
+    pub payload: Option<Value>,
+    pub status: CommandStatus,
+    pub result: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub acked_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommandPayload {
+    pub command: Cow<'static, str>,
+    pub payload: Option<Value>,
+    pub ttl_secs: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommandAck {
+    pub node_id: i32,
+    pub id: i32,
+    pub status: CommandStatus,
+    pub result: Option<Cow<'static, str>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CommandListQuery {
+    pub status: Option<CommandStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommandPollQuery {
+    pub node_id: i32,
+    pub wait: Option<u64>,
+}
@@ -0,0 +1,9 @@
+use chrono::NaiveDateTime;
+use sonic_rs::Serialize;
+
+#[derive(Serialize)]
+pub struct Deleted<T> {
+    #[serde(flatten)]
+    pub item: T,
+    pub deleted_at: NaiveDateTime,
+}
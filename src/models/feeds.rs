@@ -1,6 +1,10 @@
+use std::borrow::Cow;
+
 use chrono::NaiveDateTime;
 use sonic_rs::{Deserialize, Serialize};
 
+use super::nodes::Coordinates;
+
 #[derive(Serialize, Deserialize)]
 pub struct Feed {
     pub node_id: i32,
@@ -12,4 +16,174 @@ pub struct Feed {
 pub struct FeedPayload {
     pub node_id: i32,
     pub value: Vec<f64>,
+    pub position: Option<Coordinates>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FeedExportQuery {
+    pub format: ExportFormat,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Ndjson,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FeedIngestQuery {
+    pub commands: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FeedImportQuery {
+    pub format: Option<ImportFormat>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RejectedLine {
+    pub line: usize,
+    pub reason: Cow<'static, str>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ImportReport {
+    pub imported: u64,
+    pub rejected: usize,
+    pub rejected_lines: Vec<RejectedLine>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Raw,
+    Hourly,
+    Daily,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct NodeFeedsQuery {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct NodeTrackQuery {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CompletenessQuery {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub interval: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Gap {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub duration_secs: f64,
+    pub missing: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CompletenessReport {
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+    pub interval_secs: i32,
+    pub readings: i64,
+    pub expected: i64,
+    pub missing: i64,
+    pub gaps: i64,
+    pub uptime_percent: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FeedRollup {
+    pub time: NaiveDateTime,
+    pub min: Vec<Option<f64>>,
+    pub max: Vec<Option<f64>>,
+    pub avg: Vec<Option<f64>>,
+    pub count: Vec<i64>,
+}
+
+impl FeedRollup {
+    pub fn new(time: NaiveDateTime, channels: usize, rows: &[(i32, f64, f64, f64, i64)]) -> Self {
+        let width = rows
+            .iter()
+            .filter_map(|row| usize::try_from(row.0).ok())
+            .map(|sensor| sensor + 1)
+            .fold(channels, usize::max);
+        let mut rollup = FeedRollup {
+            time,
+            min: vec![None; width],
+            max: vec![None; width],
+            avg: vec![None; width],
+            count: vec![0; width],
+        };
+        for &(sensor, min, max, avg, count) in rows {
+            let Ok(sensor) = usize::try_from(sensor) else {
+                continue;
+            };
+            rollup.min[sensor] = Some(min);
+            rollup.max[sensor] = Some(max);
+            rollup.avg[sensor] = Some(avg);
+            rollup.count[sensor] = count;
+        }
+        rollup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn rollup_keeps_sensors_in_their_slots() {
+        let rollup = FeedRollup::new(
+            time(),
+            3,
+            &[
+                (0, 1.0, 2.0, 1.5, 4),
+                (1, 3.0, 4.0, 3.5, 4),
+                (2, 5.0, 6.0, 5.5, 4),
+            ],
+        );
+        assert_eq!(rollup.min, [Some(1.0), Some(3.0), Some(5.0)]);
+        assert_eq!(rollup.count, [4, 4, 4]);
+    }
+
+    #[test]
+    fn rollup_pads_sparse_buckets_with_null() {
+        let rollup = FeedRollup::new(time(), 4, &[(0, 1.0, 2.0, 1.5, 4), (2, 5.0, 6.0, 5.5, 3)]);
+        assert_eq!(rollup.min, [Some(1.0), None, Some(5.0), None]);
+        assert_eq!(rollup.max, [Some(2.0), None, Some(6.0), None]);
+        assert_eq!(rollup.avg, [Some(1.5), None, Some(5.5), None]);
+        assert_eq!(rollup.count, [4, 0, 3, 0]);
+    }
+
+    #[test]
+    fn rollup_widens_for_sensors_beyond_the_channel_count() {
+        let rollup = FeedRollup::new(time(), 1, &[(2, 5.0, 6.0, 5.5, 3)]);
+        assert_eq!(rollup.avg, [None, None, Some(5.5)]);
+        assert_eq!(rollup.count, [0, 0, 3]);
+    }
 }
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use chrono::NaiveDateTime;
+use sonic_rs::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Firmware {
+    pub id: i32,
+    pub hardware_id: i32,
+    pub version: String,
+    pub checksum: String,
+    pub size: i64,
+    pub release_notes: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareUploadQuery {
+    pub version: Cow<'static, str>,
+    pub release_notes: Option<Cow<'static, str>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareRollout {
+    #[serde(default)]
+    pub node_ids: Vec<i32>,
+    #[serde(default)]
+    pub group_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RolloutReport {
+    pub firmware_id: i32,
+    pub assigned: Vec<i32>,
+    pub skipped: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareCheckQuery {
+    pub node_id: i32,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareCheck {
+    pub update_available: bool,
+    pub current_version: Option<String>,
+    pub firmware: Option<Firmware>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareDownloadQuery {
+    pub node_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FirmwareReportQuery {
+    pub hardware_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FirmwareReportEntry {
+    pub hardware_id: i32,
+    pub running_version: Option<String>,
+    pub target_version: Option<String>,
+    pub nodes: i64,
+    pub node_ids: Vec<i32>,
+}
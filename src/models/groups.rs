@@ -0,0 +1,58 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use sonic_rs::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct NodeGroup {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub node_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NodeGroupPayload {
+    pub name: Cow<'static, str>,
+    pub description: Option<Cow<'static, str>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GroupMembers {
+    pub node_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagAssignment {
+    pub node_ids: Vec<i32>,
+    #[serde(default)]
+    pub set: BTreeMap<String, String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NodeTags {
+    pub node_id: i32,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BulkReport {
+    pub updated: Vec<i32>,
+    pub skipped: Vec<i32>,
+}
+
+impl BulkReport {
+    pub fn new(requested: Vec<i32>, mut updated: Vec<i32>) -> Self {
+        updated.sort_unstable();
+        updated.dedup();
+        let mut skipped: Vec<i32> = requested
+            .into_iter()
+            .filter(|id| updated.binary_search(id).is_err())
+            .collect();
+        skipped.sort_unstable();
+        skipped.dedup();
+        BulkReport { updated, skipped }
+    }
+}
@@ -16,3 +16,28 @@ pub struct HardwarePayload {
     pub type_: Cow<'static, str>,
     pub description: Cow<'static, str>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum HardwareSort {
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+    #[serde(rename = "type_")]
+    TypeAsc,
+    #[serde(rename = "-type_")]
+    TypeDesc,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct HardwareListQuery {
+    pub type_: Option<String>,
+    pub q: Option<String>,
+    pub sort: Option<HardwareSort>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
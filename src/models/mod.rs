@@ -1,6 +1,13 @@
+pub mod anomalies;
+pub mod audit;
+pub mod commands;
+pub mod deleted;
 pub mod feeds;
+pub mod firmwares;
+pub mod groups;
 pub mod hardwares;
 pub mod jwt;
 pub mod nodes;
 pub mod response;
+pub mod shadows;
 pub mod users;
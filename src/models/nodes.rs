@@ -1,8 +1,40 @@
 use std::borrow::Cow;
 
+use chrono::NaiveDateTime;
+use serde::{de, Deserializer};
 use sonic_rs::{Deserialize, Serialize};
 
-use super::feeds::Feed;
+use crate::constant::config;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeStatus {
+    Online,
+    Offline,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+impl Coordinates {
+    pub fn is_valid(&self) -> bool {
+        valid_point(self.latitude, self.longitude) && self.altitude.is_none_or(f64::is_finite)
+    }
+}
+
+fn valid_point(latitude: f64, longitude: f64) -> bool {
+    (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VirtualSensor {
+    pub name: Cow<'static, str>,
+    pub expression: Cow<'static, str>,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Node {
@@ -14,6 +46,14 @@ pub struct Node {
     pub hardware_sensor_ids: Vec<i32>,
     pub hardware_sensor_names: Vec<Cow<'static, str>>,
     pub ispublic: bool,
+    pub feeds_retention_days: Option<i32>,
+    pub heartbeat_interval_secs: Option<i32>,
+    pub last_seen_at: Option<NaiveDateTime>,
+    pub last_value: Option<Vec<f64>>,
+    pub status: NodeStatus,
+    pub coordinates: Option<Coordinates>,
+    pub address: Option<Cow<'static, str>>,
+    pub virtual_sensors: Vec<VirtualSensor>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -24,10 +64,118 @@ pub struct NodePayload {
     pub hardware_sensor_ids: Vec<i32>,
     pub hardware_sensor_names: Vec<Cow<'static, str>>,
     pub ispublic: bool,
+    pub feeds_retention_days: Option<i32>,
+    pub heartbeat_interval_secs: Option<i32>,
+    pub coordinates: Option<Coordinates>,
+    pub address: Option<Cow<'static, str>>,
+    #[serde(default)]
+    pub virtual_sensors: Vec<VirtualSensor>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct NodeWithFeed {
-    pub node: Node,
-    pub feeds: Vec<Feed>,
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum NodeSort {
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+    #[serde(rename = "last_seen_at")]
+    LastSeenAtAsc,
+    #[serde(rename = "-last_seen_at")]
+    LastSeenAtDesc,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct NodeListQuery {
+    pub owner: Option<i32>,
+    pub hardware_id: Option<i32>,
+    pub ispublic: Option<bool>,
+    pub q: Option<String>,
+    pub has_sensor: Option<i32>,
+    pub group: Option<i32>,
+    pub tag: Option<String>,
+    pub sort: Option<NodeSort>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub bbox: Option<[f64; 4]>,
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub near: Option<[f64; 2]>,
+    pub radius: Option<f64>,
+}
+
+impl NodeListQuery {
+    pub fn is_valid(&self) -> bool {
+        let bbox = self
+            .bbox
+            .is_none_or(|[min_lon, min_lat, max_lon, max_lat]| {
+                valid_point(min_lat, min_lon) && valid_point(max_lat, max_lon) && min_lat <= max_lat
+            });
+        let near = self.near.is_none_or(|[lat, lon]| valid_point(lat, lon));
+        let radius = self
+            .radius
+            .is_none_or(|km| km > 0.0 && km <= config::NEAR_MAX_RADIUS_KM);
+        let tag = self
+            .tag
+            .as_deref()
+            .is_none_or(|tag| !tag.is_empty() && !tag.starts_with(':'));
+        bbox && near && radius && tag
+    }
+
+    pub fn tag_filter(&self) -> (Option<String>, Option<[String; 2]>) {
+        match self.tag.as_deref().map(|tag| tag.split_once(':')) {
+            Some(Some((key, value))) => (None, Some([key.to_string(), value.to_string()])),
+            Some(None) => (self.tag.clone(), None),
+            None => (None, None),
+        }
+    }
+}
+
+fn comma_separated<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<Option<[f64; N]>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(de::Error::custom)?;
+    values
+        .try_into()
+        .map(Some)
+        .map_err(|_| de::Error::custom(format!("expected {} comma-separated numbers", N)))
+}
+
+#[derive(Serialize)]
+pub struct GeoJsonPoint {
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub coordinates: Vec<f64>,
+}
+
+#[derive(Serialize)]
+pub struct GeoJsonFeature {
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub id: i32,
+    pub geometry: GeoJsonPoint,
+    pub properties: Node,
+}
+
+#[derive(Serialize)]
+pub struct TrackPoint {
+    pub time: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct GeoJsonTrackFeature {
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub geometry: GeoJsonPoint,
+    pub properties: TrackPoint,
 }
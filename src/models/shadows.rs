@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use sonic_rs::{Deserialize, Serialize, Value};
+
+#[derive(Serialize, Deserialize)]
+pub struct Shadow {
+    pub node_id: i32,
+    pub desired: Value,
+    pub reported: Value,
+    pub delta: Value,
+    pub desired_version: i32,
+    pub reported_version: i32,
+    pub desired_updated_at: Option<NaiveDateTime>,
+    pub reported_updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShadowPayload {
+    pub state: Value,
+    pub version: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShadowReport {
+    pub node_id: i32,
+    pub state: Value,
+    pub version: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShadowQuery {
+    pub node_id: i32,
+}
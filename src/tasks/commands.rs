@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, Utc};
+use deadpool_postgres::Pool;
+use ntex::time::{sleep, Seconds};
+use tokio::sync::{futures::Notified, Notify};
+use tokio_postgres::types::Type;
+
+use crate::constant::{config, query};
+
+static WAITERS: Mutex<Option<HashMap<i32, Arc<Notify>>>> = Mutex::new(None);
+
+pub struct Subscription {
+    node_id: i32,
+    notify: Arc<Notify>,
+}
+
+impl Subscription {
+    pub fn notified(&self) -> Notified<'_> {
+        self.notify.notified()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut waiters = WAITERS.lock().unwrap();
+        if let Some(waiters) = waiters.as_mut() {
+            if Arc::strong_count(&self.notify) <= 2 {
+                waiters.remove(&self.node_id);
+            }
+        }
+    }
+}
+
+pub fn subscribe(node_id: i32) -> Subscription {
+    let mut waiters = WAITERS.lock().unwrap();
+    let notify = waiters
+        .get_or_insert_with(HashMap::new)
+        .entry(node_id)
+        .or_insert_with(|| Arc::new(Notify::new()))
+        .clone();
+    Subscription { node_id, notify }
+}
+
+pub fn notify(node_id: i32) {
+    let waiters = WAITERS.lock().unwrap();
+    if let Some(notify) = waiters.as_ref().and_then(|waiters| waiters.get(&node_id)) {
+        notify.notify_waiters();
+    }
+}
+
+pub async fn run(pool: Pool) {
+    loop {
+        if let Err(e) = expire(&pool).await {
+            tracing::error!(error = %e, "command expiry failed");
+        }
+        sleep(Seconds(config::COMMAND_EXPIRY_INTERVAL_SECS)).await;
+    }
+}
+
+async fn expire(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let now = Utc::now().naive_utc();
+    let stmt = client
+        .prepare_typed_cached(query::NODE_COMMANDS_EXPIRE, &[Type::TIMESTAMP])
+        .await?;
+    let expired = client.execute(&stmt, &[&now]).await?;
+    if expired > 0 {
+        tracing::info!(expired, "node commands expired");
+    }
+    let cutoff = now - Duration::days(config::COMMAND_HISTORY_RETENTION_DAYS);
+    let stmt = client
+        .prepare_typed_cached(query::NODE_COMMANDS_RETENTION_DELETE, &[Type::TIMESTAMP])
+        .await?;
+    client.execute(&stmt, &[&cutoff]).await?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use lettre::{transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
+
+use crate::constant::config;
+
+enum Job {
+    Send(Box<Message>),
+    Drain(Sender<()>),
+}
+
+static QUEUE: OnceLock<Sender<Job>> = OnceLock::new();
+
+pub fn start() {
+    let (tx, rx) = mpsc::channel();
+    if QUEUE.set(tx).is_ok() {
+        thread::Builder::new()
+            .name("mail".to_string())
+            .spawn(move || run(rx))
+            .expect("Failed to start mail worker");
+    }
+}
+
+fn run(rx: Receiver<Job>) {
+    let creds = Credentials::new(
+        config::EMAIL_USERNAME.to_string(),
+        config::EMAIL_PASSWORD.to_string(),
+    );
+    let mailer = SmtpTransport::relay(config::EMAIL_RELAY)
+        .unwrap()
+        .credentials(creds)
+        .timeout(Some(Duration::from_secs(config::MAIL_TIMEOUT_SECS)))
+        .build();
+
+    for job in rx {
+        match job {
+            Job::Send(email) => {
+                if let Err(e) = mailer.send(&email) {
+                    tracing::error!(error = %e, "failed to send email");
+                }
+            }
+            Job::Drain(done) => {
+                let _ = done.send(());
+                return;
+            }
+        }
+    }
+}
+
+pub fn send(email: Message) {
+    match QUEUE.get() {
+        Some(queue) if queue.send(Job::Send(Box::new(email))).is_ok() => (),
+        _ => tracing::error!("mail queue is not running, dropping email"),
+    }
+}
+
+pub fn drain(timeout: Duration) {
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    let (done_tx, done_rx) = mpsc::channel();
+    if queue.send(Job::Drain(done_tx)).is_ok() && done_rx.recv_timeout(timeout).is_err() {
+        tracing::warn!("mail queue not drained before shutdown deadline");
+    }
+}
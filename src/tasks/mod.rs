@@ -0,0 +1,8 @@
+pub mod commands;
+pub mod mail;
+pub mod partitions;
+pub mod purge;
+pub mod replica;
+pub mod rollup;
+pub mod shutdown;
+pub mod tls;
@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc};
+use deadpool_postgres::{Object, Pool};
+use ntex::time::{sleep, Seconds};
+use tokio_postgres::types::Type;
+
+use crate::constant::{config, query};
+
+pub async fn run(pool: Pool) {
+    loop {
+        if let Err(e) = maintain(&pool).await {
+            tracing::error!(error = %e, "partition maintenance failed");
+        }
+        sleep(Seconds(config::PARTITION_INTERVAL_SECS)).await;
+    }
+}
+
+fn partition_name(month: NaiveDate) -> String {
+    format!("feeds_p{}", month.format("%Y%m"))
+}
+
+fn partition_month(name: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}01", name.strip_prefix("feeds_p")?), "%Y%m%d").ok()
+}
+
+fn next_month(month: NaiveDate) -> NaiveDate {
+    month + Months::new(1)
+}
+
+async fn maintain(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = pool.get().await?;
+    let now = Utc::now().naive_utc();
+
+    let mut existing: BTreeSet<NaiveDate> = client
+        .query(query::FEEDS_PARTITIONS, &[])
+        .await?
+        .iter()
+        .filter_map(|row| partition_month(row.get(0)))
+        .collect();
+
+    drop_expired_partitions(&client, &mut existing, now).await?;
+    apply_node_retention(&client, now).await?;
+    create_partitions(&mut client, &existing, now).await
+}
+
+async fn drop_expired_partitions(
+    client: &Object,
+    existing: &mut BTreeSet<NaiveDate>,
+    now: NaiveDateTime,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(global_days) = config::FEEDS_RETENTION_DAYS else {
+        return Ok(());
+    };
+    let node_days: Option<i32> = client
+        .query_one(query::NODES_MAX_RETENTION_DAYS, &[])
+        .await?
+        .get(0);
+    let cutoff = now - Duration::days(global_days.max(node_days.unwrap_or(0)).into());
+    let expired: Vec<NaiveDate> = existing
+        .iter()
+        .copied()
+        .filter(|month| next_month(*month).and_hms_opt(0, 0, 0).unwrap() <= cutoff)
+        .collect();
+    for month in expired {
+        let name = partition_name(month);
+        client
+            .batch_execute(&format!(
+                "ALTER TABLE feeds DETACH PARTITION {0}; DROP TABLE {0}",
+                name
+            ))
+            .await?;
+        existing.remove(&month);
+        tracing::info!(partition = name, "feeds partition dropped");
+    }
+    Ok(())
+}
+
+async fn create_partitions(
+    client: &mut Object,
+    existing: &BTreeSet<NaiveDate>,
+    now: NaiveDateTime,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let current = now.date().with_day(1).unwrap();
+    let mut wanted: BTreeSet<NaiveDate> = (0..=config::FEEDS_PARTITIONS_AHEAD_MONTHS)
+        .map(|ahead| current + Months::new(ahead))
+        .collect();
+    for row in client.query(query::FEEDS_DEFAULT_MONTHS, &[]).await? {
+        wanted.insert(row.get::<_, NaiveDateTime>(0).date());
+    }
+
+    for month in wanted.difference(existing) {
+        let name = partition_name(*month);
+        let (from, to) = (
+            month.and_hms_opt(0, 0, 0).unwrap(),
+            next_month(*month).and_hms_opt(0, 0, 0).unwrap(),
+        );
+        let tx = client.transaction().await?;
+        tx.batch_execute(&format!(
+            "CREATE TABLE {} (LIKE feeds INCLUDING DEFAULTS INCLUDING CONSTRAINTS)",
+            name
+        ))
+        .await?;
+        let moved = tx
+            .execute(
+                &format!(
+                    "INSERT INTO {} SELECT * FROM feeds_default WHERE time >= $1 AND time < $2",
+                    name
+                ),
+                &[&from, &to],
+            )
+            .await?;
+        tx.execute(query::FEEDS_DEFAULT_DELETE_RANGE, &[&from, &to])
+            .await?;
+        tx.batch_execute(&format!(
+            "ALTER TABLE feeds ATTACH PARTITION {} FOR VALUES FROM ('{}') TO ('{}')",
+            name, from, to
+        ))
+        .await?;
+        tx.commit().await?;
+        tracing::info!(partition = name, moved, "feeds partition created");
+    }
+    Ok(())
+}
+
+async fn apply_node_retention(
+    client: &Object,
+    now: NaiveDateTime,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let stmt = client
+        .prepare_typed_cached(
+            query::FEEDS_RETENTION_DELETE,
+            &[Type::TIMESTAMP, Type::INT4],
+        )
+        .await?;
+    let deleted = client
+        .execute(&stmt, &[&now, &config::FEEDS_RETENTION_DAYS])
+        .await?;
+    if deleted > 0 {
+        tracing::info!(deleted, "expired feeds deleted");
+    }
+
+    let stmt = client
+        .prepare_typed_cached(
+            query::FEED_ANOMALIES_RETENTION_DELETE,
+            &[Type::TIMESTAMP, Type::INT4],
+        )
+        .await?;
+    let deleted = client
+        .execute(&stmt, &[&now, &config::FEEDS_RETENTION_DAYS])
+        .await?;
+    if deleted > 0 {
+        tracing::info!(deleted, "expired feed anomalies deleted");
+    }
+
+    let stmt = client
+        .prepare_typed_cached(
+            query::NODE_LOCATIONS_RETENTION_DELETE,
+            &[Type::TIMESTAMP, Type::INT4],
+        )
+        .await?;
+    let deleted = client
+        .execute(&stmt, &[&now, &config::FEEDS_RETENTION_DAYS])
+        .await?;
+    if deleted > 0 {
+        tracing::info!(deleted, "expired node locations deleted");
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use deadpool_postgres::Pool;
+use ntex::time::{sleep, Seconds};
+use tokio_postgres::types::Type;
+
+use crate::constant::{config, query};
+use crate::utils::{anomaly, auth};
+
+pub async fn run(pool: Pool) {
+    loop {
+        if let Err(e) = purge(&pool).await {
+            tracing::error!(error = %e, "purge failed");
+        }
+        sleep(Seconds(config::PURGE_INTERVAL_SECS)).await;
+    }
+}
+
+async fn purge(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let cutoff = (Utc::now() - Duration::days(config::SOFT_DELETE_RETENTION_DAYS)).naive_utc();
+    let stmt = client
+        .prepare_typed_cached(query::NODES_PURGE, &[Type::TIMESTAMP])
+        .await?;
+    for row in client.query(&stmt, &[&cutoff]).await? {
+        anomaly::evict(row.get(0));
+    }
+    for purge_query in [query::HARDWARES_PURGE, query::USERS_PURGE] {
+        let stmt = client
+            .prepare_typed_cached(purge_query, &[Type::TIMESTAMP])
+            .await?;
+        client.execute(&stmt, &[&cutoff]).await?;
+    }
+    Ok(())
+}
+
+pub async fn watch_deleted_users(pool: Pool) {
+    loop {
+        if let Err(e) = refresh_deleted_users(&pool).await {
+            tracing::error!(error = %e, "deleted users refresh failed");
+        }
+        sleep(Seconds(config::DELETED_USERS_REFRESH_SECS)).await;
+    }
+}
+
+async fn refresh_deleted_users(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let stmt = client
+        .prepare_typed_cached(query::USERS_SELECT_DELETED_IDS, &[Type::INT4_ARRAY])
+        .await?;
+    let version = auth::deleted_users_version();
+    let known = auth::deleted_user_ids();
+    let ids: HashSet<i32> = client
+        .query(&stmt, &[&known])
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    auth::set_deleted_users(ids, version);
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use deadpool_postgres::Pool;
+use ntex::time::{sleep, Seconds};
+
+use crate::{
+    constant::{config, query},
+    database::pool,
+};
+
+pub async fn run(replica: Pool) {
+    loop {
+        match lag(&replica).await {
+            Ok(lag) => {
+                let healthy = lag <= Duration::from_millis(config::DB_REPLICA_MAX_LAG_MS);
+                if healthy != pool::replica_healthy() {
+                    tracing::info!(
+                        healthy,
+                        lag_ms = lag.as_millis() as u64,
+                        "replica status changed"
+                    );
+                }
+                pool::set_replica_status(healthy, lag);
+            }
+            Err(e) => {
+                if pool::replica_healthy() {
+                    tracing::warn!(error = %e, "replica unavailable");
+                }
+                pool::set_replica_status(false, Duration::ZERO);
+            }
+        }
+        sleep(Seconds(config::DB_REPLICA_CHECK_INTERVAL_SECS)).await;
+    }
+}
+
+async fn lag(replica: &Pool) -> Result<Duration, Box<dyn std::error::Error>> {
+    let client = replica.get().await?;
+    let stmt = client.prepare_cached(query::REPLICA_LAG_MS).await?;
+    let lag_ms: f64 = client.query_one(&stmt, &[]).await?.get(0);
+    Ok(Duration::from_millis(lag_ms.max(0.0) as u64))
+}
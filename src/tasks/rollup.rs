@@ -0,0 +1,149 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use chrono::{DurationRound, NaiveDateTime, TimeDelta};
+use deadpool_postgres::{Object, Pool};
+use ntex::time::{sleep, Seconds};
+use tokio_postgres::types::Type;
+
+use crate::constant::{config, query};
+
+const SHARDS: usize = 16;
+
+type Shard = Mutex<Option<HashSet<(i32, NaiveDateTime)>>>;
+
+static DIRTY: [Shard; SHARDS] = [const { Mutex::new(None) }; SHARDS];
+
+pub fn hour(time: NaiveDateTime) -> NaiveDateTime {
+    time.duration_trunc(TimeDelta::hours(1)).unwrap_or(time)
+}
+
+pub fn mark(node_id: i32, time: NaiveDateTime) {
+    mark_all([(node_id, time)]);
+}
+
+fn shard(node_id: i32) -> &'static Shard {
+    &DIRTY[node_id.unsigned_abs() as usize % SHARDS]
+}
+
+pub fn mark_all(feeds: impl IntoIterator<Item = (i32, NaiveDateTime)>) {
+    let mut feeds = feeds.into_iter().peekable();
+    while let Some(&(node_id, _)) = feeds.peek() {
+        let mut dirty = shard(node_id).lock().unwrap();
+        let dirty = dirty.get_or_insert_with(HashSet::new);
+        while let Some((node_id, time)) = feeds.next_if(|(next, _)| *next == node_id) {
+            dirty.insert((node_id, hour(time)));
+        }
+    }
+}
+
+pub async fn run(pool: Pool) {
+    if let Err(e) = backfill(&pool).await {
+        tracing::error!(error = %e, "rollup backfill failed");
+    }
+    loop {
+        if let Err(e) = flush(&pool).await {
+            tracing::error!(error = %e, "rollup failed");
+        }
+        sleep(Seconds(config::ROLLUP_INTERVAL_SECS)).await;
+    }
+}
+
+async fn backfill(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client.query(query::ROLLUP_WATERMARKS, &[]).await?;
+    let (rolled_up, never_rolled_up): (Vec<_>, Vec<_>) = rows
+        .iter()
+        .map(|row| (row.get::<_, i32>(0), row.get::<_, Option<NaiveDateTime>>(1)))
+        .partition(|(_, since)| since.is_some());
+
+    let stmt = client
+        .prepare_typed_cached(
+            query::ROLLUP_BACKFILL,
+            &[Type::INT4_ARRAY, Type::TIMESTAMP_ARRAY, Type::TIMESTAMP],
+        )
+        .await?;
+    for nodes in [rolled_up, never_rolled_up] {
+        if nodes.is_empty() {
+            continue;
+        }
+        let oldest = nodes.iter().filter_map(|(_, since)| *since).min();
+        let (node_ids, since): (Vec<i32>, Vec<Option<NaiveDateTime>>) = nodes.into_iter().unzip();
+        let rows = client.query(&stmt, &[&node_ids, &since, &oldest]).await?;
+        mark_all(rows.iter().map(|row| (row.get(0), row.get(1))));
+    }
+    Ok(())
+}
+
+pub async fn flush(pool: &Pool) -> Result<(), Box<dyn std::error::Error>> {
+    let hours: Vec<(i32, NaiveDateTime)> = DIRTY
+        .iter()
+        .filter_map(|shard| shard.lock().unwrap().take())
+        .flatten()
+        .collect();
+    if hours.is_empty() {
+        return Ok(());
+    }
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            mark_all(hours);
+            return Err(e.into());
+        }
+    };
+    rebuild(&client, hours).await.map_err(Into::into)
+}
+
+pub async fn rebuild(
+    client: &Object,
+    mut hours: Vec<(i32, NaiveDateTime)>,
+) -> Result<(), tokio_postgres::Error> {
+    hours.sort_unstable();
+
+    let mut done = 0;
+    let result = rollup(client, &hours, &mut done).await;
+    if result.is_err() {
+        mark_all(hours.drain(done..));
+    }
+    if done > 0 {
+        tracing::debug!(hours = done, "feeds rolled up");
+    }
+    result
+}
+
+async fn rollup(
+    client: &Object,
+    hours: &[(i32, NaiveDateTime)],
+    done: &mut usize,
+) -> Result<(), tokio_postgres::Error> {
+    let hourly = client
+        .prepare_typed_cached(
+            query::ROLLUP_HOURLY_UPSERT,
+            &[Type::INT4_ARRAY, Type::TIMESTAMP_ARRAY],
+        )
+        .await?;
+    let daily = client
+        .prepare_typed_cached(
+            query::ROLLUP_DAILY_UPSERT,
+            &[Type::INT4_ARRAY, Type::TIMESTAMP_ARRAY],
+        )
+        .await?;
+
+    for batch in hours.chunks(config::ROLLUP_BATCH_SIZE) {
+        let (node_ids, buckets): (Vec<i32>, Vec<NaiveDateTime>) = batch.iter().copied().unzip();
+        client.execute(&hourly, &[&node_ids, &buckets]).await?;
+
+        let days: HashSet<(i32, NaiveDateTime)> = batch
+            .iter()
+            .map(|(node_id, time)| {
+                (
+                    *node_id,
+                    time.duration_trunc(TimeDelta::days(1)).unwrap_or(*time),
+                )
+            })
+            .collect();
+        let (node_ids, buckets): (Vec<i32>, Vec<NaiveDateTime>) = days.into_iter().unzip();
+        client.execute(&daily, &[&node_ids, &buckets]).await?;
+        *done += batch.len();
+    }
+    Ok(())
+}
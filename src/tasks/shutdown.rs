@@ -0,0 +1,17 @@
+use ntex::server::Server;
+use tokio::signal::unix::{signal, SignalKind};
+
+pub async fn on_signal(server: Server) {
+    let (Ok(mut term), Ok(mut int)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        tracing::error!("failed to install signal handlers");
+        return;
+    };
+    tokio::select! {
+        _ = term.recv() => tracing::info!("SIGTERM received, draining connections"),
+        _ = int.recv() => tracing::info!("SIGINT received, draining connections"),
+    }
+    server.stop(true).await;
+}
@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use ntex::time::{sleep, Seconds};
+
+use crate::{constant::config, tls::CertStore};
+
+pub async fn run(store: Arc<CertStore>) {
+    loop {
+        sleep(Seconds(config::TLS_RELOAD_INTERVAL_SECS)).await;
+        match store.reload_if_changed() {
+            Ok(true) => tracing::info!("TLS certificate reloaded"),
+            Ok(false) => (),
+            Err(e) => tracing::error!(error = %e, "TLS certificate reload failed"),
+        }
+    }
+}
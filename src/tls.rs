@@ -0,0 +1,89 @@
+use std::{
+    fmt, fs,
+    io::{self, BufReader},
+    path::Path,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use rustls::{
+    crypto::ring::sign::any_supported_type,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+
+use crate::constant::config;
+
+pub struct CertStore {
+    key: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl fmt::Debug for CertStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertStore").finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.key.read().ok().map(|key| key.clone())
+    }
+}
+
+fn modified() -> Option<SystemTime> {
+    let cert = fs::metadata(config::TLS_CERT_PATH).and_then(|m| m.modified());
+    let key = fs::metadata(config::TLS_KEY_PATH).and_then(|m| m.modified());
+    match (cert, key) {
+        (Ok(cert), Ok(key)) => Some(cert.max(key)),
+        _ => None,
+    }
+}
+
+fn load() -> io::Result<Arc<CertifiedKey>> {
+    let mut cert_file = BufReader::new(fs::File::open(config::TLS_CERT_PATH)?);
+    let certs = rustls_pemfile::certs(&mut cert_file).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no certificates found",
+        ));
+    }
+    let mut key_file = BufReader::new(fs::File::open(config::TLS_KEY_PATH)?);
+    let key = rustls_pemfile::private_key(&mut key_file)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no private key found"))?;
+    let key = any_supported_type(&key).map_err(io::Error::other)?;
+    Ok(Arc::new(CertifiedKey::new(certs, key)))
+}
+
+pub fn enabled() -> bool {
+    Path::new(config::TLS_CERT_PATH).exists() && Path::new(config::TLS_KEY_PATH).exists()
+}
+
+impl CertStore {
+    pub fn open() -> io::Result<Arc<Self>> {
+        let modified = modified();
+        Ok(Arc::new(CertStore {
+            key: RwLock::new(load()?),
+            modified: RwLock::new(modified),
+        }))
+    }
+
+    pub fn reload_if_changed(&self) -> io::Result<bool> {
+        let modified = modified();
+        if modified.is_none() || *self.modified.read().unwrap() == modified {
+            return Ok(false);
+        }
+        let key = load()?;
+        *self.key.write().unwrap() = key;
+        *self.modified.write().unwrap() = modified;
+        Ok(true)
+    }
+
+    pub fn server_config(self: &Arc<Self>) -> ServerConfig {
+        ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone())
+    }
+}
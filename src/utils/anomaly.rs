@@ -0,0 +1,319 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    constant::config,
+    models::anomalies::{Detector, DetectorMethod},
+};
+
+type Key = (i32, DetectorMethod);
+
+enum State {
+    Zscore(VecDeque<f64>),
+    Ewma { mean: f64, var: f64, count: i32 },
+    Flatline { last: f64, run: i32 },
+}
+
+struct NodeState {
+    generation: u64,
+    detectors: Option<Vec<Detector>>,
+    states: HashMap<Key, State>,
+    alerts: HashMap<Key, Instant>,
+    used: Instant,
+}
+
+const SHARDS: usize = 16;
+
+type Shard = Mutex<Option<HashMap<i32, NodeState>>>;
+
+static NODES: [Shard; SHARDS] = [const { Mutex::new(None) }; SHARDS];
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn with_node<T>(node_id: i32, f: impl FnOnce(&mut NodeState) -> T) -> T {
+    let mut nodes = NODES[node_id.unsigned_abs() as usize % SHARDS]
+        .lock()
+        .unwrap();
+    let nodes = nodes.get_or_insert_with(HashMap::new);
+    if !nodes.contains_key(&node_id) && nodes.len() >= config::ANOMALY_MAX_CACHED_NODES / SHARDS {
+        let oldest = nodes
+            .iter()
+            .min_by_key(|(_, node)| node.used)
+            .map(|(id, _)| *id);
+        if let Some(oldest) = oldest {
+            nodes.remove(&oldest);
+        }
+    }
+    let node = nodes.entry(node_id).or_insert_with(|| NodeState {
+        generation: GENERATION.fetch_add(1, Relaxed),
+        detectors: None,
+        states: HashMap::new(),
+        alerts: HashMap::new(),
+        used: Instant::now(),
+    });
+    node.used = Instant::now();
+    f(node)
+}
+
+pub fn defaults(detector: Detector) -> Detector {
+    let (window, threshold) = match detector.method {
+        DetectorMethod::Zscore => (
+            config::ANOMALY_ZSCORE_WINDOW,
+            config::ANOMALY_ZSCORE_THRESHOLD,
+        ),
+        DetectorMethod::Ewma => (config::ANOMALY_EWMA_WARMUP, config::ANOMALY_EWMA_THRESHOLD),
+        DetectorMethod::Flatline => (
+            config::ANOMALY_FLATLINE_WINDOW,
+            config::ANOMALY_FLATLINE_TOLERANCE,
+        ),
+    };
+    Detector {
+        window: Some(detector.window.unwrap_or(window)),
+        threshold: Some(detector.threshold.unwrap_or(threshold)),
+        alpha: Some(detector.alpha.unwrap_or(config::ANOMALY_EWMA_ALPHA)),
+        ..detector
+    }
+}
+
+fn new_state(method: DetectorMethod) -> State {
+    match method {
+        DetectorMethod::Zscore => State::Zscore(VecDeque::new()),
+        DetectorMethod::Ewma => State::Ewma {
+            mean: 0.0,
+            var: 0.0,
+            count: 0,
+        },
+        DetectorMethod::Flatline => State::Flatline {
+            last: f64::NAN,
+            run: 0,
+        },
+    }
+}
+
+pub fn cached_detectors(node_id: i32) -> Result<Vec<Detector>, u64> {
+    with_node(node_id, |node| {
+        node.detectors.clone().ok_or(node.generation)
+    })
+}
+
+pub fn cache_detectors(node_id: i32, generation: u64, detectors: Vec<Detector>) {
+    with_node(node_id, |node| {
+        if node.generation == generation {
+            node.detectors = Some(detectors);
+        }
+    });
+}
+
+pub fn observe(node_id: i32, detector: &Detector, x: f64) -> Option<f64> {
+    if !x.is_finite() {
+        return None;
+    }
+    let detector = defaults(*detector);
+    let window = detector.window.unwrap_or_default().max(1) as usize;
+    let threshold = detector.threshold.unwrap_or_default();
+    let alpha = detector.alpha.unwrap_or_default();
+
+    with_node(node_id, |node| {
+        let state = node
+            .states
+            .entry((detector.sensor, detector.method))
+            .or_insert_with(|| new_state(detector.method));
+        state.observe(x, window, threshold, alpha)
+    })
+}
+
+impl State {
+    fn observe(&mut self, x: f64, window: usize, threshold: f64, alpha: f64) -> Option<f64> {
+        match self {
+            State::Zscore(values) => {
+                let mut score = None;
+                if values.len() >= window {
+                    let n = values.len() as f64;
+                    let mean = values.iter().sum::<f64>() / n;
+                    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+                    if std > 0.0 {
+                        score = Some((x - mean).abs() / std).filter(|z| *z > threshold);
+                    }
+                }
+                values.push_back(x);
+                while values.len() > window {
+                    values.pop_front();
+                }
+                score
+            }
+            State::Ewma { mean, var, count } => {
+                let mut score = None;
+                if *count == 0 {
+                    *mean = x;
+                } else {
+                    if *count as usize >= window && *var > 0.0 {
+                        score = Some((x - *mean).abs() / var.sqrt()).filter(|z| *z > threshold);
+                    }
+                    let diff = x - *mean;
+                    *mean += alpha * diff;
+                    *var = (1.0 - alpha) * (*var + alpha * diff * diff);
+                }
+                *count = count.saturating_add(1);
+                score
+            }
+            State::Flatline { last, run } => {
+                if (x - *last).abs() <= threshold {
+                    *run = run.saturating_add(1);
+                } else {
+                    *run = 1;
+                }
+                *last = x;
+                Some(*run as f64).filter(|run| *run >= window as f64)
+            }
+        }
+    }
+}
+
+pub fn reset(node_id: i32) {
+    with_node(node_id, |node| {
+        node.generation = GENERATION.fetch_add(1, Relaxed);
+        node.detectors = None;
+        node.states.clear();
+    });
+}
+
+pub fn evict(node_id: i32) {
+    if let Some(nodes) = NODES[node_id.unsigned_abs() as usize % SHARDS]
+        .lock()
+        .unwrap()
+        .as_mut()
+    {
+        nodes.remove(&node_id);
+    }
+}
+
+pub fn should_alert(node_id: i32, sensor: i32, method: DetectorMethod) -> bool {
+    let now = Instant::now();
+    let cooldown = Duration::from_secs(config::ANOMALY_ALERT_COOLDOWN_SECS);
+    with_node(node_id, |node| match node.alerts.get(&(sensor, method)) {
+        Some(time) if now.duration_since(*time) < cooldown => false,
+        _ => {
+            node.alerts.insert((sensor, method), now);
+            true
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(method: DetectorMethod, window: i32, threshold: f64) -> Detector {
+        Detector {
+            sensor: 0,
+            method,
+            window: Some(window),
+            threshold: Some(threshold),
+            alpha: Some(0.5),
+        }
+    }
+
+    fn observe_all(node_id: i32, detector: &Detector, xs: &[f64]) -> Vec<Option<f64>> {
+        xs.iter().map(|x| observe(node_id, detector, *x)).collect()
+    }
+
+    #[test]
+    fn zscore_waits_for_a_full_window() {
+        let zscore = detector(DetectorMethod::Zscore, 5, 3.0);
+        let scores = observe_all(-101, &zscore, &[10.0, 11.0, 10.0, 11.0, 1000.0]);
+        assert!(scores.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn zscore_flags_outliers_only() {
+        let zscore = detector(DetectorMethod::Zscore, 5, 3.0);
+        observe_all(-102, &zscore, &[10.0, 11.0, 10.0, 11.0, 10.0]);
+        assert_eq!(observe(-102, &zscore, 10.5), None);
+        assert!(observe(-102, &zscore, 100.0).is_some_and(|z| z > 3.0));
+
+        observe_all(-103, &zscore, &[5.0; 5]);
+        assert_eq!(observe(-103, &zscore, 50.0), None);
+    }
+
+    #[test]
+    fn ewma_warms_up_then_scores() {
+        let ewma = detector(DetectorMethod::Ewma, 3, 3.0);
+        let scores = observe_all(-111, &ewma, &[10.0, 12.0, 1000.0]);
+        assert!(scores.iter().all(Option::is_none));
+
+        observe_all(-112, &ewma, &[10.0, 12.0, 10.0]);
+        assert_eq!(observe(-112, &ewma, 10.5), None);
+        assert!(observe(-112, &ewma, 100.0).is_some_and(|z| z > 3.0));
+    }
+
+    #[test]
+    fn flatline_respects_tolerance() {
+        let flatline = detector(DetectorMethod::Flatline, 3, 0.5);
+        assert_eq!(
+            observe_all(-121, &flatline, &[1.0, 1.2, 1.4, 1.5, 5.0]),
+            [None, None, Some(3.0), Some(4.0), None]
+        );
+
+        let exact = detector(DetectorMethod::Flatline, 3, 0.0);
+        assert_eq!(
+            observe_all(-122, &exact, &[1.0, 1.0, 1.1, 1.1, 1.1]),
+            [None, None, None, None, Some(3.0)]
+        );
+    }
+
+    #[test]
+    fn non_finite_input_is_ignored() {
+        let flatline = detector(DetectorMethod::Flatline, 3, 0.0);
+        assert_eq!(
+            observe_all(-131, &flatline, &[2.0, f64::NAN, 2.0, f64::INFINITY, 2.0]),
+            [None, None, None, None, Some(3.0)]
+        );
+
+        let zscore = detector(DetectorMethod::Zscore, 2, 3.0);
+        assert_eq!(observe_all(-132, &zscore, &[f64::NAN; 4]), [None; 4]);
+    }
+
+    #[test]
+    fn alerts_are_rate_limited_per_sensor_and_method() {
+        assert!(should_alert(-141, 0, DetectorMethod::Zscore));
+        assert!(!should_alert(-141, 0, DetectorMethod::Zscore));
+        assert!(should_alert(-141, 1, DetectorMethod::Zscore));
+        assert!(should_alert(-141, 0, DetectorMethod::Flatline));
+        assert!(should_alert(-142, 0, DetectorMethod::Zscore));
+    }
+
+    #[test]
+    fn detector_cache_is_invalidated_by_reset() {
+        let zscore = detector(DetectorMethod::Zscore, 5, 3.0);
+        let Err(generation) = cached_detectors(-151) else {
+            panic!("detectors cached before load");
+        };
+        cache_detectors(-151, generation, vec![zscore]);
+        assert_eq!(cached_detectors(-151).map(|d| d.len()), Ok(1));
+
+        reset(-151);
+        let Err(fresh) = cached_detectors(-151) else {
+            panic!("detectors cached after reset");
+        };
+        cache_detectors(-151, generation, vec![zscore]);
+        assert!(cached_detectors(-151).is_err());
+        cache_detectors(-151, fresh, Vec::new());
+        assert_eq!(cached_detectors(-151).map(|d| d.len()), Ok(0));
+    }
+
+    #[test]
+    fn evict_drops_state_and_cooldown() {
+        let flatline = detector(DetectorMethod::Flatline, 2, 0.0);
+        assert_eq!(observe_all(-161, &flatline, &[1.0, 1.0]), [None, Some(2.0)]);
+        assert!(should_alert(-161, 0, DetectorMethod::Flatline));
+
+        evict(-161);
+        assert_eq!(observe(-161, &flatline, 1.0), None);
+        assert!(should_alert(-161, 0, DetectorMethod::Flatline));
+    }
+}
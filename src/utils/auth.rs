@@ -1,11 +1,91 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
 use jsonwebtoken::{decode, errors::ErrorKind, DecodingKey, Validation};
 use ntex::http::Request;
 
 use crate::{
     constant::{config, messages},
+    logging,
     models::jwt::Claims,
 };
 
+#[derive(Default)]
+struct DeletedUsers {
+    ids: Arc<HashSet<i32>>,
+    marks: HashMap<i32, (bool, u64)>,
+}
+
+static DELETED_USERS: Mutex<Option<DeletedUsers>> = Mutex::new(None);
+static DELETED_USERS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static LOCAL_DELETED_USERS: RefCell<(u64, Arc<HashSet<i32>>)> = RefCell::default();
+}
+
+pub fn deleted_users_version() -> u64 {
+    DELETED_USERS_VERSION.load(Ordering::Acquire)
+}
+
+pub fn deleted_user_ids() -> Vec<i32> {
+    let users = DELETED_USERS.lock().unwrap();
+    users
+        .as_ref()
+        .map(|users| users.ids.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+pub fn set_deleted_users(mut ids: HashSet<i32>, version: u64) {
+    let mut users = DELETED_USERS.lock().unwrap();
+    let users = users.get_or_insert_with(DeletedUsers::default);
+    users.marks.retain(|_, (_, marked)| *marked > version);
+    for (id, (deleted, _)) in &users.marks {
+        if *deleted {
+            ids.insert(*id);
+        } else {
+            ids.remove(id);
+        }
+    }
+    users.ids = Arc::new(ids);
+    DELETED_USERS_VERSION.fetch_add(1, Ordering::Release);
+}
+
+pub fn mark_user_deleted(id: i32, deleted: bool) {
+    let mut users = DELETED_USERS.lock().unwrap();
+    let users = users.get_or_insert_with(DeletedUsers::default);
+    let ids = Arc::make_mut(&mut users.ids);
+    if deleted {
+        ids.insert(id);
+    } else {
+        ids.remove(&id);
+    }
+    let version = DELETED_USERS_VERSION.fetch_add(1, Ordering::Release) + 1;
+    users.marks.insert(id, (deleted, version));
+}
+
+fn is_deleted(id: i32) -> bool {
+    let version = deleted_users_version();
+    LOCAL_DELETED_USERS.with_borrow_mut(|local| {
+        if local.0 != version {
+            let users = DELETED_USERS.lock().unwrap();
+            *local = (
+                deleted_users_version(),
+                users
+                    .as_ref()
+                    .map(|users| users.ids.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        local.1.contains(&id)
+    })
+}
+
 pub async fn verify_jwt(token: &str) -> Result<Claims, &'static str> {
     let validation = Validation::default();
     match decode::<Claims>(
@@ -23,7 +103,11 @@ pub async fn authenticate(req: &Request) -> Result<Claims, &'static str> {
     let token = get_token(req);
     match token {
         Some(t) => match verify_jwt(t).await {
-            Ok(claims) => Ok(claims),
+            Ok(claims) if is_deleted(claims.user_id) => Err(messages::INVALID_TOKEN),
+            Ok(claims) => {
+                logging::set_user(claims.user_id);
+                Ok(claims)
+            }
             Err(err) => Err(err),
         },
         None => Err(messages::INVALID_TOKEN),
@@ -48,3 +132,25 @@ pub fn get_token(req: &Request) -> Option<&str> {
 
     token
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_keeps_marks_made_after_it_started() {
+        let version = deleted_users_version();
+        mark_user_deleted(-1, true);
+        mark_user_deleted(-2, false);
+        set_deleted_users(HashSet::from([-2, -3]), version);
+        assert!(is_deleted(-1));
+        assert!(!is_deleted(-2));
+        assert!(is_deleted(-3));
+
+        let version = deleted_users_version();
+        set_deleted_users(HashSet::from([-2]), version);
+        assert!(!is_deleted(-1));
+        assert!(is_deleted(-2));
+        assert!(!is_deleted(-3));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::HashMap, io, mem, sync::Arc};
+
+use chrono::NaiveDateTime;
+use ntex::{http::header::HeaderValue, util::Bytes};
+use parquet::{
+    basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType},
+    data_type::{DoubleType, Int32Type, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use serde::ser::{SerializeMap, Serializer};
+use sonic_rs::Serialize;
+
+use crate::models::feeds::ExportFormat;
+
+impl ExportFormat {
+    pub fn content_type(&self) -> HeaderValue {
+        match self {
+            ExportFormat::Csv => HeaderValue::from_static("text/csv; charset=utf-8"),
+            ExportFormat::Ndjson => HeaderValue::from_static("application/x-ndjson"),
+            ExportFormat::Parquet => HeaderValue::from_static("application/vnd.apache.parquet"),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+pub fn column_names(sensor_names: &[String]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::with_capacity(sensor_names.len());
+    for (index, name) in sensor_names.iter().enumerate() {
+        let name =
+            if name.is_empty() || name == "time" || name == "node_id" || columns.contains(name) {
+                format!("{}_{}", name, index)
+            } else {
+                name.clone()
+            };
+        columns.push(name);
+    }
+    columns
+}
+
+pub fn group_columns(nodes: &[(i32, Vec<String>)]) -> (Vec<String>, HashMap<i32, Vec<usize>>) {
+    let mut columns: Vec<String> = Vec::new();
+    let mut layouts = HashMap::with_capacity(nodes.len());
+    for (node_id, sensor_names) in nodes {
+        let layout = column_names(sensor_names)
+            .into_iter()
+            .map(
+                |name| match columns.iter().position(|column| *column == name) {
+                    Some(index) => index,
+                    None => {
+                        columns.push(name);
+                        columns.len() - 1
+                    }
+                },
+            )
+            .collect();
+        layouts.insert(*node_id, layout);
+    }
+    (columns, layouts)
+}
+
+fn present(value: &[f64], index: usize) -> Option<f64> {
+    value.get(index).copied().filter(|v| !v.is_nan())
+}
+
+fn csv_field(out: &mut Vec<u8>, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push(b'"');
+        out.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+        out.push(b'"');
+    } else {
+        out.extend_from_slice(field.as_bytes());
+    }
+}
+
+struct NdjsonRow<'a> {
+    columns: &'a [String],
+    node_id: Option<i32>,
+    time: &'a NaiveDateTime,
+    value: &'a [f64],
+}
+
+impl Serialize for NdjsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len() + 2))?;
+        if let Some(node_id) = self.node_id {
+            map.serialize_entry("node_id", &node_id)?;
+        }
+        map.serialize_entry("time", self.time)?;
+        for (index, column) in self.columns.iter().enumerate() {
+            map.serialize_entry(column, &present(self.value, index))?;
+        }
+        map.end()
+    }
+}
+
+pub enum FeedEncoder {
+    Csv {
+        columns: Vec<String>,
+        node_column: bool,
+        header: bool,
+    },
+    Ndjson {
+        columns: Vec<String>,
+        node_column: bool,
+    },
+    Parquet {
+        columns: Vec<String>,
+        node_column: bool,
+        writer: Box<SerializedFileWriter<Vec<u8>>>,
+    },
+}
+
+impl FeedEncoder {
+    pub fn new(format: ExportFormat, columns: Vec<String>, node_column: bool) -> io::Result<Self> {
+        match format {
+            ExportFormat::Csv => Ok(FeedEncoder::Csv {
+                columns,
+                node_column,
+                header: false,
+            }),
+            ExportFormat::Ndjson => Ok(FeedEncoder::Ndjson {
+                columns,
+                node_column,
+            }),
+            ExportFormat::Parquet => {
+                let mut fields = Vec::with_capacity(columns.len() + 2);
+                if node_column {
+                    fields.push(Arc::new(
+                        Type::primitive_type_builder("node_id", PhysicalType::INT32)
+                            .with_repetition(Repetition::REQUIRED)
+                            .build()
+                            .map_err(io::Error::other)?,
+                    ));
+                }
+                fields.push(Arc::new(
+                    Type::primitive_type_builder("time", PhysicalType::INT64)
+                        .with_repetition(Repetition::REQUIRED)
+                        .with_logical_type(Some(LogicalType::Timestamp {
+                            is_adjusted_to_u_t_c: true,
+                            unit: TimeUnit::MICROS(Default::default()),
+                        }))
+                        .build()
+                        .map_err(io::Error::other)?,
+                ));
+                for column in &columns {
+                    fields.push(Arc::new(
+                        Type::primitive_type_builder(column, PhysicalType::DOUBLE)
+                            .with_repetition(Repetition::OPTIONAL)
+                            .build()
+                            .map_err(io::Error::other)?,
+                    ));
+                }
+                let schema = Type::group_type_builder("feed")
+                    .with_fields(fields)
+                    .build()
+                    .map_err(io::Error::other)?;
+                let writer = SerializedFileWriter::new(
+                    Vec::new(),
+                    Arc::new(schema),
+                    Arc::new(WriterProperties::builder().build()),
+                )
+                .map_err(io::Error::other)?;
+                Ok(FeedEncoder::Parquet {
+                    columns,
+                    node_column,
+                    writer: Box::new(writer),
+                })
+            }
+        }
+    }
+
+    pub fn encode(&mut self, rows: &[(i32, NaiveDateTime, Vec<f64>)]) -> io::Result<Bytes> {
+        match self {
+            FeedEncoder::Csv {
+                columns,
+                node_column,
+                header,
+            } => {
+                let mut out = Vec::with_capacity(rows.len() * (columns.len() + 2) * 12);
+                if !*header {
+                    if *node_column {
+                        out.extend_from_slice(b"node_id,");
+                    }
+                    out.extend_from_slice(b"time");
+                    for column in columns.iter() {
+                        out.push(b',');
+                        csv_field(&mut out, column);
+                    }
+                    out.push(b'\n');
+                    *header = true;
+                }
+                for (node_id, time, value) in rows {
+                    if *node_column {
+                        out.extend_from_slice(node_id.to_string().as_bytes());
+                        out.push(b',');
+                    }
+                    out.extend_from_slice(
+                        time.format("%Y-%m-%dT%H:%M:%S%.f").to_string().as_bytes(),
+                    );
+                    for index in 0..columns.len() {
+                        out.push(b',');
+                        if let Some(v) = present(value, index) {
+                            out.extend_from_slice(v.to_string().as_bytes());
+                        }
+                    }
+                    out.push(b'\n');
+                }
+                Ok(Bytes::from(out))
+            }
+            FeedEncoder::Ndjson {
+                columns,
+                node_column,
+            } => {
+                let mut out = Vec::with_capacity(rows.len() * (columns.len() + 2) * 24);
+                for (node_id, time, value) in rows {
+                    let row = NdjsonRow {
+                        columns,
+                        node_id: node_column.then_some(*node_id),
+                        time,
+                        value,
+                    };
+                    sonic_rs::to_writer(&mut out, &row).map_err(io::Error::other)?;
+                    out.push(b'\n');
+                }
+                Ok(Bytes::from(out))
+            }
+            FeedEncoder::Parquet {
+                columns,
+                node_column,
+                writer,
+            } => {
+                if rows.is_empty() {
+                    return Ok(Bytes::new());
+                }
+                let mut row_group = writer.next_row_group().map_err(io::Error::other)?;
+                if *node_column {
+                    if let Some(mut column) = row_group.next_column().map_err(io::Error::other)? {
+                        let node_ids: Vec<i32> =
+                            rows.iter().map(|(node_id, _, _)| *node_id).collect();
+                        column
+                            .typed::<Int32Type>()
+                            .write_batch(&node_ids, None, None)
+                            .map_err(io::Error::other)?;
+                        column.close().map_err(io::Error::other)?;
+                    }
+                }
+                if let Some(mut column) = row_group.next_column().map_err(io::Error::other)? {
+                    let times: Vec<i64> = rows
+                        .iter()
+                        .map(|(_, time, _)| time.and_utc().timestamp_micros())
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&times, None, None)
+                        .map_err(io::Error::other)?;
+                    column.close().map_err(io::Error::other)?;
+                }
+                for index in 0..columns.len() {
+                    if let Some(mut column) = row_group.next_column().map_err(io::Error::other)? {
+                        let values: Vec<f64> = rows
+                            .iter()
+                            .filter_map(|(_, _, v)| present(v, index))
+                            .collect();
+                        let levels: Vec<i16> = rows
+                            .iter()
+                            .map(|(_, _, v)| i16::from(present(v, index).is_some()))
+                            .collect();
+                        column
+                            .typed::<DoubleType>()
+                            .write_batch(&values, Some(&levels), None)
+                            .map_err(io::Error::other)?;
+                        column.close().map_err(io::Error::other)?;
+                    }
+                }
+                row_group.close().map_err(io::Error::other)?;
+                Ok(Bytes::from(mem::take(writer.inner_mut())))
+            }
+        }
+    }
+
+    pub fn finish(self) -> io::Result<Bytes> {
+        match self {
+            FeedEncoder::Csv {
+                columns,
+                node_column,
+                header,
+            } if !header => {
+                let mut encoder = FeedEncoder::Csv {
+                    columns,
+                    node_column,
+                    header,
+                };
+                encoder.encode(&[])
+            }
+            FeedEncoder::Csv { .. } | FeedEncoder::Ndjson { .. } => Ok(Bytes::new()),
+            FeedEncoder::Parquet { writer, .. } => {
+                let out = writer.into_inner().map_err(io::Error::other)?;
+                Ok(Bytes::from(out))
+            }
+        }
+    }
+}
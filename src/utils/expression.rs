@@ -0,0 +1,441 @@
+use crate::constant::{config, messages};
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+type Function = fn(&[f64]) -> f64;
+
+enum Expr {
+    Number(f64),
+    Sensor(usize),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+pub struct Expression(Expr);
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    Comma,
+    Open,
+    Close,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    let exponent_sign = matches!(c, '+' | '-')
+                        && matches!(src[..i].chars().last(), Some('e' | 'E'));
+                    if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E') || exponent_sign) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let number = src[start..end]
+                    .parse()
+                    .map_err(|_| messages::INVALID_EXPRESSION)?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + 1;
+                    chars.next();
+                }
+                tokens.push(Token::Ident(src[start..end].to_ascii_lowercase()));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                chars.next();
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                chars.next();
+            }
+            _ => return Err(messages::INVALID_EXPRESSION),
+        }
+    }
+    Ok(tokens)
+}
+
+fn function(name: &str) -> Option<(Function, usize)> {
+    let function: (Function, usize) = match name {
+        "abs" => (|a| a[0].abs(), 1),
+        "sqrt" => (|a| a[0].sqrt(), 1),
+        "exp" => (|a| a[0].exp(), 1),
+        "ln" => (|a| a[0].ln(), 1),
+        "log10" => (|a| a[0].log10(), 1),
+        "log2" => (|a| a[0].log2(), 1),
+        "sin" => (|a| a[0].sin(), 1),
+        "cos" => (|a| a[0].cos(), 1),
+        "tan" => (|a| a[0].tan(), 1),
+        "asin" => (|a| a[0].asin(), 1),
+        "acos" => (|a| a[0].acos(), 1),
+        "atan" => (|a| a[0].atan(), 1),
+        "floor" => (|a| a[0].floor(), 1),
+        "ceil" => (|a| a[0].ceil(), 1),
+        "round" => (|a| a[0].round(), 1),
+        "pow" => (|a| a[0].powf(a[1]), 2),
+        "atan2" => (|a| a[0].atan2(a[1]), 2),
+        "min" => (|a| a[0].min(a[1]), 2),
+        "max" => (|a| a[0].max(a[1]), 2),
+        _ => return None,
+    };
+    Some(function)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), &'static str> {
+        match self.next() {
+            Some(next) if next == token => Ok(()),
+            _ => Err(messages::INVALID_EXPRESSION),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, &'static str> {
+        self.depth += 1;
+        if self.depth > config::VIRTUAL_SENSOR_MAX_DEPTH {
+            return Err(messages::INVALID_EXPRESSION);
+        }
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(c @ ('+' | '-'))) = self.peek() {
+            let op = if *c == '+' { Op::Add } else { Op::Sub };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        self.depth -= 1;
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, &'static str> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(c @ ('*' | '/' | '%'))) = self.peek() {
+            let op = match c {
+                '*' => Op::Mul,
+                '/' => Op::Div,
+                _ => Op::Rem,
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, &'static str> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => {
+                let base = self.primary()?;
+                if self.peek() == Some(&Token::Op('^')) {
+                    self.pos += 1;
+                    let exponent = self.unary()?;
+                    return Ok(Expr::Binary(Op::Pow, Box::new(base), Box::new(exponent)));
+                }
+                Ok(base)
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, &'static str> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Open) => {
+                let expr = self.expr()?;
+                self.expect(Token::Close)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(index) = name.strip_prefix('s').and_then(|i| i.parse().ok()) {
+                    return Ok(Expr::Sensor(index));
+                }
+                match name.as_str() {
+                    "pi" => return Ok(Expr::Number(std::f64::consts::PI)),
+                    "e" => return Ok(Expr::Number(std::f64::consts::E)),
+                    _ => {}
+                }
+                let (function, arity) = function(&name).ok_or(messages::INVALID_EXPRESSION)?;
+                self.expect(Token::Open)?;
+                let mut args = vec![self.expr()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    args.push(self.expr()?);
+                }
+                self.expect(Token::Close)?;
+                if args.len() != arity {
+                    return Err(messages::INVALID_EXPRESSION);
+                }
+                Ok(Expr::Call(function, args))
+            }
+            _ => Err(messages::INVALID_EXPRESSION),
+        }
+    }
+}
+
+fn max_index(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Number(_) => None,
+        Expr::Sensor(index) => Some(*index),
+        Expr::Neg(expr) => max_index(expr),
+        Expr::Binary(_, lhs, rhs) => max_index(lhs).max(max_index(rhs)),
+        Expr::Call(_, args) => args.iter().filter_map(max_index).max(),
+    }
+}
+
+fn eval(expr: &Expr, values: &[f64]) -> f64 {
+    match expr {
+        Expr::Number(number) => *number,
+        Expr::Sensor(index) => values.get(*index).copied().unwrap_or(f64::NAN),
+        Expr::Neg(expr) => -eval(expr, values),
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (eval(lhs, values), eval(rhs, values));
+            match op {
+                Op::Add => lhs + rhs,
+                Op::Sub => lhs - rhs,
+                Op::Mul => lhs * rhs,
+                Op::Div => lhs / rhs,
+                Op::Rem => lhs % rhs,
+                Op::Pow => lhs.powf(rhs),
+            }
+        }
+        Expr::Call(function, args) => {
+            let args: Vec<f64> = args.iter().map(|arg| eval(arg, values)).collect();
+            function(&args)
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(src: &str) -> Result<Self, &'static str> {
+        if src.len() > config::VIRTUAL_SENSOR_MAX_EXPRESSION_LEN {
+            return Err(messages::INVALID_EXPRESSION);
+        }
+        let mut parser = Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(messages::INVALID_EXPRESSION);
+        }
+        Ok(Expression(expr))
+    }
+
+    pub fn max_index(&self) -> Option<usize> {
+        max_index(&self.0)
+    }
+
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        eval(&self.0, values)
+    }
+}
+
+pub fn append_virtual(
+    value: &mut Vec<f64>,
+    sensors: usize,
+    expressions: &[Expression],
+) -> Result<(), &'static str> {
+    if expressions.is_empty() {
+        return Ok(());
+    }
+    if value.len() != sensors {
+        return Err(messages::SENSOR_VALUE_COUNT_MISMATCH);
+    }
+    for expression in expressions {
+        let result = expression.eval(value);
+        value.push(if result.is_finite() { result } else { f64::NAN });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, values: &[f64]) -> f64 {
+        Expression::parse(src).ok().unwrap().eval(values)
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3", &[]), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", &[]), 9.0);
+        assert_eq!(eval("10 - 4 - 3", &[]), 3.0);
+        assert_eq!(eval("24 / 4 / 2", &[]), 3.0);
+        assert_eq!(eval("7 % 4 * 2", &[]), 6.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", &[]), 512.0);
+        assert_eq!(eval("2 * 3 ^ 2", &[]), 18.0);
+    }
+
+    #[test]
+    fn unary_minus_binds_looser_than_power() {
+        assert_eq!(eval("-2 ^ 2", &[]), -4.0);
+        assert_eq!(eval("(-2) ^ 2", &[]), 4.0);
+        assert_eq!(eval("2 ^ -1", &[]), 0.5);
+        assert_eq!(eval("--3", &[]), 3.0);
+        assert_eq!(eval("+3 - -3", &[]), 6.0);
+    }
+
+    #[test]
+    fn exponent_literals() {
+        assert_eq!(eval("1e-3", &[]), 0.001);
+        assert_eq!(eval("2.5E+2", &[]), 250.0);
+        assert_eq!(eval("1e3-1", &[]), 999.0);
+        assert_eq!(eval(".5", &[]), 0.5);
+        assert!(Expression::parse("1e").is_err());
+        assert!(Expression::parse("1..2").is_err());
+    }
+
+    #[test]
+    fn sensors_constants_and_functions() {
+        assert_eq!(eval("s0 * 1.8 + 32", &[100.0]), 212.0);
+        assert_eq!(eval("max(s0, S1) - min(s0, s1)", &[3.0, 5.0]), 2.0);
+        assert_eq!(eval("round(pi * 100)", &[]), 314.0);
+        assert_eq!(eval("ln(e)", &[]), 1.0);
+        assert_eq!(eval("pow(2, 10)", &[]), 1024.0);
+        assert!(eval("s3", &[1.0]).is_nan());
+    }
+
+    #[test]
+    fn arity_and_syntax_errors() {
+        assert!(Expression::parse("sqrt(1, 2)").is_err());
+        assert!(Expression::parse("pow(2)").is_err());
+        assert!(Expression::parse("max()").is_err());
+        assert!(Expression::parse("foo(1)").is_err());
+        assert!(Expression::parse("sqrt 4").is_err());
+        assert!(Expression::parse("1 +").is_err());
+        assert!(Expression::parse("(1").is_err());
+        assert!(Expression::parse("1)").is_err());
+        assert!(Expression::parse("1 $ 2").is_err());
+        assert!(Expression::parse("").is_err());
+    }
+
+    #[test]
+    fn depth_and_length_limits() {
+        let depth = config::VIRTUAL_SENSOR_MAX_DEPTH;
+        let nested = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        assert!(Expression::parse(&nested(depth - 1)).is_ok());
+        assert!(Expression::parse(&nested(depth)).is_err());
+
+        let len = config::VIRTUAL_SENSOR_MAX_EXPRESSION_LEN;
+        assert!(Expression::parse(&"1".repeat(len)).is_ok());
+        assert!(Expression::parse(&"1".repeat(len + 1)).is_err());
+    }
+
+    #[test]
+    fn max_index_covers_every_node() {
+        let max_index = |src: &str| Expression::parse(src).ok().unwrap().max_index();
+        assert_eq!(max_index("1 + 2"), None);
+        assert_eq!(max_index("s2"), Some(2));
+        assert_eq!(max_index("-s4 + s1"), Some(4));
+        assert_eq!(max_index("s0 ^ s7"), Some(7));
+        assert_eq!(max_index("max(s3, abs(s9))"), Some(9));
+    }
+
+    #[test]
+    fn append_virtual_appends_results_in_order() {
+        let expressions = [
+            Expression::parse("s0 + s1").ok().unwrap(),
+            Expression::parse("s2 * 2").ok().unwrap(),
+        ];
+        let mut value = vec![1.0, 2.0];
+        assert!(append_virtual(&mut value, 2, &expressions).is_ok());
+        assert_eq!(value, [1.0, 2.0, 3.0, 6.0]);
+
+        let mut value = vec![1.0, 2.0, 3.0];
+        assert!(append_virtual(&mut value, 2, &[]).is_ok());
+        assert_eq!(value, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn append_virtual_rejects_value_count_mismatch() {
+        let expressions = [Expression::parse("s0 / s1").ok().unwrap()];
+
+        let mut value = vec![1.0];
+        assert_eq!(
+            append_virtual(&mut value, 2, &expressions),
+            Err(messages::SENSOR_VALUE_COUNT_MISMATCH)
+        );
+        let mut value = vec![1.0, 2.0, 3.0];
+        assert_eq!(
+            append_virtual(&mut value, 2, &expressions),
+            Err(messages::SENSOR_VALUE_COUNT_MISMATCH)
+        );
+    }
+
+    #[test]
+    fn append_virtual_keeps_physical_values_when_a_result_is_not_finite() {
+        let expressions = [
+            Expression::parse("s0 / s1").ok().unwrap(),
+            Expression::parse("ln(s1)").ok().unwrap(),
+            Expression::parse("s0 + 1").ok().unwrap(),
+            Expression::parse("s2 * 2").ok().unwrap(),
+        ];
+        let mut value = vec![1.0, 0.0];
+        assert_eq!(append_virtual(&mut value, 2, &expressions), Ok(()));
+        assert_eq!(value.len(), 6);
+        assert_eq!(value[..2], [1.0, 0.0]);
+        assert!(value[2].is_nan());
+        assert!(value[3].is_nan());
+        assert_eq!(value[4], 2.0);
+        assert!(value[5].is_nan());
+
+        let mut value = vec![0.0, 0.0];
+        assert_eq!(append_virtual(&mut value, 2, &expressions[..1]), Ok(()));
+        assert!(value[2].is_nan());
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, SeekFrom},
+    path::PathBuf,
+};
+
+use futures::{channel::mpsc::Sender, SinkExt, Stream, StreamExt};
+use ntex::util::Bytes;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::{constant::config, utils::generate_string};
+
+pub enum UploadError {
+    Empty,
+    TooLarge,
+    Body(String),
+    Io(io::Error),
+}
+
+pub struct Upload {
+    pub path: PathBuf,
+    pub checksum: String,
+    pub size: i64,
+}
+
+impl Upload {
+    pub async fn discard(self) {
+        let _ = fs::remove_file(&self.path).await;
+    }
+}
+
+pub fn path(id: i32) -> PathBuf {
+    PathBuf::from(config::FIRMWARE_DIR).join(format!("{id}.bin"))
+}
+
+pub async fn receive<S, E>(mut body: S) -> Result<Upload, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    fs::create_dir_all(config::FIRMWARE_DIR)
+        .await
+        .map_err(UploadError::Io)?;
+    let path = PathBuf::from(config::FIRMWARE_DIR).join(format!("{}.part", generate_string(16)));
+    let result = async {
+        let mut file = File::create(&path).await.map_err(UploadError::Io)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| UploadError::Body(e.to_string()))?;
+            size += chunk.len() as u64;
+            if size > config::FIRMWARE_MAX_BYTES {
+                return Err(UploadError::TooLarge);
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(UploadError::Io)?;
+        }
+        if size == 0 {
+            return Err(UploadError::Empty);
+        }
+        file.sync_all().await.map_err(UploadError::Io)?;
+        Ok((format!("{:x}", hasher.finalize()), size as i64))
+    }
+    .await;
+    match result {
+        Ok((checksum, size)) => Ok(Upload {
+            path,
+            checksum,
+            size,
+        }),
+        Err(e) => {
+            let _ = fs::remove_file(&path).await;
+            Err(e)
+        }
+    }
+}
+
+pub fn parse_range(header: Option<&str>, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return Ok(None),
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return Err(()),
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return Ok(None),
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return Ok(None),
+        },
+    };
+    if len == 0 || start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+pub async fn stream_file(
+    path: PathBuf,
+    start: u64,
+    len: u64,
+    mut tx: Sender<Result<Bytes, Box<dyn Error>>>,
+) {
+    let result: io::Result<()> = async {
+        let mut file = File::open(&path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut remaining = len;
+        let mut buf = vec![0; config::STREAM_CHUNK_SIZE];
+        while remaining > 0 {
+            let want = buf.len().min(remaining as usize);
+            let read = file.read(&mut buf[..want]).await?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            remaining -= read as u64;
+            tx.send(Ok(Bytes::copy_from_slice(&buf[..read])))
+                .await
+                .map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!(error = %e, path = %path.display(), "firmware download failed");
+        let _ = tx.send(Err(Box::new(e))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_bounded_and_open_ended() {
+        assert_eq!(parse_range(Some("bytes=0-99"), 1000), Ok(Some((0, 99))));
+        assert_eq!(
+            parse_range(Some(" bytes=10 - 19 "), 1000),
+            Ok(Some((10, 19)))
+        );
+        assert_eq!(parse_range(Some("bytes=100-"), 1000), Ok(Some((100, 999))));
+        assert_eq!(parse_range(Some("bytes=999-"), 1000), Ok(Some((999, 999))));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range(Some("bytes=-100"), 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range(Some("bytes=-5000"), 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range(Some("bytes=-0"), 1000), Err(()));
+    }
+
+    #[test]
+    fn parse_range_clamps_end_to_length() {
+        assert_eq!(
+            parse_range(Some("bytes=500-5000"), 1000),
+            Ok(Some((500, 999)))
+        );
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable_start() {
+        assert_eq!(parse_range(Some("bytes=1000-"), 1000), Err(()));
+        assert_eq!(parse_range(Some("bytes=1000-2000"), 1000), Err(()));
+        assert_eq!(parse_range(Some("bytes=0-"), 0), Err(()));
+    }
+
+    #[test]
+    fn parse_range_ignores_multiple_ranges() {
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 1000), Ok(None));
+    }
+
+    #[test]
+    fn parse_range_ignores_malformed_headers() {
+        assert_eq!(parse_range(None, 1000), Ok(None));
+        assert_eq!(parse_range(Some("items=0-1"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=abc"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=-"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=a-1"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=1-a"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=-a"), 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=5-2"), 1000), Ok(None));
+    }
+}
@@ -1,21 +1,30 @@
-use ntex::http::header::{CONTENT_TYPE, SERVER};
+use std::{io, net::IpAddr, str};
+
+use futures::{
+    channel::mpsc::{Receiver, Sender},
+    SinkExt, StreamExt,
+};
+use ntex::http::header::{CONTENT_LENGTH, CONTENT_TYPE, SERVER};
 use ntex::{
-    http::{Response, StatusCode},
+    http::{Request, Response, StatusCode},
     util::{Bytes, BytesMut},
 };
 
+use serde::de::DeserializeOwned;
 use sonic_rs::{to_writer, Serialize};
 
-use crate::models::response::ApiResponse;
+use crate::{
+    constant::{config, messages},
+    models::response::{ApiResponse, Data},
+};
 
-use super::{reserve, BytesWriter, HDR_JSON_CONTENT_TYPE, HDR_SERVER};
+use super::{BytesWriter, HDR_JSON_CONTENT_TYPE, HDR_SERVER};
 
 pub fn serialize_response<T: Serialize>(
     response: ApiResponse<T>,
     status: StatusCode,
 ) -> (Bytes, StatusCode) {
-    let mut body = BytesMut::with_capacity(10 * 1024 * 1024);
-    reserve(&mut body, 10 * 1024 * 1024);
+    let mut body = BytesMut::with_capacity(config::RESPONSE_INITIAL_CAPACITY);
     to_writer(BytesWriter(&mut body), &response).unwrap();
 
     (body.split().freeze(), status)
@@ -30,14 +39,140 @@ pub fn response_json(data: Bytes, status: StatusCode) -> Response {
     res
 }
 
+pub fn response_json_stream(body: Receiver<io::Result<Bytes>>) -> Response {
+    let mut res = Response::Ok().streaming(body);
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HDR_JSON_CONTENT_TYPE);
+    res.headers_mut().insert(SERVER, HDR_SERVER);
+
+    res
+}
+
+pub struct JsonStream {
+    buf: BytesMut,
+    tx: Sender<io::Result<Bytes>>,
+}
+
+impl JsonStream {
+    pub fn new(tx: Sender<io::Result<Bytes>>) -> Self {
+        JsonStream {
+            buf: BytesMut::with_capacity(config::STREAM_CHUNK_SIZE),
+            tx,
+        }
+    }
+
+    pub async fn write_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.buf.extend_from_slice(data);
+        self.flush_full().await
+    }
+
+    pub async fn write<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        to_writer(BytesWriter(&mut self.buf), value).map_err(io::Error::other)?;
+        self.flush_full().await
+    }
+
+    async fn flush_full(&mut self) -> io::Result<()> {
+        if self.buf.len() < config::STREAM_CHUNK_SIZE {
+            return Ok(());
+        }
+        let chunk = self.buf.split().freeze();
+        self.tx.send(Ok(chunk)).await.map_err(io::Error::other)
+    }
+
+    pub async fn finish(mut self, result: io::Result<()>) {
+        let chunk = match result {
+            Ok(()) => Ok(self.buf.split().freeze()),
+            Err(e) => Err(e),
+        };
+        let _ = self.tx.send(chunk).await;
+    }
+}
+
+fn error_json(message: &str, status: StatusCode) -> Response {
+    let response: ApiResponse<()> = ApiResponse {
+        message,
+        data: Data::None,
+    };
+    let (data, status) = serialize_response(response, status);
+    response_json(data, status)
+}
+
+fn is_json_content_type(req: &Request) -> bool {
+    let Some(content_type) = req.headers().get(CONTENT_TYPE) else {
+        return true;
+    };
+    content_type
+        .to_str()
+        .ok()
+        .and_then(|value| value.split(';').next())
+        .map(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
+        .unwrap_or(false)
+}
+
+pub async fn read_json<T: DeserializeOwned>(req: &mut Request) -> Result<T, Response> {
+    if !is_json_content_type(req) {
+        return Err(error_json(
+            messages::UNSUPPORTED_MEDIA_TYPE,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ));
+    }
+    let length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if length.is_some_and(|length| length > config::MAX_PAYLOAD_BYTES) {
+        return Err(error_json(
+            messages::PAYLOAD_TOO_LARGE,
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+
+    let mut buf = BytesMut::with_capacity(length.unwrap_or(0));
+    let payload = req.payload();
+    while let Some(chunk) = payload.next().await {
+        let chunk =
+            chunk.map_err(|_| error_json(messages::INVALID_PAYLOAD, StatusCode::BAD_REQUEST))?;
+        if buf.len() + chunk.len() > config::MAX_PAYLOAD_BYTES {
+            return Err(error_json(
+                messages::PAYLOAD_TOO_LARGE,
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+
+    let data = str::from_utf8(&buf)
+        .map_err(|_| error_json(messages::INVALID_UTF8, StatusCode::BAD_REQUEST))?;
+    sonic_rs::from_str(data)
+        .map_err(|_| error_json(messages::INVALID_PAYLOAD, StatusCode::BAD_REQUEST))
+}
+
 pub fn extract_id_from_path(path: &str, prefix: &str) -> Option<i32> {
     path.strip_prefix(prefix)
         .and_then(|p| p.strip_suffix("/"))
         .and_then(|id_str| id_str.parse::<i32>().ok())
 }
 
+pub fn extract_id_from_subpath(path: &str, prefix: &str, suffix: &str) -> Option<i32> {
+    path.strip_prefix(prefix)
+        .and_then(|p| p.strip_suffix(suffix))
+        .and_then(|id_str| id_str.parse::<i32>().ok())
+}
+
 pub fn extract_jwt_from_path(path: &str, prefix: &str) -> Option<String> {
     path.strip_prefix(prefix)
         .and_then(|p| p.strip_suffix("/"))
         .map(|id_str| id_str.to_string())
 }
+
+pub fn client_ip(req: &Request) -> Option<IpAddr> {
+    req.peer_addr().map(|addr| addr.ip())
+}
+
+pub fn parse_query<T: DeserializeOwned>(req: &Request) -> Option<T> {
+    serde_urlencoded::from_str(req.uri().query().unwrap_or("")).ok()
+}
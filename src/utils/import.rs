@@ -0,0 +1,157 @@
+use chrono::{DateTime, NaiveDateTime};
+use sonic_rs::{JsonContainerTrait, JsonValueTrait, Value};
+
+use crate::{constant::messages, models::feeds::ImportFormat};
+
+pub struct FeedParser {
+    format: ImportFormat,
+    columns: Vec<String>,
+    header: Option<Vec<Option<usize>>>,
+}
+
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+pub fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+}
+
+fn into_value(values: Vec<Option<f64>>) -> Result<Vec<f64>, &'static str> {
+    let len = values
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |i| i + 1);
+    if len == 0 {
+        return Err(messages::IMPORT_NO_VALUES);
+    }
+    values[..len]
+        .iter()
+        .map(|v| v.ok_or(messages::IMPORT_MISSING_VALUE))
+        .collect()
+}
+
+impl FeedParser {
+    pub fn new(format: ImportFormat, columns: Vec<String>) -> Self {
+        FeedParser {
+            format,
+            columns,
+            header: None,
+        }
+    }
+
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    pub fn parse_header(&mut self, line: &str) -> Result<(), &'static str> {
+        let fields = split_csv_line(line).ok_or(messages::IMPORT_INVALID_HEADER)?;
+        let mut header = Vec::with_capacity(fields.len());
+        let mut has_time = false;
+        for field in fields {
+            if field == "time" && !has_time {
+                has_time = true;
+                header.push(None);
+            } else {
+                match self.column_index(&field) {
+                    Some(index) if !header.contains(&Some(index)) => header.push(Some(index)),
+                    _ => return Err(messages::IMPORT_INVALID_HEADER),
+                }
+            }
+        }
+        if !has_time {
+            return Err(messages::IMPORT_INVALID_HEADER);
+        }
+        self.header = Some(header);
+        Ok(())
+    }
+
+    pub fn needs_header(&self) -> bool {
+        matches!(self.format, ImportFormat::Csv) && self.header.is_none()
+    }
+
+    pub fn parse_line(&self, line: &str) -> Result<(NaiveDateTime, Vec<f64>), &'static str> {
+        match self.format {
+            ImportFormat::Csv => self.parse_csv_line(line),
+            ImportFormat::Ndjson => self.parse_ndjson_line(line),
+        }
+    }
+
+    fn parse_csv_line(&self, line: &str) -> Result<(NaiveDateTime, Vec<f64>), &'static str> {
+        let header = self
+            .header
+            .as_ref()
+            .ok_or(messages::IMPORT_INVALID_HEADER)?;
+        let fields = split_csv_line(line).ok_or(messages::IMPORT_INVALID_LINE)?;
+        if fields.len() != header.len() {
+            return Err(messages::IMPORT_INVALID_LINE);
+        }
+        let mut time = None;
+        let mut values = vec![None; self.columns.len()];
+        for (field, column) in fields.iter().zip(header) {
+            match column {
+                None => time = Some(parse_time(field).ok_or(messages::IMPORT_INVALID_TIME)?),
+                Some(_) if field.is_empty() => (),
+                Some(index) => {
+                    let value = field
+                        .parse::<f64>()
+                        .map_err(|_| messages::IMPORT_INVALID_VALUE)?;
+                    values[*index] = Some(value);
+                }
+            }
+        }
+        Ok((
+            time.ok_or(messages::IMPORT_INVALID_TIME)?,
+            into_value(values)?,
+        ))
+    }
+
+    fn parse_ndjson_line(&self, line: &str) -> Result<(NaiveDateTime, Vec<f64>), &'static str> {
+        let row: Value = sonic_rs::from_str(line).map_err(|_| messages::IMPORT_INVALID_LINE)?;
+        let row = row.as_object().ok_or(messages::IMPORT_INVALID_LINE)?;
+        let mut time = None;
+        let mut values = vec![None; self.columns.len()];
+        for (key, value) in row.iter() {
+            if key == "time" {
+                let parsed = value.as_str().and_then(parse_time);
+                time = Some(parsed.ok_or(messages::IMPORT_INVALID_TIME)?);
+                continue;
+            }
+            let index = self
+                .column_index(key)
+                .ok_or(messages::IMPORT_UNKNOWN_COLUMN)?;
+            if value.is_null() {
+                continue;
+            }
+            values[index] = Some(value.as_f64().ok_or(messages::IMPORT_INVALID_VALUE)?);
+        }
+        Ok((
+            time.ok_or(messages::IMPORT_INVALID_TIME)?,
+            into_value(values)?,
+        ))
+    }
+}
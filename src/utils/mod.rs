@@ -15,14 +15,20 @@ pub const BODY_PLAIN_TEXT: Bytes = Bytes::from_static(b"Hello, World!");
 const HW: usize = 128 * 1024;
 pub const SIZE: usize = 27;
 
+pub mod anomaly;
 pub mod auth;
+pub mod export;
+pub mod expression;
+pub mod firmware;
 pub mod http;
+pub mod import;
+pub mod shadow;
 
 pub fn generate_string(len: usize) -> String {
     let mut s = String::with_capacity(len);
     let mut rng = nanorand::WyRand::new();
     for _ in 0..len {
-        let random_char = (b'a' as i32 + rng.generate_range(0..26) as i32) as u8 as char;
+        let random_char = (b'a' as i32 + rng.generate_range(0..26)) as u8 as char;
         s.push(random_char);
     }
     s
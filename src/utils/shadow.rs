@@ -0,0 +1,42 @@
+use sonic_rs::{JsonContainerTrait, JsonValueMutTrait, JsonValueTrait, Object, Value};
+
+pub fn empty() -> Value {
+    Object::new().into_value()
+}
+
+pub fn merge(target: &mut Value, patch: &Value) {
+    let Some(patch) = patch.as_object() else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = empty();
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch.iter() {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge(target.entry(key).or_default(), value);
+        }
+    }
+}
+
+pub fn delta(desired: &Value, reported: &Value) -> Option<Value> {
+    match (desired.as_object(), reported.as_object()) {
+        (Some(desired), Some(reported)) => {
+            let mut delta = Object::new();
+            for (key, value) in desired.iter() {
+                let diff = match reported.get(&key) {
+                    Some(current) => self::delta(value, current),
+                    None => Some(value.clone()),
+                };
+                if let Some(diff) = diff {
+                    delta.insert(key, diff);
+                }
+            }
+            (!delta.is_empty()).then(|| delta.into_value())
+        }
+        _ => (desired != reported).then(|| desired.clone()),
+    }
+}